@@ -0,0 +1,57 @@
+use tel_cst::lex;
+use tel_cst::TokenKind;
+
+/// Line comment in the source, which the parser discards.
+#[derive(Debug, PartialEq)]
+pub struct Comment {
+    /// Byte position of the `#`.
+    pub pos: u32,
+    /// The comment including `#`, without trailing whitespace.
+    pub text: String,
+    /// Whether the comment has been printed already.
+    pub done: bool,
+}
+
+impl Comment {
+    pub fn end(&self) -> u32 {
+        self.pos + self.text.len() as u32
+    }
+}
+
+/// Find all `#` comments that are not inside a text literal.
+pub fn find_comments(source: &str) -> Vec<Comment> {
+    lex(source).into_iter()
+        .filter(|token| token.kind == TokenKind::Comment)
+        .map(|token| Comment {
+            pos: token.span.start,
+            text: token.text(source).trim_end().to_owned(),
+            done: false,
+        })
+        .collect()
+}
+
+/// Whether the code between two items contains an empty line.
+pub fn has_blank_line(gap: &str) -> bool {
+    let lines = gap.split('\n').collect::<Vec<_>>();
+    lines.len() > 2 && lines[1..lines.len() - 1].iter().any(|line| line.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_text() {
+        let comments = find_comments("x = \"#no\" # yes  \n'#no' #again");
+        let found = comments.iter().map(|comment| (comment.pos, comment.text.as_str())).collect::<Vec<_>>();
+        assert_eq!(found, vec![(10, "# yes"), (24, "#again")]);
+    }
+
+    #[test]
+    fn blank_lines() {
+        assert!(has_blank_line("\n\n"));
+        assert!(has_blank_line("  \n \t \n  "));
+        assert!(!has_blank_line("\n"));
+        assert!(!has_blank_line(" # text\n"));
+    }
+}
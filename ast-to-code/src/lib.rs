@@ -0,0 +1,25 @@
+//! Print an [Ast] as canonical Tel source code.
+//! * One statement per line, separated by newlines instead of `;`.
+//! * Four spaces of indentation per block.
+//! * Parentheses only where precedence needs them.
+//! * Lambdas as last argument are written as trailing closures.
+//! * Long method chains are broken before `.`, which does not need `...` continuation.
+
+use tel_ast::Ast;
+
+use self::printer::Printer;
+
+mod comments;
+mod printer;
+
+/// Canonical code for the ast, without comments, which are not part of the ast.
+pub fn ast_to_code(ast: &Ast) -> String {
+    Printer::new(None).file(ast)
+}
+
+/// Canonical code for the ast that was parsed from `source`, keeping the comments and
+/// single blank lines from the source. Comments in the middle of a statement are moved
+/// to their own line before that statement.
+pub fn format_code(ast: &Ast, source: &str) -> String {
+    Printer::new(Some(source)).file(ast)
+}
@@ -0,0 +1,871 @@
+use tel_ast::AssignmentDest;
+use tel_ast::AssignmentKw;
+use tel_ast::Assignments;
+use tel_ast::Ast;
+use tel_ast::BinOpCode;
+use tel_ast::Block;
+use tel_ast::Closure;
+use tel_ast::Enum;
+use tel_ast::EnumVariant;
+use tel_ast::Expr;
+use tel_ast::ExprKind;
+use tel_ast::Impl;
+use tel_ast::Import;
+use tel_ast::Interface;
+use tel_ast::Invoke;
+use tel_ast::MatchArm;
+use tel_ast::Method;
+use tel_ast::SetField;
+use tel_ast::Signature;
+use tel_ast::Struct;
+use tel_ast::TextPart;
+use tel_ast::Type;
+use tel_ast::UnaryOpCode;
+use tel_common::Span;
+
+use crate::comments::find_comments;
+use crate::comments::has_blank_line;
+use crate::comments::Comment;
+
+const INDENT: &str = "    ";
+/// Method chains longer than this are broken over multiple lines.
+const MAX_WIDTH: usize = 100;
+/// Closures with a single expression up to this length are kept on one line.
+const MAX_INLINE_BODY: usize = 60;
+
+// Precedence levels, matching the grammar, higher binds stronger.
+const CONTROL_LEVEL: u8 = 0;
+const DOT_LEVEL: u8 = 5;
+const UNARY_LEVEL: u8 = 6;
+const SINGLE_LEVEL: u8 = 7;
+
+pub struct Printer<'a> {
+    source: Option<&'a str>,
+    comments: Vec<Comment>,
+    /// Quote of the text whose code is being printed, which texts inside that code cannot use.
+    quote: Option<char>,
+}
+
+/// Output for a sequence of statements or members, which all have the same indentation.
+struct Lines {
+    out: String,
+    depth: usize,
+    /// Start of the source that belongs to this sequence, comments before it belong to the parent.
+    lo: u32,
+    /// End of the previous item in the source, to find blank lines.
+    prev_end: Option<u32>,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(source: Option<&'a str>) -> Self {
+        Printer {
+            source,
+            comments: source.map(find_comments).unwrap_or_default(),
+            quote: None,
+        }
+    }
+
+    pub fn file(&mut self, ast: &Ast) -> String {
+        let mut lines = Lines { out: String::new(), depth: 0, lo: 0, prev_end: None };
+        for import in &ast.imports {
+            self.push(&mut lines, import.span, &import_line(import));
+        }
+        for block in &ast.blocks {
+            let text = self.block(block, 0);
+            self.push(&mut lines, block.span(), &text);
+        }
+        self.finish(&mut lines, u32::MAX);
+        lines.out
+    }
+
+    fn statements(&mut self, blocks: &[Block], depth: usize, lo: u32, hi: u32) -> String {
+        let mut lines = Lines { out: String::new(), depth, lo, prev_end: None };
+        for block in blocks {
+            let text = self.block(block, depth);
+            self.push(&mut lines, block.span(), &text);
+        }
+        self.finish(&mut lines, hi);
+        lines.out
+    }
+
+    /// Add a line for an item, after the comments before it. Comments inside the item that were
+    /// not printed as part of a nested block are moved to before the item.
+    fn push(&mut self, lines: &mut Lines, span: Span, text: &str) {
+        let mut inside = Vec::new();
+        for ix in 0..self.comments.len() {
+            let comment = &self.comments[ix];
+            if comment.done || comment.pos < lines.lo || comment.pos >= span.end {
+                continue;
+            }
+            if comment.pos >= span.start {
+                inside.push(ix);
+                continue;
+            }
+            self.separate(lines, comment.pos);
+            self.emit_comment(lines, ix);
+            lines.prev_end = Some(self.comments[ix].end());
+        }
+        self.separate(lines, span.start);
+        for ix in inside {
+            self.emit_comment(lines, ix);
+        }
+        push_indent(&mut lines.out, lines.depth);
+        lines.out.push_str(text);
+        lines.out.push('\n');
+        lines.prev_end = Some(span.end);
+    }
+
+    /// Add the comments after the last item.
+    fn finish(&mut self, lines: &mut Lines, hi: u32) {
+        for ix in 0..self.comments.len() {
+            let comment = &self.comments[ix];
+            if comment.done || comment.pos < lines.lo || comment.pos >= hi {
+                continue;
+            }
+            self.separate(lines, comment.pos);
+            self.emit_comment(lines, ix);
+            lines.prev_end = Some(self.comments[ix].end());
+        }
+    }
+
+    fn emit_comment(&mut self, lines: &mut Lines, ix: usize) {
+        let comment = &mut self.comments[ix];
+        comment.done = true;
+        push_indent(&mut lines.out, lines.depth);
+        lines.out.push_str(&comment.text);
+        lines.out.push('\n');
+    }
+
+    /// Keep (at most one) blank line if the source had one since the previous item.
+    fn separate(&self, lines: &mut Lines, pos: u32) {
+        let (Some(source), Some(prev)) = (self.source, lines.prev_end) else {
+            return;
+        };
+        if prev < pos && has_blank_line(&source[prev as usize..pos as usize]) {
+            lines.out.push('\n');
+        }
+    }
+
+    fn has_comments(&self, lo: u32, hi: u32) -> bool {
+        self.comments.iter().any(|comment| !comment.done && lo <= comment.pos && comment.pos < hi)
+    }
+
+    /// Position of the last occurrence of a keyword in the source range, outside of comments.
+    fn find_keyword(&self, keyword: &str, lo: u32, hi: u32) -> Option<u32> {
+        let source = self.source?;
+        source[lo as usize..hi as usize].rmatch_indices(keyword)
+            .map(|(ix, _)| lo + ix as u32)
+            .find(|&pos| !self.comments.iter().any(|comment| comment.pos <= pos && pos < comment.end()))
+    }
+
+    fn block(&mut self, block: &Block, depth: usize) -> String {
+        match block {
+            Block::Assigns(assign) => self.assignments(assign, depth),
+            Block::Expression(expr) => self.expr(expr, depth),
+            Block::Return(expr) => format!("return {}", self.expr(expr, depth)),
+            Block::SetField(set) => self.set_field(set, depth),
+            Block::Struct(strct) => self.strct(strct, depth),
+            Block::Enum(enm) => self.enm(enm, depth),
+            Block::Interface(interface) => self.interface(interface, depth),
+            Block::Impl(imp) => self.imp(imp, depth),
+        }
+    }
+
+    fn assignments(&mut self, assign: &Assignments, depth: usize) -> String {
+        let mut out = public(assign.public).to_owned();
+        match assign.op {
+            None => for dest in &assign.dest {
+                out.push_str(&assign_dest(dest));
+                out.push_str(" = ");
+            },
+            Some(op) => {
+                debug_assert!(assign.dest.len() == 1, "compound assignment to multiple variables");
+                let dest = &assign.dest[0];
+                out.push_str(&assign_dest(dest));
+                let Some(symbol) = compound_symbol(op) else {
+                    let value = self.operand(&assign.value, bin_op_level(op) + 1, depth);
+                    return format!("{out} = {} {} {value}", dest.target, bin_op_symbol(op))
+                };
+                out.push_str(symbol);
+            }
+        }
+        out.push_str(&self.expr(&assign.value, depth));
+        out
+    }
+
+    fn text(&mut self, parts: &[TextPart], depth: usize) -> String {
+        let literal = parts.iter()
+            .filter_map(|part| match part {
+                TextPart::Text(text) => Some(text.as_str()),
+                TextPart::Code(_) => None,
+            })
+            .collect::<String>();
+        let quote = match self.quote {
+            Some('"') => '\'',
+            Some(_) => '"',
+            None if literal.contains('"') && !literal.contains('\'') => '\'',
+            None => '"',
+        };
+        let outer = self.quote.replace(quote);
+        let mut out = String::from(quote);
+        for part in parts {
+            match part {
+                TextPart::Text(text) => push_escaped(&mut out, text, quote, outer),
+                TextPart::Code(expr) => {
+                    out.push('{');
+                    out.push_str(&self.expr(expr, depth));
+                    out.push('}');
+                }
+            }
+        }
+        self.quote = outer;
+        out.push(quote);
+        out
+    }
+
+    fn set_field(&mut self, set: &SetField, depth: usize) -> String {
+        let target = self.operand(&set.target, DOT_LEVEL, depth);
+        match set.op {
+            None => format!("{target}.{} = {}", set.field, self.expr(&set.value, depth)),
+            Some(op) => match compound_symbol(op) {
+                Some(symbol) => format!("{target}.{}{symbol}{}", set.field, self.expr(&set.value, depth)),
+                None => {
+                    let value = self.operand(&set.value, bin_op_level(op) + 1, depth);
+                    format!("{target}.{0} = {target}.{0} {1} {value}", set.field, bin_op_symbol(op))
+                }
+            },
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, depth: usize) -> String {
+        match &expr.kind {
+            ExprKind::Int(nr) => format!("{nr}"),
+            // floats always have a fraction, otherwise they would be read as integers
+            ExprKind::Float(nr) if nr.fract() == 0.0 => format!("{nr}.0"),
+            ExprKind::Float(nr) => format!("{nr}"),
+            ExprKind::Text(text) => self.text(&[TextPart::Text(text.clone())], depth),
+            ExprKind::Interpolate(parts) => self.text(parts, depth),
+            ExprKind::List(items) => format!("[{}]", items.iter()
+                .map(|item| self.expr(item, depth))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            ExprKind::Map(entries) if entries.is_empty() => "{:}".to_owned(),
+            ExprKind::Map(entries) => format!("{{{}}}", entries.iter()
+                .map(|(key, value)| format!("{}: {}", self.map_key(key, depth), self.expr(value, depth)))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            ExprKind::Index(base, index) => format!("{}[{}]", self.operand(base, DOT_LEVEL, depth), self.expr(index, depth)),
+            ExprKind::Propagate(inner) => format!("{}?", self.operand(inner, DOT_LEVEL, depth)),
+            ExprKind::BinOp(op, left, right) => {
+                let op_level = bin_op_level(*op);
+                let left = self.operand(left, op_level, depth);
+                let right = self.operand(right, op_level + 1, depth);
+                format!("{left} {} {right}", bin_op_symbol(*op))
+            }
+            ExprKind::UnaryOp(op, inner) => {
+                let symbol = match op {
+                    UnaryOpCode::Not => "!",
+                    UnaryOpCode::Min => "-",
+                };
+                format!("{symbol}{}", self.operand(inner, SINGLE_LEVEL, depth))
+            }
+            ExprKind::Invoke(invoke) => self.invoke(invoke, depth),
+            ExprKind::Dot(..) => self.chain(expr, depth),
+            ExprKind::Closure(closure) => self.closure(closure, depth),
+            ExprKind::If(branches, otherwise) => {
+                let mut out = String::new();
+                let else_lo = match otherwise {
+                    Some(otherwise) => {
+                        let last_cond_end = branches.last().map(|(cond, _)| cond.span.end).unwrap_or(expr.span.start);
+                        let body_start = otherwise.first().map(|block| block.span().start).unwrap_or(expr.span.end);
+                        self.find_keyword("else", last_cond_end, body_start).unwrap_or(body_start)
+                    }
+                    None => expr.span.end,
+                };
+                for (ix, (cond, body)) in branches.iter().enumerate() {
+                    out.push_str(if ix == 0 { "if (" } else { " elif (" });
+                    out.push_str(&self.expr(cond, depth));
+                    out.push_str(") ");
+                    let hi = branches.get(ix + 1).map(|(next, _)| next.span.start).unwrap_or(else_lo);
+                    out.push_str(&self.body(body, depth, cond.span.end, hi));
+                }
+                if let Some(otherwise) = otherwise {
+                    out.push_str(" else ");
+                    out.push_str(&self.body(otherwise, depth, else_lo, expr.span.end));
+                }
+                out
+            }
+            ExprKind::While(cond, body) => format!(
+                "while ({}) {}",
+                self.expr(cond, depth),
+                self.body(body, depth, cond.span.end, expr.span.end),
+            ),
+            ExprKind::ForEach(dest, iter, body) => format!(
+                "for ({} in {}) {}",
+                assign_dest(dest),
+                self.expr(iter, depth),
+                self.body(body, depth, iter.span.end, expr.span.end),
+            ),
+            ExprKind::Match(value, arms, otherwise) => self.match_expr(value, arms, otherwise.as_deref(), expr.span, depth),
+        }
+    }
+
+    /// Match with each arm on its own line, and `else` last.
+    fn match_expr(&mut self, value: &Expr, arms: &[MatchArm], otherwise: Option<&[Block]>, span: Span, depth: usize) -> String {
+        let header = format!("match ({})", self.expr(value, depth));
+        if arms.is_empty() && otherwise.is_none() && !self.has_comments(value.span.end, span.end) {
+            return format!("{header} {{}}");
+        }
+        let mut lines = Lines { out: String::new(), depth: depth + 1, lo: value.span.end, prev_end: None };
+        for arm in arms {
+            let pattern = match &arm.binding {
+                Some(binding) => format!("{} {binding}", arm.variant),
+                None => arm.variant.to_string(),
+            };
+            let body = self.short_body(&arm.body, depth + 1, arm.span);
+            self.push(&mut lines, arm.span, &format!("{pattern} {body}"));
+        }
+        if let Some(otherwise) = otherwise {
+            let arms_end = arms.last().map_or(value.span.end, |arm| arm.span.end);
+            let else_lo = self.find_keyword("else", arms_end, span.end).unwrap_or(arms_end);
+            let body = self.short_body(otherwise, depth + 1, Span { start: else_lo, end: span.end });
+            self.push(&mut lines, Span { start: else_lo, end: span.end }, &format!("else {body}"));
+        }
+        self.finish(&mut lines, span.end);
+        members(header, lines, depth)
+    }
+
+    /// Only literals can be map keys without parentheses, because a name followed by `:` is a typed assignment.
+    fn map_key(&mut self, key: &Expr, depth: usize) -> String {
+        let code = self.expr(key, depth);
+        match key.kind {
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Text(_) | ExprKind::Interpolate(_) => code,
+            _ => format!("({code})"),
+        }
+    }
+
+    /// Expression that needs at least the given precedence level to be used without parentheses.
+    fn operand(&mut self, expr: &Expr, min_level: u8, depth: usize) -> String {
+        let code = self.expr(expr, depth);
+        if expr_level(expr) < min_level {
+            format!("({code})")
+        } else {
+            code
+        }
+    }
+
+    /// Dot-access chain, broken before each `.` if it is too long for one line.
+    fn chain(&mut self, expr: &Expr, depth: usize) -> String {
+        let mut links = Vec::new();
+        let mut base = expr;
+        while let ExprKind::Dot(inner, invoke) = &base.kind {
+            links.push(invoke);
+            base = inner;
+        }
+        links.reverse();
+        let base = self.operand(base, DOT_LEVEL, depth);
+        let links = links.into_iter()
+            .map(|invoke| self.invoke(invoke, depth))
+            .collect::<Vec<_>>();
+        let flat_len = base.len() + links.iter().map(|link| link.len() + 1).sum::<usize>();
+        let is_multiline = base.contains('\n') || links.iter().any(|link| link.contains('\n'));
+        let separator = if links.len() >= 2 && !is_multiline && depth * INDENT.len() + flat_len > MAX_WIDTH {
+            format!("\n{}.", INDENT.repeat(depth + 1))
+        } else {
+            ".".to_owned()
+        };
+        let mut out = base;
+        for link in links {
+            out.push_str(&separator);
+            out.push_str(&link);
+        }
+        out
+    }
+
+    fn invoke(&mut self, invoke: &Invoke, depth: usize) -> String {
+        let mut args = &*invoke.args;
+        let mut trailing = None;
+        if let Some((last, init)) = args.split_last() {
+            if let ExprKind::Closure(closure) = &last.kind {
+                if closure.lambda {
+                    trailing = Some(closure);
+                    args = init;
+                }
+            }
+        }
+        let mut out = invoke.iden.to_string();
+        if !args.is_empty() {
+            let args = args.iter()
+                .map(|arg| self.expr(arg, depth))
+                .collect::<Vec<_>>();
+            out.push('(');
+            out.push_str(&args.join(", "));
+            out.push(')');
+        }
+        if let Some(closure) = trailing {
+            out.push(' ');
+            out.push_str(&self.closure(closure, depth));
+        }
+        out
+    }
+
+    fn closure(&mut self, closure: &Closure, depth: usize) -> String {
+        let mut out = String::new();
+        if !closure.lambda {
+            out.push_str("fn");
+            out.push_str(&signature(closure));
+            out.push(' ');
+        }
+        out.push_str(&self.closure_body(closure, depth));
+        out
+    }
+
+    /// Body of a closure, on one line if it is a short expression.
+    fn closure_body(&mut self, closure: &Closure, depth: usize) -> String {
+        self.short_body(&closure.blocks, depth, closure.span)
+    }
+
+    /// Like [Self::body], but a short expression is kept on one line.
+    fn short_body(&mut self, blocks: &[Block], depth: usize, span: Span) -> String {
+        if let [Block::Expression(expr)] = blocks {
+            if !self.has_comments(span.start, span.end) {
+                let inline = self.expr(expr, depth);
+                if !inline.contains('\n') && inline.len() <= MAX_INLINE_BODY {
+                    return format!("{{ {inline} }}");
+                }
+            }
+        }
+        self.body(blocks, depth, span.start, span.end)
+    }
+
+    /// Statements between braces, on separate lines.
+    fn body(&mut self, blocks: &[Block], depth: usize, lo: u32, hi: u32) -> String {
+        if blocks.is_empty() && !self.has_comments(lo, hi) {
+            return "{}".to_owned();
+        }
+        let mut out = "{\n".to_owned();
+        out.push_str(&self.statements(blocks, depth + 1, lo, hi));
+        push_indent(&mut out, depth);
+        out.push('}');
+        out
+    }
+
+    fn strct(&mut self, strct: &Struct, depth: usize) -> String {
+        let header = format!("{}struct {}{}", public(strct.public), strct.iden, generics(&strct.generics));
+        let span = strct.span;
+        if strct.fields.is_empty() && strct.methods.is_empty() && !self.has_comments(span.start, span.end) {
+            return format!("{header} {{}}");
+        }
+        let mut lines = Lines { out: String::new(), depth: depth + 1, lo: span.start, prev_end: None };
+        for (name, field_type) in &strct.fields {
+            self.push(&mut lines, field_type.span, &format!("{name}: {},", typ(field_type)));
+        }
+        for method in &strct.methods {
+            self.method(&mut lines, method, depth + 1);
+        }
+        self.finish(&mut lines, span.end);
+        members(header, lines, depth)
+    }
+
+    fn method(&mut self, lines: &mut Lines, method: &Method, depth: usize) {
+        let closure = &method.closure;
+        let text = format!("fn {}{} {}", method.iden, signature(closure), self.closure_body(closure, depth));
+        self.push(lines, method.span, &text);
+    }
+
+    fn interface(&mut self, interface: &Interface, depth: usize) -> String {
+        let header = format!("{}interface {}", public(interface.public), interface.iden);
+        let span = interface.span;
+        if interface.methods.is_empty() && !self.has_comments(span.start, span.end) {
+            return format!("{header} {{}}");
+        }
+        let mut lines = Lines { out: String::new(), depth: depth + 1, lo: span.start, prev_end: None };
+        for method in &interface.methods {
+            self.push(&mut lines, method.span, &format!("fn {}{}", method.iden, method_signature(method)));
+        }
+        self.finish(&mut lines, span.end);
+        members(header, lines, depth)
+    }
+
+    fn imp(&mut self, imp: &Impl, depth: usize) -> String {
+        let header = format!("impl {} for {}", typ(&imp.interface), typ(&imp.target));
+        let span = imp.span;
+        if imp.methods.is_empty() && !self.has_comments(span.start, span.end) {
+            return format!("{header} {{}}");
+        }
+        let mut lines = Lines { out: String::new(), depth: depth + 1, lo: span.start, prev_end: None };
+        for method in &imp.methods {
+            self.method(&mut lines, method, depth + 1);
+        }
+        self.finish(&mut lines, span.end);
+        members(header, lines, depth)
+    }
+
+    fn enm(&mut self, enm: &Enum, depth: usize) -> String {
+        let header = format!("{}enum {}{}", public(enm.public), enm.iden, generics(&enm.generics));
+        let span = enm.span;
+        if enm.variants.is_empty() && !self.has_comments(span.start, span.end) {
+            return format!("{header} {{}}");
+        }
+        let mut lines = Lines { out: String::new(), depth: depth + 1, lo: span.start, prev_end: None };
+        for variant in &enm.variants {
+            let (text, variant_span) = match variant {
+                EnumVariant::Struct(strct) => (self.strct(strct, depth + 1), strct.span),
+                EnumVariant::Enum(nested) => (self.enm(nested, depth + 1), nested.span),
+                EnumVariant::Existing(existing) => (typ(existing), existing.span),
+            };
+            self.push(&mut lines, variant_span, &format!("{text},"));
+        }
+        self.finish(&mut lines, span.end);
+        members(header, lines, depth)
+    }
+}
+
+fn members(header: String, lines: Lines, depth: usize) -> String {
+    let mut out = header;
+    out.push_str(" {\n");
+    out.push_str(&lines.out);
+    push_indent(&mut out, depth);
+    out.push('}');
+    out
+}
+
+fn push_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
+
+/// Add text in a way that is read back the same, inside the given quotes. The quote of an
+/// enclosing text is also escaped, otherwise it would end that text.
+fn push_escaped(out: &mut String, text: &str, quote: char, outer: Option<char>) {
+    for ch in text.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '{' | '}' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            _ if ch == quote || Some(ch) == outer => {
+                out.push('\\');
+                out.push(ch);
+            }
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Generics, parameters and return type of a function, like `<T>(x: T): Int`.
+fn signature(closure: &Closure) -> String {
+    let params = closure.params.iter().map(assign_dest).collect::<Vec<_>>();
+    let generics = generics(&closure.generics);
+    match &closure.ret {
+        Some(ret) => format!("{generics}({}): {}", params.join(", "), typ(ret)),
+        None => format!("{generics}({})", params.join(", ")),
+    }
+}
+
+/// Parameters and return type of an interface method, like `(rate: f64): f64`.
+fn method_signature(method: &Signature) -> String {
+    let params = method.params.iter().map(assign_dest).collect::<Vec<_>>();
+    match &method.ret {
+        Some(ret) => format!("({}): {}", params.join(", "), typ(ret)),
+        None => format!("({})", params.join(", ")),
+    }
+}
+
+fn import_line(import: &Import) -> String {
+    let names = import.names.iter().map(|name| name.iden.as_str()).collect::<Vec<_>>();
+    format!("import {} {{ {} }}", import.module, names.join(", "))
+}
+
+fn public(public: bool) -> &'static str {
+    if public { "pub " } else { "" }
+}
+
+fn assign_dest(dest: &AssignmentDest) -> String {
+    let kw = match dest.kw {
+        AssignmentKw::None => "",
+        AssignmentKw::Outer => "outer ",
+        AssignmentKw::Local => "local ",
+        AssignmentKw::Mut => "mut ",
+    };
+    match &dest.typ {
+        Some(dest_type) => format!("{kw}{}: {}", dest.target, typ(dest_type)),
+        None => format!("{kw}{}", dest.target),
+    }
+}
+
+fn typ(typ: &Type) -> String {
+    format!("{}{}", typ.iden, type_list(&typ.generics))
+}
+
+fn type_list(types: &[Type]) -> String {
+    if types.is_empty() {
+        return String::new();
+    }
+    format!("<{}>", types.iter().map(typ).collect::<Vec<_>>().join(", "))
+}
+
+fn generics(generics: &[AssignmentDest]) -> String {
+    if generics.is_empty() {
+        return String::new();
+    }
+    format!("<{}>", generics.iter().map(assign_dest).collect::<Vec<_>>().join(", "))
+}
+
+fn expr_level(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::If(..) | ExprKind::While(..) | ExprKind::ForEach(..) | ExprKind::Match(..) => CONTROL_LEVEL,
+        ExprKind::BinOp(op, ..) => bin_op_level(*op),
+        ExprKind::Dot(..) | ExprKind::Index(..) | ExprKind::Propagate(_) => DOT_LEVEL,
+        ExprKind::UnaryOp(..) => UNARY_LEVEL,
+        ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Text(_) | ExprKind::Interpolate(_) | ExprKind::List(_) | ExprKind::Map(_)
+            | ExprKind::Invoke(_) | ExprKind::Closure(_) => SINGLE_LEVEL,
+    }
+}
+
+fn bin_op_level(op: BinOpCode) -> u8 {
+    match op {
+        BinOpCode::And | BinOpCode::Or | BinOpCode::Xor => 1,
+        BinOpCode::Eq | BinOpCode::Neq | BinOpCode::Lt | BinOpCode::Gt | BinOpCode::Le | BinOpCode::Ge => 2,
+        BinOpCode::Add | BinOpCode::Sub => 3,
+        BinOpCode::Mul | BinOpCode::Div | BinOpCode::Modulo => 4,
+    }
+}
+
+/// Only arithmetic has compound assignment syntax, others are written out like `a = a and b`.
+fn compound_symbol(op: BinOpCode) -> Option<&'static str> {
+    match op {
+        BinOpCode::Add => Some(" += "),
+        BinOpCode::Sub => Some(" -= "),
+        BinOpCode::Mul => Some(" *= "),
+        BinOpCode::Div => Some(" /= "),
+        BinOpCode::Modulo => Some(" %= "),
+        BinOpCode::Eq | BinOpCode::Neq | BinOpCode::Lt | BinOpCode::Gt | BinOpCode::Le | BinOpCode::Ge
+            | BinOpCode::And | BinOpCode::Or | BinOpCode::Xor => None,
+    }
+}
+
+fn bin_op_symbol(op: BinOpCode) -> &'static str {
+    match op {
+        BinOpCode::Add => "+",
+        BinOpCode::Sub => "-",
+        BinOpCode::Mul => "*",
+        BinOpCode::Div => "/",
+        BinOpCode::Modulo => "%",
+        BinOpCode::Eq => "==",
+        BinOpCode::Neq => "!=",
+        BinOpCode::Lt => "<",
+        BinOpCode::Gt => ">",
+        BinOpCode::Le => "<=",
+        BinOpCode::Ge => ">=",
+        BinOpCode::And => "and",
+        BinOpCode::Or => "or",
+        BinOpCode::Xor => "xor",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tel_ast::BinOpCode;
+    use tel_ast::Block;
+    use tel_parser::str_to_ast;
+
+    use crate::ast_to_code;
+    use crate::format_code;
+
+    fn format(code: &str) -> String {
+        let ast = str_to_ast(PathBuf::from("test.tel"), code.to_owned()).unwrap();
+        format_code(&ast, code)
+    }
+
+    #[test]
+    fn statements_and_precedence() {
+        let code = "a = mut b:Int=(1 + 2) * 3;c=a - (b - 1)\nc += -(a.x).abs\nc  %=  2\nif (a>b) {x} elif (a<b) {y} else {}\n";
+        assert_eq!(format(code), "\
+a = mut b: Int = (1 + 2) * 3
+c = a - (b - 1)
+c += -(a.x).abs
+c %= 2
+if (a > b) {
+    x
+} elif (a < b) {
+    y
+} else {}
+");
+    }
+
+    #[test]
+    fn imports_and_public() {
+        let code = "import shapes {Circle,area}\n\n\npub  limit:i64=3\npub struct P {x: i64}\npub enum E { struct A {} }\npub interface I {}\n";
+        assert_eq!(format(code), "\
+import shapes { Circle, area }
+
+pub limit: i64 = 3
+pub struct P {
+    x: i64,
+}
+pub enum E {
+    struct A {},
+}
+pub interface I {}
+");
+    }
+
+    #[test]
+    fn closures() {
+        let code = "f(1, {it * 2})\ng(fn<T: Number>(x: T): T { return x })\nh { a = 1\n a }\nk(fn() { 1 })\n";
+        assert_eq!(format(code), "\
+f(1) { it * 2 }
+g(fn<T: Number>(x: T): T {
+    return x
+})
+h {
+    a = 1
+    a
+}
+k(fn() { 1 })
+");
+    }
+
+    #[test]
+    fn match_arms() {
+        let code = "x = match (shape) { Circle c { c.radius }\n  # other shapes\n  Square { a = 1\n a }\n else { 0 } }\nmatch (y) {}\n";
+        assert_eq!(format(code), "\
+x = match (shape) {
+    Circle c { c.radius }
+    # other shapes
+    Square {
+        a = 1
+        a
+    }
+    else { 0 }
+}
+match (y) {}
+");
+    }
+
+    #[test]
+    fn declarations() {
+        let code = "struct Point<N: Number> { x: N, y: N\nfn norm(): N { (x * x + y * y).sqrt }\nfn with<M:Number>(m: M) { m }\n}\nenum E { struct A {}, B<T>,\n}\np.x.y = self.y\np.x *= 2\n";
+        assert_eq!(format(code), "\
+struct Point<N: Number> {
+    x: N,
+    y: N,
+    fn norm(): N { (x * x + y * y).sqrt }
+    fn with<M: Number>(m: M) { m }
+}
+enum E {
+    struct A {},
+    B<T>,
+}
+p.x.y = self.y
+p.x *= 2
+");
+    }
+
+    #[test]
+    fn interfaces() {
+        let code = "interface Currency { fn cents(): f64\n  fn convert(rate: f64,\n): Currency }\ninterface Empty {}\nimpl Currency for Euro {\nfn cents(): f64 { amount * 100 }\n}\n";
+        assert_eq!(format(code), "\
+interface Currency {
+    fn cents(): f64
+    fn convert(rate: f64): Currency
+}
+interface Empty {}
+impl Currency for Euro {
+    fn cents(): f64 { amount * 100 }
+}
+");
+    }
+
+    #[test]
+    fn texts() {
+        let code = "a = 'say \"hi\"'\nb = \"it's\\n\\{{x}\\}\"\nc = \"{items.join(', ')} and {'{n}'}\"\nd = \"{'say \\\"hi\\\"'}\"\n";
+        assert_eq!(format(code), code);
+        assert_eq!(format("a = 'plain'\n"), "a = \"plain\"\n");
+    }
+
+    #[test]
+    fn collections() {
+        let code = "a = [1,2 ,\n[ ]]\nb = {'k':a[0], 2: -1,(k): {:},}\nc = (-x)[1].y[\"z\"]\n";
+        assert_eq!(format(code), "a = [1, 2, []]\nb = {\"k\": a[0], 2: -1, (k): {:}}\nc = -x[1].y[\"z\"]\n");
+    }
+
+    #[test]
+    fn propagate() {
+        assert_eq!(format("a = parse(x) ?.value?\nb = (-x)?\nc = -x?\n"), "a = parse(x)?.value?\nb = -x?\nc = -x?\n");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(format("a = 12i64 + 3\nb = 2f64 * 1.50\nc = 0.25f64\n"), "a = 12 + 3\nb = 2.0 * 1.5\nc = 0.25\n");
+    }
+
+    #[test]
+    fn long_chain() {
+        let code = "total = items.filter { it % 2 == 0 }.map { -2 * it }.take(99).skip(1).enumerate().collect_into_list.sum.to_text\n";
+        assert_eq!(format(code), "\
+total = items
+    .filter { it % 2 == 0 }
+    .map { -2 * it }
+    .take(99)
+    .skip(1)
+    .enumerate
+    .collect_into_list
+    .sum
+    .to_text
+");
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let code = "# top\n\nx = 1\n\n\n# about y\ny = x ...\n  + 1\n# end\nwhile (x) {\n    # inside\n    x = y\n    # last\n}\n";
+        assert_eq!(format(code), "\
+# top
+
+x = 1
+
+# about y
+y = x + 1
+# end
+while (x) {
+    # inside
+    x = y
+    # last
+}
+");
+    }
+
+    #[test]
+    fn comments_inside_statement() {
+        let code = "total = items  # one\n    .filter { it > 1 } # two\n    .sum\n";
+        assert_eq!(format(code), "# one\n# two\ntotal = items.filter { it > 1 }.sum\n");
+    }
+
+    #[test]
+    fn compound_without_syntax() {
+        let mut ast = str_to_ast(PathBuf::from("test.tel"), "a.b += 1 or c\nx -= y\n".to_owned()).unwrap();
+        for block in ast.blocks.iter_mut() {
+            match block {
+                Block::SetField(set) => set.op = Some(BinOpCode::Or),
+                Block::Assigns(assign) => assign.op = Some(BinOpCode::Lt),
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(ast_to_code(&ast), "a.b = a.b or (1 or c)\nx = x < y\n");
+    }
+
+    #[test]
+    fn without_source() {
+        let ast = str_to_ast(PathBuf::from("test.tel"), "# gone\nx = 'it\"s'\n".to_owned()).unwrap();
+        assert_eq!(ast_to_code(&ast), "x = 'it\"s'\n");
+    }
+}
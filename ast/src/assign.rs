@@ -2,6 +2,7 @@ use serde::Serialize;
 use crate::op::BinOpCode;
 use crate::{Expr, Type};
 use tel_common::Identifier;
+use tel_common::Span;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum AssignmentKw {
@@ -18,16 +19,31 @@ pub enum AssignmentKw {
     Mut,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Assignments {
     pub dest: Box<[AssignmentDest]>,
     pub op: Option<BinOpCode>,
     pub value: Box<Expr>,
+    /// Declared with `pub` at the top level, so other files can import it.
+    pub public: bool,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Assignment to a field, like `a.b.c = 1` or `a.b += 1`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SetField {
+    /// The value that has the field, which is `a.b` in the example.
+    pub target: Box<Expr>,
+    pub field: Identifier,
+    pub op: Option<BinOpCode>,
+    pub value: Box<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct AssignmentDest {
     pub kw: AssignmentKw,
     pub target: Identifier,
     pub typ: Option<Type>,
+    pub span: Span,
 }
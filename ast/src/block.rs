@@ -1,19 +1,57 @@
 use serde::Serialize;
 
 use crate::assign::Assignments;
-use crate::types::{Enum, Struct};
+use crate::assign::SetField;
+use crate::types::{Enum, Impl, Interface, Struct};
 use crate::Expr;
+use tel_common::Identifier;
+use tel_common::Span;
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Ast {
+    /// Other files whose public items this file uses, which come before the code.
+    pub imports: Box<[Import]>,
     pub blocks: Box<[Block]>,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Use of public items from the file with the module name, like `import shapes { Circle, area }`
+/// for `shapes.tel` in the same directory.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Import {
+    pub module: Identifier,
+    pub names: Box<[ImportName]>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportName {
+    pub iden: Identifier,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Block {
     Assigns(Assignments),
+    SetField(SetField),
     Expression(Expr),
     Return(Expr),
     Struct(Struct),
     Enum(Enum),
+    Interface(Interface),
+    Impl(Impl),
+}
+
+impl Block {
+    /// For return statements, this is the span of the returned value.
+    pub fn span(&self) -> Span {
+        match self {
+            Block::Assigns(assign) => assign.span,
+            Block::SetField(set) => set.span,
+            Block::Expression(expr) | Block::Return(expr) => expr.span,
+            Block::Struct(strct) => strct.span,
+            Block::Enum(enm) => enm.span,
+            Block::Interface(interface) => interface.span,
+            Block::Impl(imp) => imp.span,
+        }
+    }
 }
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
+use tel_common::Diagnostic;
 use tel_common::Identifier;
+use tel_common::Span;
 
 #[derive(Debug, PartialEq)]
 pub enum ParseErr {
@@ -9,14 +11,15 @@ pub enum ParseErr {
     CouldNotRead(PathBuf, String),
     ParseErr {
         file: PathBuf,
-        line: usize,
-        msg: String,
+        diagnostics: Vec<Diagnostic>,
     },
     ScopeErr {
-        // file: PathBuf,
-        // line: usize,
-        //TODO @mark: ^
+        span: Span,
+        previous: Span,
         msg: String,
     },
-    UnknownIdentifier(Identifier),
+    UnknownIdentifier {
+        iden: Identifier,
+        span: Span,
+    },
 }
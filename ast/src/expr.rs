@@ -4,12 +4,47 @@ use crate::assign::AssignmentDest;
 use crate::function::{Closure, Invoke};
 use crate::op::{BinOpCode, UnaryOpCode};
 use crate::Block;
+use tel_common::Identifier;
 use tel_common::SString;
+use tel_common::Span;
 
-#[derive(Debug, PartialEq, Serialize)]
-pub enum Expr {
-    Num(f64),
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+
+    pub fn bin_op(op: BinOpCode, left: Expr, right: Expr) -> Self {
+        let span = left.span.join(right.span);
+        Expr::new(ExprKind::BinOp(op, Box::new(left), Box::new(right)), span)
+    }
+
+    pub fn closure(closure: Closure) -> Self {
+        let span = closure.span;
+        Expr::new(ExprKind::Closure(closure), span)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ExprKind {
+    Int(i64),
+    Float(f64),
     Text(SString),
+    /// Text with code in braces, like "total: {x}", which is turned into text when evaluated.
+    Interpolate(Box<[TextPart]>),
+    /// List literal, like `[1, 2, 3]`.
+    List(Box<[Expr]>),
+    /// Map literal, like `{"a": 1}`, with the entries in the order they are written.
+    Map(Box<[(Expr, Expr)]>),
+    /// Element of a list or map, like `items[0]`.
+    Index(Box<Expr>, Box<Expr>),
+    /// Value of an `Option` or `Result`, or return it if it is `None` or `Err`, like `parse(x)?`.
+    Propagate(Box<Expr>),
     /// Binary operation, e.g. 'x+y', 'x==y', 'x or y'. Parser handled precedence.
     BinOp(BinOpCode, Box<Expr>, Box<Expr>),
     /// Unary operation, '!x' or '-x'
@@ -23,5 +58,24 @@ pub enum Expr {
     If(Box<[(Expr, Box<[Block]>)]>, Option<Box<[Block]>>),
     While(Box<Expr>, Box<[Block]>),
     ForEach(AssignmentDest, Box<Expr>, Box<[Block]>),
+    /// Branch on the variant of a value, with an optional `else` for the variants not listed.
+    Match(Box<Expr>, Box<[MatchArm]>, Option<Box<[Block]>>),
+}
+
+/// Piece of an interpolated text, either literally or the result of an expression.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TextPart {
+    Text(SString),
+    Code(Expr),
+}
+
+/// Branch of a match, which is taken if the value is the variant, or a variant nested inside it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchArm {
+    pub variant: Identifier,
+    /// Name for the value as that variant, in the body.
+    pub binding: Option<Identifier>,
+    pub body: Box<[Block]>,
+    pub span: Span,
 }
 
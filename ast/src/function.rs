@@ -1,20 +1,29 @@
 use serde::Serialize;
 use tel_common::Identifier;
+use tel_common::Span;
 use crate::assign::AssignmentDest;
 use crate::block::Block;
 use crate::Expr;
+use crate::Type;
 
 /// Can be a variable read or a function call. A function call without () cannot be differentiated from
 /// a function call by the parser, this must be done later.
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Invoke {
     pub iden: Identifier,
     //TODO @mark: to smallvec or something:
     pub args: Box<[Expr]>,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Closure {
     pub blocks: Box<[Block]>,
+    /// Type parameters, like `T` in `fn<T: Number>(x: T)`.
+    pub generics: Box<[AssignmentDest]>,
     pub params: Box<[AssignmentDest]>,
+    pub ret: Option<Type>,
+    /// Written as just `{ ... }`, without `fn`. A `return` inside returns from the function around it.
+    pub lambda: bool,
+    pub span: Span,
 }
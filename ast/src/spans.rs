@@ -0,0 +1,265 @@
+use tel_common::Span;
+
+use crate::AssignmentDest;
+use crate::Assignments;
+use crate::Ast;
+use crate::Block;
+use crate::Closure;
+use crate::Enum;
+use crate::EnumVariant;
+use crate::Expr;
+use crate::ExprKind;
+use crate::Impl;
+use crate::Import;
+use crate::ImportName;
+use crate::Interface;
+use crate::Invoke;
+use crate::MatchArm;
+use crate::Method;
+use crate::SetField;
+use crate::Signature;
+use crate::Struct;
+use crate::TextPart;
+use crate::Type;
+
+/// Change every span in a part of the syntax tree, like moving code that was parsed by itself
+/// to where it is in the file.
+pub trait MapSpans {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span);
+}
+
+impl<T: MapSpans> MapSpans for [T] {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.iter_mut().for_each(|item| item.map_spans(f));
+    }
+}
+
+impl<T: MapSpans + ?Sized> MapSpans for Box<T> {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        (**self).map_spans(f);
+    }
+}
+
+impl<T: MapSpans> MapSpans for Option<T> {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        if let Some(item) = self {
+            item.map_spans(f);
+        }
+    }
+}
+
+impl Ast {
+    /// Copy with every position set to zero, to compare code that was formatted differently.
+    pub fn without_spans(&self) -> Ast {
+        let mut ast = self.clone();
+        ast.map_spans(&mut |_| Span::new(0, 0));
+        ast
+    }
+}
+
+impl MapSpans for Ast {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.imports.map_spans(f);
+        self.blocks.map_spans(f);
+    }
+}
+
+impl MapSpans for Import {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.names.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for ImportName {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Block {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        match self {
+            Block::Assigns(assign) => assign.map_spans(f),
+            Block::SetField(set) => set.map_spans(f),
+            Block::Expression(expr) | Block::Return(expr) => expr.map_spans(f),
+            Block::Struct(strct) => strct.map_spans(f),
+            Block::Enum(enm) => enm.map_spans(f),
+            Block::Interface(interface) => interface.map_spans(f),
+            Block::Impl(imp) => imp.map_spans(f),
+        }
+    }
+}
+
+impl MapSpans for Assignments {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.dest.map_spans(f);
+        self.value.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for SetField {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.target.map_spans(f);
+        self.value.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for AssignmentDest {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.typ.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Expr {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        match &mut self.kind {
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Text(_) => {}
+            ExprKind::Interpolate(parts) => parts.map_spans(f),
+            ExprKind::List(items) => items.map_spans(f),
+            ExprKind::Map(entries) => for (key, value) in entries.iter_mut() {
+                key.map_spans(f);
+                value.map_spans(f);
+            },
+            ExprKind::Index(base, index) => {
+                base.map_spans(f);
+                index.map_spans(f);
+            }
+            ExprKind::Propagate(inner) | ExprKind::UnaryOp(_, inner) => inner.map_spans(f),
+            ExprKind::BinOp(_, left, right) => {
+                left.map_spans(f);
+                right.map_spans(f);
+            }
+            ExprKind::Invoke(invoke) => invoke.map_spans(f),
+            ExprKind::Dot(target, member) => {
+                target.map_spans(f);
+                member.map_spans(f);
+            }
+            ExprKind::Closure(closure) => closure.map_spans(f),
+            ExprKind::If(branches, otherwise) => {
+                for (condition, body) in branches.iter_mut() {
+                    condition.map_spans(f);
+                    body.map_spans(f);
+                }
+                otherwise.map_spans(f);
+            }
+            ExprKind::While(condition, body) => {
+                condition.map_spans(f);
+                body.map_spans(f);
+            }
+            ExprKind::ForEach(dest, iterable, body) => {
+                dest.map_spans(f);
+                iterable.map_spans(f);
+                body.map_spans(f);
+            }
+            ExprKind::Match(value, arms, otherwise) => {
+                value.map_spans(f);
+                arms.map_spans(f);
+                otherwise.map_spans(f);
+            }
+        }
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for TextPart {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        if let TextPart::Code(expr) = self {
+            expr.map_spans(f);
+        }
+    }
+}
+
+impl MapSpans for MatchArm {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.body.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Invoke {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.args.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Closure {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.blocks.map_spans(f);
+        self.generics.map_spans(f);
+        self.params.map_spans(f);
+        self.ret.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Type {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.generics.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Struct {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        for (_, typ) in &mut self.fields {
+            typ.map_spans(f);
+        }
+        self.methods.map_spans(f);
+        self.generics.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Method {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.closure.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Interface {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.methods.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Signature {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.params.map_spans(f);
+        self.ret.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Impl {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.interface.map_spans(f);
+        self.target.map_spans(f);
+        self.methods.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for Enum {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        self.variants.map_spans(f);
+        self.generics.map_spans(f);
+        self.span = f(self.span);
+    }
+}
+
+impl MapSpans for EnumVariant {
+    fn map_spans(&mut self, f: &mut impl FnMut(Span) -> Span) {
+        match self {
+            EnumVariant::Struct(strct) => strct.map_spans(f),
+            EnumVariant::Enum(enm) => enm.map_spans(f),
+            EnumVariant::Existing(typ) => typ.map_spans(f),
+        }
+    }
+}
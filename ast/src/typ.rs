@@ -1,9 +1,11 @@
 use serde::Serialize;
 use tel_common::Identifier;
+use tel_common::Span;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Type {
     //TODO @mark:
     pub iden: Identifier,
     pub generics: Box<[Type]>,
+    pub span: Span,
 }
@@ -1,23 +1,67 @@
 use crate::assign::AssignmentDest;
+use crate::Closure;
 use crate::Type;
 use serde::Serialize;
 use tel_common::Identifier;
+use tel_common::Span;
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Struct {
     pub iden: Identifier,
     pub fields: Vec<(Identifier, Type)>,
+    pub methods: Vec<Method>,
     pub generics: Box<[AssignmentDest]>,
+    /// Can be imported by other files, for top-level declarations with `pub`.
+    pub public: bool,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Function declared in a struct, which can use the fields and `self` of the instance it is called on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Method {
+    pub iden: Identifier,
+    pub closure: Closure,
+    pub span: Span,
+}
+
+/// Methods that a type must have to satisfy the interface when it is used as a generic bound.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Interface {
+    pub iden: Identifier,
+    pub methods: Vec<Signature>,
+    pub public: bool,
+    pub span: Span,
+}
+
+/// Method without a body, like `fn cents(): f64` in an interface.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Signature {
+    pub iden: Identifier,
+    pub params: Box<[AssignmentDest]>,
+    pub ret: Option<Type>,
+    pub span: Span,
+}
+
+/// Methods of a struct or enum that implement an interface, like `impl Currency for Euro { ... }`.
+/// The interface and target are names, without generic parameters.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Impl {
+    pub interface: Type,
+    pub target: Type,
+    pub methods: Vec<Method>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Enum {
     pub iden: Identifier,
     pub variants: Box<[EnumVariant]>,
     pub generics: Box<[AssignmentDest]>,
+    pub public: bool,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum EnumVariant {
     Struct(Struct),
     Enum(Enum),
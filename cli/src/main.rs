@@ -1,19 +1,35 @@
 use std::io;
+use std::io::IsTerminal;
 use std::io::Read;
 use std::path::PathBuf;
+use std::process;
 
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
 
 use telc::tel_build;
-use telc::tel_build_str;
+use telc::tel_fmt;
+use telc::tel_lsp;
+use telc::tel_run_str;
 use telc::BuildArgs;
+use telc::FmtArgs;
+use telc::ErrReport;
 
 #[derive(Parser, Debug)]
 #[command(name = "tel")]
 struct TelCli {
     #[clap(subcommand)]
     subcommand: SubCmd,
+    /// How to show errors, 'json' is meant for editor integration
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human, global = true)]
+    error_format: ErrorFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ErrorFormat {
+    Human,
+    Json,
 }
 
 #[derive(Parser, Debug)]
@@ -42,15 +58,33 @@ struct EvalCli {
     pub debug: bool,
 }
 
+#[derive(Parser, Debug)]
+#[command(name = "fmt")]
+struct FmtCli {
+    /// Path of the file to format
+    #[arg(default_value = "./main.tel")]
+    pub path: PathBuf,
+    /// Do not change the file, fail if it is not formatted
+    #[arg(long)]
+    pub check: bool,
+}
+
 #[derive(Subcommand, Debug)]
 enum SubCmd {
     Build(BuildCli),
     Script(EvalCli),
+    /// Format the code, keeping comments
+    Fmt(FmtCli),
+    /// Run the language server, communicating over stdin and stdout
+    Lsp,
 }
 
 #[test]
 fn test_cli_args() {
     TelCli::try_parse_from(["tel", "build", "-v"]).unwrap();
+    TelCli::try_parse_from(["tel", "script", "--error-format", "json", "x"]).unwrap();
+    TelCli::try_parse_from(["tel", "lsp"]).unwrap();
+    TelCli::try_parse_from(["tel", "fmt", "--check", "main.tel"]).unwrap();
 }
 
 fn main() {
@@ -58,7 +92,7 @@ fn main() {
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "warn"),
     );
     let args = TelCli::parse();
-    match args.subcommand {
+    let result = match args.subcommand {
         SubCmd::Build(build_args) => tel_build(&BuildArgs {
             path: build_args.path,
             verbose: build_args.verbose,
@@ -69,12 +103,31 @@ fn main() {
                 (None, true) => read_source_from_stdin(),
                 _ => panic!("must provide either a source string, or --stdin to read input from standard input"),  // TODO @mark: error handling
             };
-            tel_build_str(PathBuf::from("script-input"), code, script_args.debug).unwrap(); // TODO @mark: error handling
-            eprintln!("built successfully, cannot run yet"); //TODO @mark: impl
-            Ok(())
+            tel_run_str(PathBuf::from("script-input"), code, script_args.debug)
+        }
+        SubCmd::Fmt(fmt_args) => tel_fmt(&FmtArgs {
+            path: fmt_args.path,
+            check: fmt_args.check,
+        }),
+        SubCmd::Lsp => {
+            if let Err(err) = tel_lsp(io::stdin().lock(), io::stdout().lock()) {
+                eprintln!("language server stopped: {err}");
+                process::exit(1)
+            }
+            return;
         }
+    };
+    if let Err(err) = result {
+        report(&err, args.error_format);
+        process::exit(1)
+    }
+}
+
+fn report(err: &ErrReport, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprint!("{}", err.render_human(io::stderr().is_terminal())),
+        ErrorFormat::Json => eprintln!("{}", err.render_json()),
     }
-    .unwrap() //TODO @mark: do not unwrap
 }
 
 fn read_source_from_stdin() -> String {
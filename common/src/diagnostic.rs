@@ -0,0 +1,259 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::span::source_line_col;
+use crate::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Span in the code with an explanation. Primary labels point at the problem itself,
+/// secondary labels at related code, like an earlier declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub msg: String,
+    pub primary: bool,
+}
+
+/// Problem found in the code, which can be shown to users or sent to an editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable code like E0201, which does not change when the message wording does.
+    pub code: &'static str,
+    pub msg: String,
+    pub file: Option<PathBuf>,
+    pub labels: Vec<Label>,
+    /// Help texts that are not tied to a position in the code.
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, msg: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            msg: msg.into(),
+            file: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(code: &'static str, msg: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, msg)
+        }
+    }
+
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, msg: impl Into<String>) -> Self {
+        self.labels.push(Label { span, msg: msg.into(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, msg: impl Into<String>) -> Self {
+        self.labels.push(Label { span, msg: msg.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Span of the first primary label, if any.
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter()
+            .find(|label| label.primary)
+            .map(|label| label.span)
+    }
+
+    /// Terminal output similar to rustc, with the labelled code underlined.
+    /// The `code` is the source of [Self::file], and is only used if there are labels.
+    pub fn render_human(&self, code: &str, color: bool) -> String {
+        let style = Style { color };
+        let mut out = String::with_capacity(256);
+        let (severity, severity_color) = match self.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+        writeln!(
+            out,
+            "{}{}",
+            style.paint(severity_color, &format!("{severity}[{}]", self.code)),
+            style.paint(BOLD, &format!(": {}", self.msg)),
+        ).unwrap();
+        let mut labels = self.labels.iter()
+            .map(|label| (source_line_col(code, label.span.start as usize), label))
+            .collect::<Vec<_>>();
+        labels.sort_by_key(|((line, col), label)| (*line, *col, !label.primary));
+        let max_line = labels.iter().map(|((line, _), _)| line + 1).max().unwrap_or(0);
+        let gutter = " ".repeat(max_line.to_string().len());
+        if let Some(file) = &self.file {
+            match self.primary_span().or(labels.first().map(|(_, label)| label.span)) {
+                Some(span) => {
+                    let (line, col) = source_line_col(code, span.start as usize);
+                    writeln!(out, "{gutter}{} {}:{}:{}", style.paint(BLUE, "-->"), file.to_string_lossy(), line + 1, col + 1).unwrap();
+                }
+                None => writeln!(out, "{gutter}{} {}", style.paint(BLUE, "-->"), file.to_string_lossy()).unwrap(),
+            }
+        }
+        if !labels.is_empty() {
+            writeln!(out, "{gutter} {}", style.paint(BLUE, "|")).unwrap();
+        }
+        let mut prev_line = None;
+        for ((line, col), label) in &labels {
+            let text = code.lines().nth(*line).unwrap_or("");
+            if prev_line.is_some_and(|prev| prev + 1 < *line) {
+                writeln!(out, "{}", style.paint(BLUE, "...")).unwrap();
+            }
+            if prev_line != Some(*line) {
+                writeln!(out, "{} {} {text}", style.paint(BLUE, &format!("{:>width$}", line + 1, width = gutter.len())), style.paint(BLUE, "|")).unwrap();
+            }
+            prev_line = Some(*line);
+            let len = label.span.len().min(text.len().saturating_sub(*col)).max(1);
+            let (marker, marker_color) = if label.primary { ("^", severity_color) } else { ("-", BLUE) };
+            let underline = format!("{} {}", marker.repeat(len), label.msg);
+            writeln!(out, "{gutter} {} {}{}", style.paint(BLUE, "|"), " ".repeat(*col), style.paint(marker_color, underline.trim_end())).unwrap();
+        }
+        if !self.notes.is_empty() && !labels.is_empty() {
+            writeln!(out, "{gutter} {}", style.paint(BLUE, "|")).unwrap();
+        }
+        for note in &self.notes {
+            writeln!(out, "{gutter} {} {note}", style.paint(BLUE, "= help:")).unwrap();
+        }
+        out
+    }
+
+    /// Single-line json object, for editor integration.
+    pub fn render_json(&self, code: &str) -> String {
+        let labels = self.labels.iter()
+            .map(|label| {
+                let (line, column) = source_line_col(code, label.span.start as usize);
+                let (end_line, end_column) = source_line_col(code, label.span.end as usize);
+                JsonLabel {
+                    start: label.span.start,
+                    end: label.span.end,
+                    line: line + 1,
+                    column: column + 1,
+                    end_line: end_line + 1,
+                    end_column: end_column + 1,
+                    message: &label.msg,
+                    primary: label.primary,
+                }
+            })
+            .collect();
+        let json = JsonDiagnostic {
+            severity: self.severity,
+            code: self.code,
+            message: &self.msg,
+            file: self.file.as_deref(),
+            labels,
+            notes: &self.notes,
+        };
+        serde_json::to_string(&json).expect("diagnostic can always be serialized")
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    severity: Severity,
+    code: &'static str,
+    message: &'a str,
+    file: Option<&'a Path>,
+    labels: Vec<JsonLabel<'a>>,
+    notes: &'a [String],
+}
+
+/// Positions are included both as byte offsets and as one-based line and column.
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    start: u32,
+    end: u32,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+    message: &'a str,
+    primary: bool,
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+
+#[derive(Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(self, ansi: &str, text: &str) -> String {
+        if self.color {
+            format!("{ansi}{text}\x1b[0m")
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "x = 1\ny = x + z\nx = 2\n";
+
+    fn example() -> Diagnostic {
+        Diagnostic::error("E0201", "unknown identifier 'z'")
+            .with_file("test.tel")
+            .with_label(Span::new(14, 15), "not found in this scope")
+            .with_secondary(Span::new(0, 1), "did you mean 'x'?")
+            .with_note("variables must be assigned before they are used")
+    }
+
+    #[test]
+    fn human() {
+        assert_eq!(example().render_human(CODE, false), "\
+error[E0201]: unknown identifier 'z'
+ --> test.tel:2:9
+  |
+1 | x = 1
+  | - did you mean 'x'?
+2 | y = x + z
+  |         ^ not found in this scope
+  |
+  = help: variables must be assigned before they are used
+");
+    }
+
+    #[test]
+    fn human_without_labels() {
+        let diagnostic = Diagnostic::warning("W0001", "something is off").with_note("try again");
+        assert_eq!(diagnostic.render_human("", false), "warning[W0001]: something is off\n  = help: try again\n");
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(example().render_json(CODE), concat!(
+            r#"{"severity":"error","code":"E0201","message":"unknown identifier 'z'","file":"test.tel","labels":["#,
+            r#"{"start":14,"end":15,"line":2,"column":9,"end_line":2,"end_column":10,"message":"not found in this scope","primary":true},"#,
+            r#"{"start":0,"end":1,"line":1,"column":1,"end_line":1,"end_column":2,"message":"did you mean 'x'?","primary":false}],"#,
+            r#""notes":["variables must be assigned before they are used"]}"#,
+        ));
+    }
+}
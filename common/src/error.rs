@@ -1,7 +1,8 @@
-
 // TODO: Maybe this does not need be below ast in dependency tree, just below non-api crates?
 
+use crate::Diagnostic;
 use crate::Identifier;
+use crate::Span;
 use ::std::path::PathBuf;
 
 #[derive(Debug, PartialEq)]
@@ -10,16 +11,181 @@ pub enum TelErr {
         file: PathBuf,
     },
     CouldNotRead(PathBuf, String),
+    CouldNotWrite(PathBuf, String),
+    NotFormatted {
+        file: PathBuf,
+    },
     ParseErr {
         file: PathBuf,
-        line: usize,
-        msg: String,
+        diagnostics: Vec<Diagnostic>,
     },
     ScopeErr {
-        // file: PathBuf,
-        // line: usize,
-        //TODO @mark: ^
+        span: Span,
+        /// Where the name was declared first.
+        previous: Span,
         msg: String,
     },
-    UnknownIdentifier(Identifier),
+    UnknownIdentifier {
+        iden: Identifier,
+        span: Span,
+    },
+    ImmutableAssign {
+        iden: Identifier,
+        span: Span,
+        /// Where the variable was declared.
+        declared: Span,
+    },
+    NoOuterVariable {
+        iden: Identifier,
+        span: Span,
+    },
+    CompoundAssignNew {
+        iden: Identifier,
+        span: Span,
+    },
+    InvalidAssignTarget {
+        span: Span,
+    },
+    /// Closure like `{ ... }` with a `return`, which is used somewhere it may outlive the function it returns from.
+    EscapingReturn {
+        span: Span,
+    },
+    /// Import of a module without a file, which is looked for next to the importing file.
+    ImportNotFound {
+        module: Identifier,
+        file: PathBuf,
+        span: Span,
+    },
+    /// Files that import each other, directly or through other files. The first and last file are the same.
+    ImportCycle {
+        cycle: Vec<PathBuf>,
+        span: Span,
+    },
+    /// Imported name that the module does not declare with `pub`.
+    NotPublic {
+        iden: Identifier,
+        module: Identifier,
+        span: Span,
+    },
+    /// Public declaration without the type annotations that its signature needs.
+    MissingPublicType {
+        iden: Identifier,
+        span: Span,
+    },
+    /// `pub` on something that cannot be imported.
+    InvalidPublic {
+        span: Span,
+        msg: String,
+    },
+    TypeErr {
+        span: Span,
+        /// The annotation or declaration that requires the type, if it is known and in the same file.
+        declared: Option<Span>,
+        msg: String,
+    },
+    ExecuteErr {
+        msg: String,
+        span: Span,
+    },
+}
+
+impl TelErr {
+    /// Structured version of the error, which does not yet have the file set, unless it is known
+    /// from the error itself. Parsing can find multiple errors, other phases stop at the first.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        if let TelErr::ParseErr { file, diagnostics } = self {
+            return diagnostics.iter()
+                .map(|diagnostic| diagnostic.clone().with_file(file))
+                .collect()
+        }
+        vec![self.diagnostic()]
+    }
+
+    /// Change every position in the error, like when the code was at another place while it was checked.
+    pub fn map_spans(&mut self, f: impl Fn(Span) -> Span) {
+        match self {
+            TelErr::FileNotFound { .. } | TelErr::CouldNotRead(..) | TelErr::CouldNotWrite(..) | TelErr::NotFormatted { .. } => {}
+            TelErr::ParseErr { diagnostics, .. } => for label in diagnostics.iter_mut().flat_map(|diagnostic| &mut diagnostic.labels) {
+                label.span = f(label.span);
+            },
+            TelErr::ScopeErr { span, previous, .. } | TelErr::ImmutableAssign { span, declared: previous, .. } => {
+                *span = f(*span);
+                *previous = f(*previous);
+            }
+            TelErr::TypeErr { span, declared, .. } => {
+                *span = f(*span);
+                *declared = declared.map(&f);
+            }
+            TelErr::UnknownIdentifier { span, .. } | TelErr::NoOuterVariable { span, .. } | TelErr::CompoundAssignNew { span, .. }
+                | TelErr::InvalidAssignTarget { span } | TelErr::EscapingReturn { span } | TelErr::ImportNotFound { span, .. }
+                | TelErr::ImportCycle { span, .. } | TelErr::NotPublic { span, .. } | TelErr::MissingPublicType { span, .. }
+                | TelErr::InvalidPublic { span, .. } | TelErr::ExecuteErr { span, .. } => *span = f(*span),
+        }
+    }
+
+    fn diagnostic(&self) -> Diagnostic {
+        match self {
+            TelErr::FileNotFound { file } => Diagnostic::error("E0001", "file not found")
+                .with_file(file),
+            TelErr::CouldNotRead(file, msg) => Diagnostic::error("E0002", format!("could not read file: {msg}"))
+                .with_file(file),
+            TelErr::CouldNotWrite(file, msg) => Diagnostic::error("E0003", format!("could not write file: {msg}"))
+                .with_file(file),
+            TelErr::NotFormatted { file } => Diagnostic::error("E0004", "file is not formatted")
+                .with_file(file)
+                .with_note("run 'tel fmt' to format it"),
+            TelErr::ParseErr { .. } => unreachable!("handled by diagnostics"),
+            TelErr::ScopeErr { span, previous, msg } => Diagnostic::error("E0200", msg)
+                .with_label(*span, "declared again here")
+                .with_secondary(*previous, "first declared here")
+                .with_note("use a different name, or reassign without a type or keyword to reuse the variable"),
+            TelErr::UnknownIdentifier { iden, span } => Diagnostic::error("E0201", format!("unknown identifier '{iden}'"))
+                .with_label(*span, "not found in this scope")
+                .with_note("variables can only be used in the block they are assigned in, or blocks inside it"),
+            TelErr::ImmutableAssign { iden, span, declared } => Diagnostic::error("E0202", format!("cannot assign twice to immutable variable '{iden}'"))
+                .with_label(*span, "assigned again here")
+                .with_secondary(*declared, "declared here")
+                .with_note("declare it with 'mut' to allow assigning to it again, or with 'local' to shadow it"),
+            TelErr::NoOuterVariable { iden, span } => Diagnostic::error("E0203", format!("no outer variable '{iden}' to assign to"))
+                .with_label(*span, "'outer' requires an existing variable")
+                .with_note("remove 'outer' to declare a new variable"),
+            TelErr::CompoundAssignNew { iden, span } => Diagnostic::error("E0204", format!("cannot use compound assignment to declare '{iden}'"))
+                .with_label(*span, "compound assignment needs an existing variable")
+                .with_note("assign a value first, or use 'outer' to update a variable from outside the function"),
+            TelErr::InvalidAssignTarget { span } => Diagnostic::error("E0205", "can only assign to fields of a variable")
+                .with_label(*span, "not a variable or field")
+                .with_note("store the value in a mutable variable first, then assign to its field"),
+            TelErr::EscapingReturn { span } => Diagnostic::error("E0206", "closure with 'return' can only be passed to a function that does not keep it")
+                .with_label(*span, "'return' in here returns from the function around this closure")
+                .with_note("write it as 'fn() { ... }' to return from the closure itself, or pass it directly to a function that only calls it"),
+            TelErr::ImportNotFound { module, file, span } => Diagnostic::error("E0207", format!("module '{module}' not found"))
+                .with_label(*span, "imported here")
+                .with_note(format!("expected a file at '{}'", file.display())),
+            TelErr::ImportCycle { cycle, span } => Diagnostic::error("E0208", format!("files import each other: {}", cycle.iter()
+                    .map(|file| file.file_name().unwrap_or(file.as_os_str()).to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")))
+                .with_label(*span, "this import is part of the cycle")
+                .with_note("move what both files need to a separate file that they both import"),
+            TelErr::NotPublic { iden, module, span } => Diagnostic::error("E0209", format!("'{iden}' is not public in module '{module}'"))
+                .with_label(*span, "imported here")
+                .with_note("only top-level declarations with 'pub' can be imported"),
+            TelErr::MissingPublicType { iden, span } => Diagnostic::error("E0210", format!("public '{iden}' needs type annotations"))
+                .with_label(*span, "declared public here")
+                .with_note("annotate the type of public variables, and the parameters and result of public functions and methods"),
+            TelErr::InvalidPublic { span, msg } => Diagnostic::error("E0211", msg)
+                .with_label(*span, "cannot be public")
+                .with_note("only top-level structs, enums, interfaces and immutable assignments can be public"),
+            TelErr::TypeErr { span, declared, msg } => {
+                let diagnostic = Diagnostic::error("E0300", msg)
+                    .with_label(*span, "type error here");
+                match declared {
+                    Some(declared) => diagnostic.with_secondary(*declared, "expected because of this"),
+                    None => diagnostic,
+                }
+            }
+            TelErr::ExecuteErr { msg, span } => Diagnostic::error("E0400", msg)
+                .with_label(*span, "failed while running this"),
+        }
+    }
 }
@@ -7,12 +7,13 @@ use log::debug;
 use serde::Serialize;
 use serde::Serializer;
 
-//TODO @mark: should actively-used keywords be in here?
+// Keywords from the grammar must be here, otherwise an ast could contain names that cannot be written as code
 //TODO @mark: should 'assert' be reserved?
-const RESERVED: [&str; 138] = [
+const RESERVED: [&str; 148] = [
     "abstract",
     "alias",
     "all",
+    "and",
     "annotation",
     "any",
     "as",
@@ -47,7 +48,10 @@ const RESERVED: [&str; 138] = [
     "double",
     "dynamic",
     "elementwise",
+    "elif",
+    "else",
     "end",
+    "enum",
     "eval",
     "except",
     "extends",
@@ -57,11 +61,13 @@ const RESERVED: [&str; 138] = [
     "final",
     "finally",
     "float",
+    "fn",
     "fun",
     "for",
     "get",
     "global",
     "goto",
+    "if",
     "impl",
     "implements",
     "import",
@@ -86,6 +92,7 @@ const RESERVED: [&str; 138] = [
     "match",
     "module",
     "move",
+    "mut",
     "NaN",
     "native",
     "nill",
@@ -94,6 +101,7 @@ const RESERVED: [&str; 138] = [
     "object",
     "open",
     "operator",
+    "or",
     "out",
     "outer",
     "override",
@@ -102,6 +110,7 @@ const RESERVED: [&str; 138] = [
     "pass",
     "private",
     "proof",
+    "pub",
     "public",
     "pure",
     "raise",
@@ -117,6 +126,7 @@ const RESERVED: [&str; 138] = [
     "spawn",
     "static",
     "steel",
+    "struct",
     "super",
     "switch",
     "sync",
@@ -205,6 +215,20 @@ impl Identifier {
         }
         Ok(Identifier { name })
     }
+
+    /// The `self` of methods, which is reserved so that it cannot be declared like other names.
+    pub fn receiver() -> Self {
+        Identifier { name: "self".into() }
+    }
+
+    /// The `it` parameter of closures that do not declare their parameters, like `{ it * 2 }`.
+    pub fn implicit_param() -> Self {
+        Identifier { name: "it".into() }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
 }
 
 impl fmt::Display for Identifier {
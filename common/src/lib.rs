@@ -5,8 +5,15 @@
 
 mod identifier;
 mod error;
+mod diagnostic;
+mod span;
 pub mod parse_util;
 
 pub use crate::error::TelErr;
+pub use crate::diagnostic::Diagnostic;
+pub use crate::diagnostic::Label;
+pub use crate::diagnostic::Severity;
 pub use crate::identifier::Identifier;
+pub use crate::span::Span;
+pub use crate::span::source_line_col;
 pub use smartstring::alias::String as SString;
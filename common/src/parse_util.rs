@@ -1,3 +1,4 @@
+use crate::Span;
 
 pub fn vec_and<T>(mut items: Vec<T>, addition: Option<T>) -> Vec<T> {
     if let Some(addition) = addition {
@@ -5,3 +6,8 @@ pub fn vec_and<T>(mut items: Vec<T>, addition: Option<T>) -> Vec<T> {
     }
     items
 }
+
+/// Span of a string literal token without the leading whitespace.
+pub fn unquote_span(token: &str, start: usize, end: usize) -> Span {
+    Span::new(start + token.len() - token.trim_start().len(), end)
+}
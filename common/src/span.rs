@@ -0,0 +1,75 @@
+use std::mem::size_of;
+
+use serde::Serialize;
+
+/// Byte range in the source code of a file, used to point at the code an error is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Span {
+    pub start: u32,
+    pub end: u32,
+}
+
+const _: () = assert!(size_of::<Span>() == 8);
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        debug_assert!(start <= end, "span start {start} is after end {end}");
+        debug_assert!(end <= u32::MAX as usize, "source files larger than 4GB are not supported");
+        Span { start: start as u32, end: end as u32 }
+    }
+
+    pub fn len(self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.start == self.end
+    }
+
+    /// The smallest span that contains both spans.
+    pub fn join(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// Zero-based line and column of the start.
+    pub fn line_col(self, code: &str) -> (usize, usize) {
+        source_line_col(code, self.start as usize)
+    }
+}
+
+/// Zero-based line number and column for a byte position.
+pub fn source_line_col(code: &str, start: usize) -> (usize, usize) {
+    let mut err_line_nr = 0;
+    let mut err_char_in_line = 0;
+    let mut char_nr = 0;
+    for line in code.lines() {
+        if char_nr + line.len() >= start {
+            err_char_in_line = start - char_nr;
+            break;
+        }
+        char_nr += line.len() + 1;
+        err_line_nr += 1;
+    }
+    (err_line_nr, err_char_in_line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let code = "a = 1\nb = a + c\nb.print\n";
+        assert_eq!(Span::new(0, 1).line_col(code), (0, 0));
+        assert_eq!(Span::new(14, 15).line_col(code), (1, 8));
+        assert_eq!(Span::new(code.len(), code.len()).line_col(code), (3, 0));
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(Span::new(4, 6).join(Span::new(1, 5)), Span::new(1, 6));
+    }
+}
@@ -27,25 +27,9 @@ fn generate_example_parse_tests() {
         write!(
             test_code,
             "#[test]
-fn parse_{name}() {{
-    // generated!
-    let pth = PathBuf::from(\"{pth_str}\");
-    let code = read_to_string(&pth).unwrap();
-    // str_to_ast, ast_to_api, get_test_modes should be available in the context where this is included
-    let mode = get_test_modes(&code);
-    let parse_res = str_to_ast(pth, code);
-    assert!(!mode.should_fail);  // TODO @mark
-    if let Err(ParseErr::ParseErr {{ msg, .. }}) = &parse_res {{
-        eprintln!(\"Failed to parse example file {pth_str}:\\n{{}}\", msg);
-    }}
-    if mode.parse_only {{
-        println!(\"parsing only\");
-        return;
-    }}
-    let api_res = ast_to_api(parse_res.unwrap());
-    if let Err(TelErr::ParseErr {{ msg, .. }}) = &api_res {{
-        eprintln!(\"Failed to resolve scopes for example file {pth_str}:\\n{{}}\", msg);
-    }}
+fn example_{name}() {{
+    // generated! check_example should be available in the context where this is included
+    check_example(PathBuf::from(\"{pth_str}\"));
 }}\n\n"
         )
         .unwrap();
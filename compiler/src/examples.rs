@@ -1,12 +1,44 @@
 use std::fs::read_to_string;
 use std::path::PathBuf;
-use tel_ast::ParseErr;
-use tel_common::TelErr;
+use tel_ast::Ast;
+use tel_ast_to_code::format_code;
 use tel_parser::str_to_ast;
-use crate::ast_to_api;
+use crate::check_modules;
+use crate::execute::execute;
+use crate::modules::load_modules;
+use crate::modules::locate_err;
+use crate::parse_err_to_tel_err;
+use crate::ErrReport;
 
 include!(concat!(env!("OUT_DIR"), "/parse_tests.rs"));
 
+/// Every example should parse, check and run, unless it is marked to fail.
+fn check_example(pth: PathBuf) {
+    let code = read_to_string(&pth).unwrap();
+    let mode = get_test_modes(&code);
+    match (run_example(pth.clone(), code, mode), mode.should_fail) {
+        (Ok(()), true) => panic!("example file {} should fail, but it succeeded", pth.display()),
+        (Err(err), false) => panic!("example file {} failed:\n{err}", pth.display()),
+        (Ok(()), false) | (Err(_), true) => {},
+    }
+}
+
+fn run_example(pth: PathBuf, code: String, mode: Mode) -> Result<(), String> {
+    let ast = str_to_ast(pth.clone(), code.clone())
+        .map_err(|err| ErrReport::new(parse_err_to_tel_err(err), &pth, &code).render_human(false))?;
+    check_round_trip(&code, &ast);
+    if mode.parse_only {
+        return Ok(());
+    }
+    let modules = load_modules(pth, code, ast)
+        .map_err(|err| ErrReport::from(err).render_human(false))?;
+    let (file, _) = check_modules(&modules)
+        .map_err(|err| ErrReport::from(err).render_human(false))?;
+    let mut out = Vec::new();
+    execute(&file, &mut out)
+        .map_err(|err| ErrReport::from(locate_err(&modules, err)).render_human(false))
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Mode {
     parse_only: bool,  // TODO @mark: this one is temporary
@@ -29,3 +61,12 @@ fn get_test_modes(code: &str) -> Mode {
     mode
 }
 
+/// Formatting the code and parsing it again should give the same ast, and formatting again should not change it.
+fn check_round_trip(code: &str, ast: &Ast) {
+    let formatted = format_code(ast, code);
+    let reparsed = str_to_ast(PathBuf::from("formatted.tel"), formatted.clone())
+        .unwrap_or_else(|err| panic!("formatted code does not parse: {err:?}\n{formatted}"));
+    assert_eq!(ast.without_spans(), reparsed.without_spans(), "formatting changed the ast:\n{formatted}");
+    assert_eq!(format_code(&reparsed, &formatted), formatted, "formatting is not stable");
+}
+
@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use tel_hir::Variable;
+
+use crate::execute::value::Value;
+
+/// Values of the variables in one block. Each block gets a new environment,
+/// so that closures created inside loops capture the variables of that iteration.
+#[derive(Debug, Default)]
+pub struct Env<'a> {
+    values: RefCell<HashMap<Variable, Value<'a>>>,
+    parent: Option<Rc<Env<'a>>>,
+    /// Whether this is the outermost block of a function call, which is where `return` goes.
+    frame: bool,
+}
+
+impl<'a> Env<'a> {
+    pub fn new_root() -> Rc<Self> {
+        Rc::new(Env::default())
+    }
+
+    pub fn new_child(parent: &Rc<Self>) -> Rc<Self> {
+        Rc::new(Env {
+            values: RefCell::new(HashMap::new()),
+            parent: Some(parent.clone()),
+            frame: false,
+        })
+    }
+
+    pub fn new_frame(parent: &Rc<Self>) -> Rc<Self> {
+        Rc::new(Env {
+            values: RefCell::new(HashMap::new()),
+            parent: Some(parent.clone()),
+            frame: true,
+        })
+    }
+
+    /// The function call that this block is part of, or the root for top-level code.
+    pub fn frame(self: &Rc<Self>) -> Rc<Self> {
+        match &self.parent {
+            Some(parent) if !self.frame => parent.frame(),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn get(&self, var: Variable) -> Option<Value<'a>> {
+        if let Some(value) = self.values.borrow().get(&var) {
+            return Some(value.clone())
+        }
+        self.parent.as_ref()?.get(var)
+    }
+
+    /// Scoping already decided which variable this is, so if it exists anywhere, update it,
+    /// and otherwise this is the first assignment and it is created in the current block.
+    pub fn assign(&self, var: Variable, value: Value<'a>) {
+        if let Err(value) = self.update(var, value) {
+            self.declare(var, value)
+        }
+    }
+
+    pub fn declare(&self, var: Variable, value: Value<'a>) {
+        self.values.borrow_mut().insert(var, value);
+    }
+
+    fn update(&self, var: Variable, value: Value<'a>) -> Result<(), Value<'a>> {
+        if let Some(existing) = self.values.borrow_mut().get_mut(&var) {
+            *existing = value;
+            return Ok(())
+        }
+        match &self.parent {
+            Some(parent) => parent.update(var, value),
+            None => Err(value),
+        }
+    }
+}
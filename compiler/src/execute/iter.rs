@@ -0,0 +1,99 @@
+//! Consuming the lazy sequences behind iterators.
+
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::execute::as_bool;
+use crate::execute::fail;
+use crate::execute::value::Sequence;
+use crate::execute::value::Value;
+use crate::execute::Interpreter;
+use crate::execute::Unwind;
+
+/// Called with each item, returns whether to continue with the next one.
+type Visit<'v, 'a, W> = dyn FnMut(&mut Interpreter<'a, W>, Value<'a>) -> Result<bool, Unwind<'a>> + 'v;
+
+impl<'a, W: Write> Interpreter<'a, W> {
+    /// The sequence to iterate over for a value that can be iterated, like a text or list.
+    /// Iterating over a map gives its keys.
+    pub(super) fn sequence(&self, value: &Value<'a>) -> Result<Rc<Sequence<'a>>, Unwind<'a>> {
+        Ok(match value {
+            Value::Iter(sequence) => sequence.clone(),
+            Value::Text(text) => Rc::new(Sequence::Chars(text.clone())),
+            Value::List(items) => Rc::new(Sequence::Items(items.clone())),
+            Value::Map(entries) => Rc::new(Sequence::Keys(entries.clone())),
+            other => return fail(format!("cannot iterate over {}", other.type_name())),
+        })
+    }
+
+    /// Pass each item of the sequence to `visit`, computing them one at a time, until it returns false.
+    /// The result is false if `visit` stopped early.
+    pub(super) fn each(&mut self, sequence: &Sequence<'a>, visit: &mut Visit<'_, 'a, W>) -> Result<bool, Unwind<'a>> {
+        match sequence {
+            Sequence::Range(start, end) => {
+                for nr in *start..*end {
+                    if !visit(self, Value::Int(nr))? {
+                        return Ok(false)
+                    }
+                }
+            }
+            Sequence::Chars(text) => {
+                for ch in text.chars() {
+                    if !visit(self, Value::Text(Rc::new(ch.encode_utf8(&mut [0; 4]).into())))? {
+                        return Ok(false)
+                    }
+                }
+            }
+            Sequence::Items(items) => {
+                for item in items.iter() {
+                    if !visit(self, item.clone())? {
+                        return Ok(false)
+                    }
+                }
+            }
+            Sequence::Keys(entries) => {
+                for (key, _) in entries.iter() {
+                    if !visit(self, key.clone())? {
+                        return Ok(false)
+                    }
+                }
+            }
+            Sequence::Values(entries) => {
+                for (_, value) in entries.iter() {
+                    if !visit(self, value.clone())? {
+                        return Ok(false)
+                    }
+                }
+            }
+            Sequence::Map(source, f) => return self.each(source, &mut |this, item| {
+                let mapped = this.call(f.clone(), vec![item])?;
+                visit(this, mapped)
+            }),
+            Sequence::Filter(source, f) => return self.each(source, &mut |this, item| {
+                if as_bool(&this.call(f.clone(), vec![item.clone()])?)? {
+                    visit(this, item)
+                } else {
+                    Ok(true)
+                }
+            }),
+            Sequence::Take(source, count) => {
+                if *count == 0 {
+                    return Ok(true)
+                }
+                let mut remaining = *count;
+                let mut stopped = false;
+                // the source stops once enough items were taken, but that is not an early stop of `visit`
+                self.each(source, &mut |this, item| {
+                    remaining -= 1;
+                    if !visit(this, item)? {
+                        stopped = true;
+                        return Ok(false)
+                    }
+                    Ok(remaining > 0)
+                })?;
+                return Ok(!stopped)
+            }
+        }
+        Ok(true)
+    }
+}
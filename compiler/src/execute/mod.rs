@@ -0,0 +1,1044 @@
+//! Tree-walking interpreter for the resolved program.
+//!
+//! This evaluates the hir directly, which is simple but not particularly fast.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Write;
+use std::mem;
+use std::panic;
+use std::rc::Rc;
+use std::thread;
+
+use tel_common::Identifier;
+use tel_common::Span;
+use tel_common::TelErr;
+use tel_hir as hir;
+use tel_hir::Builtin;
+use tel_hir::TelFile;
+use tel_hir::Variable;
+
+use self::env::Env;
+use self::value::ClosureValue;
+use self::value::Sequence;
+use self::value::StructValue;
+use self::value::Value;
+
+mod env;
+mod iter;
+mod value;
+
+/// Control flow that skips the rest of the evaluation: either a `return`,
+/// which stops at the function call it belongs to, or an error, which stops the program.
+enum Unwind<'a> {
+    /// The value, the frame of the function that returns, and where the return is.
+    Return(Value<'a>, Rc<Env<'a>>, Span),
+    /// Error that does not know its position yet, it gets that of the expression that was being evaluated.
+    Fail(String),
+    Error(TelErr),
+}
+
+impl Unwind<'_> {
+    fn at(self, span: Span) -> Self {
+        match self {
+            Unwind::Fail(msg) => Unwind::Error(TelErr::ExecuteErr { msg, span }),
+            other => other,
+        }
+    }
+}
+
+type Eval<'a> = Result<Value<'a>, Unwind<'a>>;
+
+fn fail<'a, T>(msg: impl Into<String>) -> Result<T, Unwind<'a>> {
+    Err(Unwind::Fail(msg.into()))
+}
+
+/// Recursion deeper than this is an error, instead of overflowing the stack.
+const MAX_CALL_DEPTH: usize = 10_000;
+/// Every call takes several nested evaluations, so this is much more than threads get by default.
+const STACK_SIZE: usize = 1 << 30;
+
+/// Run the top-level code of the file, sending printed output to `out`.
+pub fn execute(file: &TelFile, out: impl Write + Send) -> Result<(), TelErr> {
+    thread::scope(|scope| thread::Builder::new()
+        .name("tel-execute".to_owned())
+        .stack_size(STACK_SIZE)
+        .spawn_scoped(scope, || execute_here(file, out))
+        .expect("could not start thread to run the code")
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload)))
+}
+
+fn execute_here(file: &TelFile, out: impl Write) -> Result<(), TelErr> {
+    let mut interpreter = Interpreter::new(file, out);
+    let globals = interpreter.globals.clone();
+    match interpreter.eval_block(&file.main, &globals) {
+        // a top-level return just ends the program
+        Ok(_) => Ok(()),
+        Err(Unwind::Return(_, frame, _)) if Rc::ptr_eq(&frame, &globals) => Ok(()),
+        Err(Unwind::Return(_, _, span)) => Err(TelErr::ExecuteErr {
+            msg: "cannot return from a function that already finished".to_owned(),
+            span,
+        }),
+        Err(Unwind::Fail(msg)) => unreachable!("error without position: {msg}"),
+        Err(Unwind::Error(err)) => Err(err),
+    }
+}
+
+struct Interpreter<'a, W: Write> {
+    file: &'a TelFile,
+    structs: HashMap<Variable, &'a hir::Struct>,
+    enums: HashMap<Variable, &'a hir::Enum>,
+    globals: Rc<Env<'a>>,
+    out: W,
+    /// Number of function calls that have not returned yet.
+    depth: usize,
+}
+
+impl<'a, W: Write> Interpreter<'a, W> {
+    fn new(file: &'a TelFile, out: W) -> Self {
+        let globals = Env::new_root();
+        for strct in &file.structs {
+            globals.declare(strct.var, Value::Type(strct.var));
+        }
+        for enm in &file.enums {
+            globals.declare(enm.var, Value::Type(enm.var));
+        }
+        for interface in &file.interfaces {
+            globals.declare(interface.var, Value::Type(interface.var));
+        }
+        for function in &file.functions {
+            //TODO @mark: functions refer to the globals that contain them, so this never gets freed
+            globals.declare(function.var, Value::Closure(Rc::new(ClosureValue {
+                closure: &function.closure,
+                env: globals.clone(),
+            })));
+        }
+        Interpreter {
+            file,
+            structs: file.structs.iter().map(|strct| (strct.var, strct)).collect(),
+            enums: file.enums.iter().map(|enm| (enm.var, enm)).collect(),
+            globals,
+            out,
+            depth: 0,
+        }
+    }
+
+    /// Evaluate statements in the given environment, the value is that of the last statement.
+    fn eval_block(&mut self, block: &'a hir::Block, env: &Rc<Env<'a>>) -> Eval<'a> {
+        let mut last = Value::Void;
+        for stmt in &block.stmts {
+            last = match stmt {
+                hir::Stmt::Assign(hir::Assignment { var, value }) => {
+                    let value = self.eval(value, env)?;
+                    env.assign(*var, value);
+                    Value::Void
+                }
+                hir::Stmt::SetField(hir::SetField { var, fields, value }) => {
+                    let new = self.eval(value, env)?;
+                    self.set_field(*var, fields, new, env).map_err(|unwind| unwind.at(value.span))?;
+                    Value::Void
+                }
+                hir::Stmt::Expression(expr) => self.eval(expr, env)?,
+                hir::Stmt::Return(expr) => return Err(Unwind::Return(self.eval(expr, env)?, env.frame(), expr.span)),
+            }
+        }
+        Ok(last)
+    }
+
+    fn set_field(&mut self, var: Variable, fields: &[Identifier], value: Value<'a>, env: &Rc<Env<'a>>) -> Result<(), Unwind<'a>> {
+        let Some(current) = env.get(var) else {
+            return fail(format!("'{}' was used before it was assigned", var.iden(&self.file.variables)))
+        };
+        env.assign(var, self.with_field(current, fields, value)?);
+        Ok(())
+    }
+
+    fn eval(&mut self, expr: &'a hir::Expr, env: &Rc<Env<'a>>) -> Eval<'a> {
+        self.eval_kind(expr, env).map_err(|unwind| unwind.at(expr.span))
+    }
+
+    fn eval_kind(&mut self, expr: &'a hir::Expr, env: &Rc<Env<'a>>) -> Eval<'a> {
+        match &expr.kind {
+            hir::ExprKind::Int(nr) => Ok(Value::Int(*nr)),
+            hir::ExprKind::Float(nr) => Ok(Value::Float(*nr)),
+            hir::ExprKind::Text(text) => Ok(Value::Text(Rc::new(text.clone()))),
+            hir::ExprKind::Interpolate(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    let value = self.eval(part, env)?;
+                    text.push_str(&self.fmt_value(&value));
+                }
+                Ok(Value::Text(Rc::new(text.as_str().into())))
+            }
+            hir::ExprKind::List(elements) => Ok(Value::List(Rc::new(self.eval_all(elements, env)?))),
+            hir::ExprKind::Map(entries) => {
+                let mut map = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let key = self.eval(key, env)?;
+                    let value = self.eval(value, env)?;
+                    self.insert(&mut map, key, value)?;
+                }
+                Ok(Value::Map(Rc::new(map)))
+            }
+            hir::ExprKind::Index(base, index) => {
+                let base = self.eval(base, env)?;
+                let index = self.eval(index, env)?;
+                self.index(&base, &index)
+            }
+            hir::ExprKind::Propagate(inner) => {
+                let value = self.eval(inner, env)?;
+                match self.unpack(&value)? {
+                    Some(inner) => Ok(inner),
+                    None => Err(Unwind::Return(value, env.frame(), expr.span)),
+                }
+            }
+            hir::ExprKind::Invoke(hir::Invoke { var, args }) => {
+                if let Some(builtin) = var.builtin() {
+                    // without arguments this refers to the function, unless it has no parameters, like `true`
+                    if args.is_empty() && !builtin.params().is_empty() {
+                        return Ok(Value::Builtin(builtin))
+                    }
+                    return self.eval_builtin(builtin.with_arity(args.len()), args, env)
+                }
+                let Some(value) = env.get(*var) else {
+                    return fail(format!("'{}' was used before it was assigned", var.iden(&self.file.variables)))
+                };
+                if args.is_empty() && !self.is_nullary_callable(&value) {
+                    return Ok(value)
+                }
+                let args = self.eval_all(args, env)?;
+                self.call(value, args)
+            }
+            hir::ExprKind::Dot(target, hir::Member { iden, args }) => {
+                let target = self.eval(target, env)?;
+                let args = self.eval_all(args, env)?;
+                self.member(target, iden.as_str(), args)
+            }
+            hir::ExprKind::Closure(closure) => Ok(Value::Closure(Rc::new(ClosureValue {
+                closure,
+                env: env.clone(),
+            }))),
+            hir::ExprKind::If(branches, otherwise) => {
+                for (condition, body) in branches {
+                    if as_bool(&self.eval(condition, env)?)? {
+                        return self.eval_block(body, &Env::new_child(env))
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.eval_block(body, &Env::new_child(env)),
+                    None => Ok(Value::Void),
+                }
+            }
+            hir::ExprKind::While(condition, body) => {
+                let mut last = Value::Void;
+                while as_bool(&self.eval(condition, env)?)? {
+                    last = self.eval_block(body, &Env::new_child(env))?;
+                }
+                Ok(last)
+            }
+            hir::ExprKind::ForEach(var, iterable, body) => {
+                let items = self.eval(iterable, env)?;
+                let items = self.sequence(&items)?;
+                let mut last = Value::Void;
+                self.each(&items, &mut |this, item| {
+                    let iteration_env = Env::new_child(env);
+                    iteration_env.declare(*var, item);
+                    last = this.eval_block(body, &iteration_env)?;
+                    Ok(true)
+                })?;
+                Ok(last)
+            }
+            hir::ExprKind::Match(value, arms, otherwise) => {
+                let value = self.eval(value, env)?;
+                let typ = match &value {
+                    Value::Struct(instance) => Some(instance.typ),
+                    _ => None,
+                };
+                for arm in arms {
+                    if typ.is_some_and(|typ| self.is_variant(typ, arm.variant)) {
+                        let arm_env = Env::new_child(env);
+                        if let Some(binding) = arm.binding {
+                            arm_env.declare(binding, value);
+                        }
+                        return self.eval_block(&arm.body, &arm_env)
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.eval_block(body, &Env::new_child(env)),
+                    None => fail(format!("no branch of match handles {}", match typ {
+                        Some(typ) => typ.iden(&self.file.variables).as_str(),
+                        None => value.type_name(),
+                    })),
+                }
+            }
+        }
+    }
+
+    /// Whether the struct `typ` is `variant`, or one of its variants if it is an enum (possibly nested).
+    fn is_variant(&self, typ: Variable, variant: Variable) -> bool {
+        if typ == variant {
+            return true
+        }
+        let Some(enm) = self.enums.get(&variant) else {
+            return false
+        };
+        enm.variants.iter().any(|inner| match inner {
+            hir::EnumVariant::Struct(inner) | hir::EnumVariant::Enum(inner) => self.is_variant(typ, *inner),
+            // type annotations are not scoped, so existing types are found by name
+            hir::EnumVariant::Existing(existing) => self.file.structs.iter().map(|strct| strct.var)
+                .chain(self.file.enums.iter().map(|enm| enm.var))
+                .find(|known| known.iden(&self.file.variables) == &existing.iden)
+                .is_some_and(|known| self.is_variant(typ, known)),
+        })
+    }
+
+    fn eval_all(&mut self, exprs: &'a [hir::Expr], env: &Rc<Env<'a>>) -> Result<Vec<Value<'a>>, Unwind<'a>> {
+        exprs.iter()
+            .map(|expr| self.eval(expr, env))
+            .collect()
+    }
+
+    fn eval_builtin(&mut self, builtin: Builtin, args: &'a [hir::Expr], env: &Rc<Env<'a>>) -> Eval<'a> {
+        // and/or do not evaluate the right side if the left side decides the result
+        if let (Builtin::And | Builtin::Or, [left, right]) = (builtin, args) {
+            let left = as_bool(&self.eval(left, env)?)?;
+            if left == (builtin == Builtin::Or) {
+                return Ok(Value::Bool(left))
+            }
+            return Ok(Value::Bool(as_bool(&self.eval(right, env)?)?))
+        }
+        let args = self.eval_all(args, env)?;
+        self.call_builtin(builtin, args)
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, args: Vec<Value<'a>>) -> Eval<'a> {
+        Ok(match (builtin, args.as_slice()) {
+            (Builtin::Add, [Value::Text(left), Value::Text(right)]) => Value::Text(Rc::new(format!("{left}{right}").as_str().into())),
+            (_, [left, right]) if builtin.is_arithmetic() => arithmetic(builtin, left, right)?,
+            (Builtin::Eq, [left, right]) => Value::Bool(self.values_eq(left, right)?),
+            (Builtin::Neq, [left, right]) => Value::Bool(!self.values_eq(left, right)?),
+            (Builtin::Lt, [left, right]) => Value::Bool(compare(left, right)?.is_some_and(Ordering::is_lt)),
+            (Builtin::Gt, [left, right]) => Value::Bool(compare(left, right)?.is_some_and(Ordering::is_gt)),
+            (Builtin::Le, [left, right]) => Value::Bool(compare(left, right)?.is_some_and(Ordering::is_le)),
+            (Builtin::Ge, [left, right]) => Value::Bool(compare(left, right)?.is_some_and(Ordering::is_ge)),
+            (Builtin::And, [left, right]) => Value::Bool(as_bool(left)? && as_bool(right)?),
+            (Builtin::Or, [left, right]) => Value::Bool(as_bool(left)? || as_bool(right)?),
+            (Builtin::Xor, [left, right]) => Value::Bool(as_bool(left)? != as_bool(right)?),
+            (Builtin::Neg, [Value::Int(nr)]) => match nr.checked_neg() {
+                Some(negated) => Value::Int(negated),
+                None => return fail("integer overflow in Neg"),
+            },
+            (Builtin::Neg, [value]) => Value::Float(-as_float(value)?),
+            (Builtin::Not, [value]) => Value::Bool(!as_bool(value)?),
+            (Builtin::Print | Builtin::PrintNoBreak, [value]) => {
+                let text = self.fmt_value(value);
+                let end = if builtin == Builtin::Print { "\n" } else { "" };
+                if let Err(err) = write!(self.out, "{text}{end}") {
+                    return fail(format!("could not print: {err}"))
+                }
+                Value::Void
+            }
+            (Builtin::True, []) => Value::Bool(true),
+            (Builtin::False, []) => Value::Bool(false),
+            (Builtin::Assert, [condition]) => if as_bool(condition)? {
+                Value::Void
+            } else {
+                return fail("assertion failed")
+            },
+            (Builtin::Range, [start, end]) => Value::Iter(Rc::new(Sequence::Range(as_int(start)?, as_int(end)?))),
+            (Builtin::RangeTo, [end]) => Value::Iter(Rc::new(Sequence::Range(0, as_int(end)?))),
+            (Builtin::Sqrt, [value]) => Value::Float(as_float(value)?.sqrt()),
+            (Builtin::Upper, [text]) => Value::Text(Rc::new(as_text(text)?.to_uppercase().into())),
+            (Builtin::Lower, [text]) => Value::Text(Rc::new(as_text(text)?.to_lowercase().into())),
+            (Builtin::Trim, [text]) => Value::Text(Rc::new(as_text(text)?.trim().into())),
+            (Builtin::Slice, [text, start, end]) => {
+                let text = as_text(text)?;
+                let len = text.chars().count();
+                let (start, end) = (as_index(start)?, as_index(end)?);
+                if start > end || end > len {
+                    return fail(format!("cannot slice characters {start} to {end} of text with length {len}"))
+                }
+                Value::Text(Rc::new(text.chars().skip(start).take(end - start).collect::<String>().as_str().into()))
+            }
+            (Builtin::Split, [text, separator]) => {
+                let separator = as_text(separator)?;
+                if separator.is_empty() {
+                    return fail("cannot split on empty text")
+                }
+                Value::List(Rc::new(as_text(text)?.split(separator)
+                    .map(|part| Value::Text(Rc::new(part.into())))
+                    .collect()))
+            }
+            (Builtin::ToText, [value]) => Value::Text(Rc::new(self.fmt_value(value).as_str().into())),
+            (Builtin::ToInt, [Value::Int(nr)]) => Value::Int(*nr),
+            (Builtin::ToInt, [value]) => {
+                let nr = as_float(value)?.trunc();
+                // the upper limit itself cannot be represented, so it overflows
+                if !(nr >= i64::MIN as f64 && nr < i64::MAX as f64) {
+                    return fail(format!("cannot convert {nr} to an integer"))
+                }
+                Value::Int(nr as i64)
+            }
+            (Builtin::ToFloat, [value]) => Value::Float(as_float(value)?),
+            (Builtin::Then, [condition, f]) => if as_bool(condition)? {
+                let value = self.call(f.clone(), vec![])?;
+                self.some(value)
+            } else {
+                self.none()
+            },
+            (Builtin::Alternatively, [option, condition, f]) => match self.unpack(option)? {
+                None if as_bool(condition)? => {
+                    let value = self.call(f.clone(), vec![])?;
+                    self.some(value)
+                }
+                _ => option.clone(),
+            },
+            (Builtin::Otherwise, [optional, f]) => match (self.unpack(optional)?, optional) {
+                (Some(value), _) => value,
+                // the error is passed if the function takes it
+                (None, Value::Struct(instance)) if instance.typ == self.file.prelude.err && !self.is_nullary_callable(f) => {
+                    self.call(f.clone(), vec![instance.fields[0].clone()])?
+                }
+                (None, _) => self.call(f.clone(), vec![])?,
+            },
+            (Builtin::Len, [Value::Text(text)]) => Value::Int(text.chars().count() as i64),
+            (Builtin::Len, [Value::List(items)]) => Value::Int(items.len() as i64),
+            (Builtin::Len, [Value::Map(entries)]) => Value::Int(entries.len() as i64),
+            (Builtin::Contains, [Value::Text(text), part]) => Value::Bool(text.contains(as_text(part)?)),
+            (Builtin::Contains, [Value::List(items), item]) => {
+                for known in items.iter() {
+                    if self.values_eq(known, item)? {
+                        return Ok(Value::Bool(true))
+                    }
+                }
+                Value::Bool(false)
+            }
+            (Builtin::Contains, [Value::Map(entries), key]) => Value::Bool(self.find_key(entries, key)?.is_some()),
+            (Builtin::Push, [Value::List(items), item]) => {
+                let mut items = (**items).clone();
+                items.push(item.clone());
+                Value::List(Rc::new(items))
+            }
+            (Builtin::Insert, [Value::Map(entries), key, value]) => {
+                let mut entries = (**entries).clone();
+                self.insert(&mut entries, key.clone(), value.clone())?;
+                Value::Map(Rc::new(entries))
+            }
+            (Builtin::Remove, [Value::Map(entries), key]) => {
+                let mut entries = (**entries).clone();
+                if let Some(ix) = self.find_key(&entries, key)? {
+                    entries.remove(ix);
+                }
+                Value::Map(Rc::new(entries))
+            }
+            (Builtin::Keys, [Value::Map(entries)]) => Value::Iter(Rc::new(Sequence::Keys(entries.clone()))),
+            (Builtin::Values, [Value::Map(entries)]) => Value::Iter(Rc::new(Sequence::Values(entries.clone()))),
+            (_, [target, ..]) if builtin.is_collection() && args.len() == builtin.params().len() =>
+                return fail(format!("'{}' cannot be used on {}", builtin.name(), target.type_name())),
+            (Builtin::Iter, [items]) => Value::Iter(self.sequence(items)?),
+            (Builtin::Map, [items, f]) => Value::Iter(Rc::new(Sequence::Map(self.sequence(items)?, f.clone()))),
+            (Builtin::Filter, [items, f]) => Value::Iter(Rc::new(Sequence::Filter(self.sequence(items)?, f.clone()))),
+            (Builtin::Take, [items, count]) => Value::Iter(Rc::new(Sequence::Take(self.sequence(items)?, as_int(count)?.max(0) as usize))),
+            (Builtin::Sum, [items]) => {
+                let mut total = Value::Int(0);
+                let items = self.sequence(items)?;
+                self.each(&items, &mut |_, item| {
+                    total = arithmetic(Builtin::Add, &total, &item)?;
+                    Ok(true)
+                })?;
+                total
+            }
+            (Builtin::ForEach, [items, f]) => {
+                let items = self.sequence(items)?;
+                self.each(&items, &mut |this, item| {
+                    this.call(f.clone(), vec![item])?;
+                    Ok(true)
+                })?;
+                Value::Void
+            }
+            (Builtin::Collect, [items]) => {
+                let mut collected = Vec::new();
+                let items = self.sequence(items)?;
+                self.each(&items, &mut |_, item| {
+                    collected.push(item);
+                    Ok(true)
+                })?;
+                Value::List(Rc::new(collected))
+            }
+            (Builtin::Join, [items, separator]) => {
+                let mut parts = Vec::new();
+                let items = self.sequence(items)?;
+                self.each(&items, &mut |this, item| {
+                    parts.push(this.fmt_value(&item));
+                    Ok(true)
+                })?;
+                Value::Text(Rc::new(parts.join(as_text(separator)?).as_str().into()))
+            }
+            _ => return fail(format!("'{}' expects {} arguments, got {}", builtin.name(), builtin.params().len(), args.len())),
+        })
+    }
+
+    /// Functions without parameters are called even without `()`, because
+    /// the parser cannot tell the difference.
+    fn is_nullary_callable(&self, value: &Value<'a>) -> bool {
+        match value {
+            Value::Closure(closure) => closure.closure.params.is_empty(),
+            Value::Type(typ) => self.structs.get(typ).is_some_and(|strct| strct.fields.is_empty()),
+            _ => false,
+        }
+    }
+
+    fn call(&mut self, callee: Value<'a>, args: Vec<Value<'a>>) -> Eval<'a> {
+        match callee {
+            Value::Closure(closure) => {
+                let params = &closure.closure.params;
+                if params.len() != args.len() {
+                    return fail(format!("expected {} arguments, got {}", params.len(), args.len()))
+                }
+                // lambdas are part of the function that created them, so they do not catch `return`
+                let call_env = if closure.closure.lambda {
+                    Env::new_child(&closure.env)
+                } else {
+                    Env::new_frame(&closure.env)
+                };
+                for (param, arg) in params.iter().zip(args) {
+                    call_env.declare(*param, arg);
+                }
+                if self.depth >= MAX_CALL_DEPTH {
+                    return fail(format!("more than {MAX_CALL_DEPTH} nested calls, the recursion may not end"))
+                }
+                self.depth += 1;
+                let result = self.eval_block(&closure.closure.body, &call_env);
+                self.depth -= 1;
+                match result {
+                    Ok(value) => Ok(value),
+                    Err(Unwind::Return(value, frame, _)) if Rc::ptr_eq(&frame, &call_env) => Ok(value),
+                    Err(err) => Err(err),
+                }
+            }
+            Value::Type(typ) => {
+                let iden = typ.iden(&self.file.variables);
+                let Some(strct) = self.structs.get(&typ) else {
+                    return fail(format!("cannot create '{iden}' directly, use one of the variants"))
+                };
+                if strct.fields.len() != args.len() {
+                    return fail(format!("'{iden}' has {} fields, got {} values", strct.fields.len(), args.len()))
+                }
+                Ok(Value::Struct(Rc::new(StructValue {
+                    typ,
+                    fields: args.into_boxed_slice(),
+                })))
+            }
+            Value::Builtin(builtin) => self.call_builtin(builtin, args),
+            other => fail(format!("{} cannot be called", other.type_name())),
+        }
+    }
+
+    fn member(&mut self, target: Value<'a>, iden: &str, args: Vec<Value<'a>>) -> Eval<'a> {
+        let mut method = None;
+        if let Value::Struct(instance) = &target {
+            let strct = self.structs[&instance.typ];
+            if let Some(ix) = strct.fields.iter().position(|field| field.iden.as_str() == iden) {
+                let value = instance.fields[ix].clone();
+                return if args.is_empty() { Ok(value) } else { self.call(value, args) }
+            }
+            method = strct.methods.iter()
+                .find(|method| method.var.iden(&self.file.variables).as_str() == iden)
+                .or_else(|| self.impl_method(instance.typ, iden));
+        }
+        let mut all_args = Vec::with_capacity(args.len() + 1);
+        all_args.push(target);
+        all_args.extend(args);
+        if let Some(method) = method {
+            // like named functions, methods run in the global environment
+            let closure = Value::Closure(Rc::new(ClosureValue {
+                closure: &method.closure,
+                env: self.globals.clone(),
+            }));
+            return self.call(closure, all_args)
+        }
+        let Some(builtin) = Builtin::method(iden, all_args.len() - 1) else {
+            return fail(format!("{} has no field or method '{iden}' that takes {} arguments", all_args[0].type_name(), all_args.len() - 1))
+        };
+        self.call_builtin(builtin, all_args)
+    }
+
+    /// The value in a `Some` or `Ok`, or nothing for `None` and `Err`.
+    fn unpack(&self, optional: &Value<'a>) -> Result<Option<Value<'a>>, Unwind<'a>> {
+        let prelude = &self.file.prelude;
+        match optional {
+            Value::Struct(instance) if instance.typ == prelude.some || instance.typ == prelude.ok =>
+                Ok(Some(instance.fields[0].clone())),
+            Value::Struct(instance) if instance.typ == prelude.none || instance.typ == prelude.err => Ok(None),
+            other => fail(format!("expected an option or result, got {}", other.type_name())),
+        }
+    }
+
+    fn some(&self, value: Value<'a>) -> Value<'a> {
+        Value::Struct(Rc::new(StructValue { typ: self.file.prelude.some, fields: Box::new([value]) }))
+    }
+
+    fn none(&self) -> Value<'a> {
+        Value::Struct(Rc::new(StructValue { typ: self.file.prelude.none, fields: Box::new([]) }))
+    }
+
+    /// Method from an impl for the struct, or for an enum that contains it.
+    fn impl_method(&self, typ: Variable, iden: &str) -> Option<&'a hir::Function> {
+        self.file.impls.iter()
+            .filter(|imp| self.is_variant(typ, imp.target))
+            .flat_map(|imp| imp.methods.iter())
+            .find(|method| method.var.iden(&self.file.variables).as_str() == iden)
+    }
+
+    /// Element of a list or text at a position, or value of a map by key.
+    fn index(&self, base: &Value<'a>, index: &Value<'a>) -> Eval<'a> {
+        if let Value::Map(entries) = base {
+            return match self.find_key(entries, index)? {
+                Some(ix) => Ok(entries[ix].1.clone()),
+                None => fail(format!("key {} is not in the map", self.fmt_value(index))),
+            }
+        }
+        let ix = as_int(index)?;
+        let (element, len) = match base {
+            Value::List(items) => (usize::try_from(ix).ok().and_then(|ix| items.get(ix)).cloned(), items.len()),
+            Value::Text(text) => {
+                let ch = usize::try_from(ix).ok().and_then(|ix| text.chars().nth(ix));
+                (ch.map(|ch| Value::Text(Rc::new(ch.encode_utf8(&mut [0; 4]).into()))), text.chars().count())
+            }
+            other => return fail(format!("cannot index into {}", other.type_name())),
+        };
+        match element {
+            Some(element) => Ok(element),
+            None => fail(format!("index {ix} is out of bounds for {} of length {len}", base.type_name())),
+        }
+    }
+
+    /// Position of the key among the entries of a map.
+    fn find_key(&self, entries: &[(Value<'a>, Value<'a>)], key: &Value<'a>) -> Result<Option<usize>, Unwind<'a>> {
+        for (ix, (known, _)) in entries.iter().enumerate() {
+            if self.values_eq(known, key)? {
+                return Ok(Some(ix))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Set the value for the key, replacing the current value if the key is already in the map.
+    fn insert(&self, entries: &mut Vec<(Value<'a>, Value<'a>)>, key: Value<'a>, value: Value<'a>) -> Result<(), Unwind<'a>> {
+        match self.find_key(entries, &key)? {
+            Some(ix) => entries[ix].1 = value,
+            None => entries.push((key, value)),
+        }
+        Ok(())
+    }
+
+    /// Copy of the value with the field at the end of the path replaced, since structs are values.
+    fn with_field(&self, target: Value<'a>, path: &[Identifier], value: Value<'a>) -> Eval<'a> {
+        let Some((field, rest)) = path.split_first() else {
+            return Ok(value)
+        };
+        let Value::Struct(instance) = target else {
+            return fail(format!("{} has no field '{field}'", target.type_name()))
+        };
+        let Some(ix) = self.structs[&instance.typ].fields.iter().position(|known| &known.iden == field) else {
+            return fail(format!("struct has no field '{field}'"))
+        };
+        let mut changed = Rc::unwrap_or_clone(instance);
+        let current = mem::replace(&mut changed.fields[ix], Value::Void);
+        changed.fields[ix] = self.with_field(current, rest, value)?;
+        Ok(Value::Struct(Rc::new(changed)))
+    }
+
+    fn values_eq(&self, left: &Value<'a>, right: &Value<'a>) -> Result<bool, Unwind<'a>> {
+        Ok(match (left, right) {
+            (Value::Void, Value::Void) => true,
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => as_float(left)? == as_float(right)?,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Text(left), Value::Text(right)) => left == right,
+            (Value::List(left), Value::List(right)) => {
+                if left.len() != right.len() {
+                    return Ok(false)
+                }
+                for (left_item, right_item) in left.iter().zip(right.iter()) {
+                    if !self.values_eq(left_item, right_item)? {
+                        return Ok(false)
+                    }
+                }
+                true
+            }
+            (Value::Map(left), Value::Map(right)) => {
+                if left.len() != right.len() {
+                    return Ok(false)
+                }
+                for (key, left_value) in left.iter() {
+                    let Some(ix) = self.find_key(right, key)? else {
+                        return Ok(false)
+                    };
+                    if !self.values_eq(left_value, &right[ix].1)? {
+                        return Ok(false)
+                    }
+                }
+                true
+            }
+            (Value::Struct(left), Value::Struct(right)) => {
+                if left.typ != right.typ {
+                    return Ok(false)
+                }
+                for (left_field, right_field) in left.fields.iter().zip(right.fields.iter()) {
+                    if !self.values_eq(left_field, right_field)? {
+                        return Ok(false)
+                    }
+                }
+                true
+            }
+            (Value::Type(left), Value::Type(right)) => left == right,
+            (Value::Builtin(left), Value::Builtin(right)) => left == right,
+            (Value::Closure(_), _) | (_, Value::Closure(_)) => return fail("closures cannot be compared"),
+            (Value::Iter(_), _) | (_, Value::Iter(_)) => return fail("iterators cannot be compared"),
+            _ => false,
+        })
+    }
+
+    fn fmt_value(&self, value: &Value<'a>) -> String {
+        match value {
+            Value::Void => "void".to_owned(),
+            Value::Int(nr) => nr.to_string(),
+            Value::Float(nr) => nr.to_string(),
+            Value::Bool(bool) => bool.to_string(),
+            Value::Text(text) => text.to_string(),
+            Value::Iter(_) => "<iterator>".to_owned(),
+            Value::List(items) => format!("[{}]", items.iter()
+                .map(|item| self.fmt_value(item))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            Value::Map(entries) if entries.is_empty() => "{:}".to_owned(),
+            Value::Map(entries) => format!("{{{}}}", entries.iter()
+                .map(|(key, value)| format!("{}: {}", self.fmt_value(key), self.fmt_value(value)))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            Value::Closure(_) => "<closure>".to_owned(),
+            Value::Struct(instance) => {
+                let fields = self.structs[&instance.typ].fields.iter()
+                    .zip(instance.fields.iter())
+                    .map(|(field, value)| format!("{}: {}", field.iden, self.fmt_value(value)))
+                    .collect::<Vec<_>>();
+                format!("{} {{ {} }}", instance.typ.iden(&self.file.variables), fields.join(", "))
+            }
+            Value::Type(typ) => typ.iden(&self.file.variables).to_string(),
+            Value::Builtin(builtin) => format!("<builtin {}>", builtin.name()),
+        }
+    }
+}
+
+/// Integers can be used as floats.
+fn as_float<'a>(value: &Value<'a>) -> Result<f64, Unwind<'a>> {
+    match value {
+        Value::Int(nr) => Ok(*nr as f64),
+        Value::Float(nr) => Ok(*nr),
+        other => fail(format!("expected a number, got {}", other.type_name())),
+    }
+}
+
+fn as_int<'a>(value: &Value<'a>) -> Result<i64, Unwind<'a>> {
+    match value {
+        Value::Int(nr) => Ok(*nr),
+        other => fail(format!("expected an integer, got {}", other.type_name())),
+    }
+}
+
+/// Position in a text, which should not be negative.
+fn as_index<'a>(value: &Value<'a>) -> Result<usize, Unwind<'a>> {
+    match as_int(value)? {
+        nr if nr >= 0 => Ok(nr as usize),
+        nr => fail(format!("expected a position in text, got {nr}")),
+    }
+}
+
+/// Arithmetic on two integers is checked for overflow and gives an integer, otherwise it uses floats.
+/// Integer division rounds towards zero, and for both integers and floats the remainder has the sign of the left side.
+fn arithmetic<'a>(op: Builtin, left: &Value<'a>, right: &Value<'a>) -> Eval<'a> {
+    if let (Value::Int(left), Value::Int(right)) = (left, right) {
+        let result = match op {
+            Builtin::Div | Builtin::Modulo if *right == 0 => return fail("division by zero"),
+            Builtin::Add => left.checked_add(*right),
+            Builtin::Sub => left.checked_sub(*right),
+            Builtin::Mul => left.checked_mul(*right),
+            Builtin::Div => left.checked_div(*right),
+            Builtin::Modulo => left.checked_rem(*right),
+            _ => unreachable!("not a binary arithmetic operator: {op:?}"),
+        };
+        return match result {
+            Some(nr) => Ok(Value::Int(nr)),
+            None => fail(format!("integer overflow in {op:?}")),
+        }
+    }
+    let (left, right) = (as_float(left)?, as_float(right)?);
+    if right == 0.0 && matches!(op, Builtin::Div | Builtin::Modulo) {
+        return fail("division by zero")
+    }
+    Ok(Value::Float(match op {
+        Builtin::Add => left + right,
+        Builtin::Sub => left - right,
+        Builtin::Mul => left * right,
+        Builtin::Div => left / right,
+        Builtin::Modulo => left % right,
+        _ => unreachable!("not a binary arithmetic operator: {op:?}"),
+    }))
+}
+
+/// Order of two numbers or two texts, or `None` if a number is NaN.
+fn compare<'a>(left: &Value<'a>, right: &Value<'a>) -> Result<Option<Ordering>, Unwind<'a>> {
+    match (left, right) {
+        (Value::Text(left), Value::Text(right)) => Ok(Some(left.cmp(right))),
+        (Value::Int(left), Value::Int(right)) => Ok(Some(left.cmp(right))),
+        _ => Ok(as_float(left)?.partial_cmp(&as_float(right)?)),
+    }
+}
+
+fn as_text<'a, 'b>(value: &'b Value<'a>) -> Result<&'b str, Unwind<'a>> {
+    match value {
+        Value::Text(text) => Ok(text),
+        other => fail(format!("expected text, got {}", other.type_name())),
+    }
+}
+
+fn as_bool<'a>(value: &Value<'a>) -> Result<bool, Unwind<'a>> {
+    match value {
+        Value::Bool(bool) => Ok(*bool),
+        other => fail(format!("expected a bool, got {}", other.type_name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::parse_str;
+
+    use super::*;
+
+    fn run(code: &str) -> Result<String, TelErr> {
+        let file = parse_str(PathBuf::from("test.tel"), code.to_owned())?;
+        let mut out = Vec::new();
+        execute(&file, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn run_err(code: &str) -> String {
+        match run(code) {
+            Err(TelErr::ExecuteErr { msg, .. }) => msg,
+            other => panic!("expected execution error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(run("x = 1 + 2; x.print").unwrap(), "3\n");
+        assert_eq!(run("(2 * (3 - 5) / 4 % 3).print").unwrap(), "-1\n");
+        assert_eq!(run("(-1.5 + 3).print").unwrap(), "1.5\n");
+    }
+
+    #[test]
+    fn text_and_comparison() {
+        assert_eq!(run("\"hello\".print\n(1 < 2 and !(2 == 3)).print").unwrap(), "hello\ntrue\n");
+    }
+
+    #[test]
+    fn overloaded_builtin() {
+        assert_eq!(run("range(3).sum.print\nrange(2, 4).sum.print").unwrap(), "3\n5\n");
+    }
+
+    #[test]
+    fn print_without_line_break() {
+        assert_eq!(run("\"a\".print_no_break\n1.print_no_break()\n\"b\".print").unwrap(), "a1b\n");
+    }
+
+    #[test]
+    fn booleans() {
+        let code = "mut done = false\ndone = done or true\nflags: List<Bool> = [done, !true]\n\"{flags} {true xor false}\".print";
+        assert_eq!(run(code).unwrap(), "[true, false] true\n");
+        assert_eq!(run_err("(true and false).assert"), "assertion failed");
+    }
+
+    #[test]
+    fn runaway_recursion() {
+        let code = "count = fn(n: i64): i64 {\n    1 + count(n + 1)\n}\ncount(0).print";
+        assert_eq!(run_err(code), "more than 10000 nested calls, the recursion may not end");
+        let code = "depth = fn(n: i64): i64 {\n    if (n == 0) {\n        return 0\n    }\n    1 + depth(n - 1)\n}\ndepth(9000).print";
+        assert_eq!(run(code).unwrap(), "9000\n");
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(run_err("(1 / 0).print"), "division by zero");
+    }
+
+    #[test]
+    fn errors_point_at_expression() {
+        let code = "half = fn(n: i64): i64 {\n    n / 0\n}\nx = [1, 2]\nx[half(3)].print";
+        let Err(TelErr::ExecuteErr { span, .. }) = run(code) else { panic!() };
+        assert_eq!(&code[span.start as usize..span.end as usize], "n / 0");
+        let Err(TelErr::ExecuteErr { span, .. }) = run("x = [1, 2]\ny = x[5]") else { panic!() };
+        assert_eq!(span, Span::new(15, 19));
+    }
+
+    #[test]
+    fn lists_and_maps() {
+        let code = "nums = [3, 1]\nmore = nums.push(4)\n\"{nums} {more} {more[2]} {more.len}\".print\n\
+            ages = {\"ann\": 31, \"bob\": 27, \"ann\": 32}\nages.print\nages.insert(\"cy\", 5).remove(\"bob\").print\n\
+            for (name in ages) {\n    \"{name}={ages[name]}\".print\n}\n\
+            (ages.values.sum + nums.iter.sum).print\n\
+            ([1, 2] == [1, 2] and {1: 2, 3: 4} == {3: 4, 1: 2} and more.contains(4) and \"abc\".contains(\"bc\")).print";
+        assert_eq!(run(code).unwrap(), "[3, 1] [3, 1, 4] 4 3\n{ann: 32, bob: 27}\n{ann: 32, cy: 5}\nann=32\nbob=27\n63\ntrue\n");
+        assert_eq!(run("{:}.print\n\"héllo\"[1].print").unwrap(), "{:}\né\n");
+        assert_eq!(run_err("[1, 2][-1].print"), "index -1 is out of bounds for list of length 2");
+        assert_eq!(run_err("{\"a\": 1}[\"b\"].print"), "key b is not in the map");
+    }
+
+    #[test]
+    fn integers_and_floats() {
+        assert_eq!(run("(7 / 2).print\n(-7 / 2).print\n(-7 % 3).print\n(7.0 / 2).print").unwrap(), "3\n-3\n-1\n3.5\n");
+        assert_eq!(run("(2.9.to_int + (-2.9).to_int).print\n(3.to_float / 2).print\n4f64.print").unwrap(), "0\n1.5\n4\n");
+        assert_eq!(run_err("(9223372036854775807 + 1).print"), "integer overflow in Add");
+        assert_eq!(run_err("(5 % 0).print"), "division by zero");
+        assert_eq!(run_err("(2.0 * 9223372036854775807).to_int.print"), "cannot convert 18446744073709552000 to an integer");
+    }
+
+    #[test]
+    fn if_elif_else() {
+        let code = "for (i in 1.range(4)) {\n    msg = if (i == 1) {\n        \"one\"\n    } elif (i == 2) {\n        \"two\"\n    } else {\n        \"many\"\n    }\n    msg.print\n}";
+        assert_eq!(run(code).unwrap(), "one\ntwo\nmany\n");
+    }
+
+    #[test]
+    fn while_loop_updates_outer() {
+        let code = "mut i = 0\ntotal = while (i < 5) {\n    i = i + 1\n    i * i\n}\ntotal.print";
+        assert_eq!(run(code).unwrap(), "25\n");
+    }
+
+    #[test]
+    fn recursive_function() {
+        let code = "fac(5).print\nfac = fn(n) {\n    if (n <= 1) {\n        return 1\n    }\n    n * fac(n - 1)\n}";
+        assert_eq!(run(code).unwrap(), "120\n");
+    }
+
+    #[test]
+    fn closure_captures_and_trailing_closure() {
+        let code = "apply = fn(x, f) {\n    f(x)\n}\nfactor = 3\napply(2) fn(n) { n * factor }.print";
+        assert_eq!(run(code).unwrap(), "6\n");
+    }
+
+    #[test]
+    fn implicit_it_and_capture() {
+        let code = "twice = fn(f) { fn(x) { f(f(x)) } }\nadd6 = twice { it + 3 }\nadd6(1).print\n\
+            make = fn(n) { { it * n } }\ntimes4 = make(4)\ntimes4(2).print\nmut count = 0\ninc = { outer count += 1 }\ninc()\ninc\ncount.print";
+        assert_eq!(run(code).unwrap(), "7\n8\n2\n");
+    }
+
+    #[test]
+    fn lazy_iterators() {
+        assert_eq!(run("1.range(1000000000).map { it * 2 }.take(3).sum.print").unwrap(), "12\n");
+        let code = "evens = 10.range.filter { it % 2 == 0 }.collect\nevens.print\nfor (x in evens.iter.take(2)) {\n    x.print\n}\n\"ab\".map { it.upper }.for_each { it.print }";
+        assert_eq!(run(code).unwrap(), "[0, 2, 4, 6, 8]\n0\n2\nA\nB\n");
+        assert_eq!(run("(3.range.collect == 3.range.collect).print").unwrap(), "true\n");
+        assert_eq!(run_err("x = 3.range == 3.range"), "iterators cannot be compared");
+    }
+
+    #[test]
+    fn text_operations() {
+        let code = "x = 4\n\"x is {x}, half is {x / 2}\\tok\".print\n('a' + \"b\" + 1.5.to_text).print\n(\"apple\" < \"banana\" and \"b\" >= \"b\").print";
+        assert_eq!(run(code).unwrap(), "x is 4, half is 2\tok\nab1.5\ntrue\n");
+        let code = "words = \"dé jà vu\".split(\" \")\nwords.print\nwords.iter.map { it.upper }.join(\"-\").print\n\"héllo\".slice(1, 3).print";
+        assert_eq!(run(code).unwrap(), "[dé, jà, vu]\nDÉ-JÀ-VU\nél\n");
+        assert_eq!(run_err("\"abc\".slice(2, 5).print"), "cannot slice characters 2 to 5 of text with length 3");
+        assert_eq!(run_err("\"abc\".slice(-1, 1).print"), "expected a position in text, got -1");
+    }
+
+    #[test]
+    fn struct_construct_and_fields() {
+        let code = "struct Point {\n    x: f64,\n    y: f64,\n}\np = Point(1, 2)\n(p.x + p.y).print\np.print";
+        assert_eq!(run(code).unwrap(), "3\nPoint { x: 1, y: 2 }\n");
+    }
+
+    #[test]
+    fn enum_variants() {
+        let code = "enum Shape {\n    struct Circle { radius: f64 },\n    struct Square { side: f64 },\n}\nshape = Square(2)\n(shape.side * shape.side).print";
+        assert_eq!(run(code).unwrap(), "4\n");
+    }
+
+    #[test]
+    fn use_before_assign() {
+        assert_eq!(run_err("f()\nf = fn() {\n    x\n}\nx = 1"), "'x' was used before it was assigned");
+    }
+
+    #[test]
+    fn prelude_functions() {
+        assert_eq!(run("op_add(1, 2).print\n\"  Hi \".trim.upper.print\n\"abc\".len.print").unwrap(), "3\nHI\n3\n");
+        assert_eq!(run("show = print\nshow(op_not(1 > 2))").unwrap(), "true\n");
+        assert_eq!(run_err("(1 == 1).assert\n(1 == 2).assert"), "assertion failed");
+    }
+
+    #[test]
+    fn function_shadow_and_outer() {
+        assert_eq!(run("x = 1\nf = fn() {\n    x = 2\n    x\n}\nf().print\nx.print").unwrap(), "2\n1\n");
+        assert_eq!(run("mut count = 0\ninc = fn() {\n    outer count = count + 1\n}\ninc()\ninc()\ncount.print").unwrap(), "2\n");
+    }
+
+    #[test]
+    fn compound_assignment() {
+        assert_eq!(run("mut x = 7\nx += 3\nx -= 1\nx *= 2\nx /= 3\nx %= 5\nx.print").unwrap(), "1\n");
+        assert_eq!(run_err("mut x = 1\nx /= 0"), "division by zero");
+    }
+
+    #[test]
+    fn match_nested_variants() {
+        let code = "enum Shape {\n    struct Circle { radius: f64 },\n    enum Polygon {\n        struct Square { side: f64 },\n        struct Triangle { base: f64, height: f64 },\n    },\n}\n\
+            area = fn(shape: Shape) {\n    match (shape) {\n        Circle c { c.radius * c.radius * 3 }\n        Square s { s.side * s.side }\n        Polygon { -1 }\n    }\n}\n\
+            area(Circle(1)).print\narea(Square(2)).print\narea(Triangle(1, 2)).print";
+        assert_eq!(run(code).unwrap(), "3\n4\n-1\n");
+        assert_eq!(run("struct A {}\nx = match (A) {\n    A { 1 }\n    else { 2 }\n}\nx.print").unwrap(), "1\n");
+    }
+
+    #[test]
+    fn interface_methods() {
+        let code = "interface Priced {\n    fn cents(): f64\n}\nenum Currency {\n    struct Euro { amount: f64 },\n    struct Dollar { amount: f64 },\n}\n\
+            impl Priced for Currency {\n    fn cents(): f64 {\n        match (self) {\n            Euro e { e.amount * 100 }\n            Dollar d { d.amount * 90 }\n        }\n    }\n}\n\
+            total = fn<P: Priced>(items: P): f64 { items.cents }\ntotal(Euro(2)).print\nDollar(1).cents.print";
+        assert_eq!(run(code).unwrap(), "200\n90\n");
+    }
+
+    #[test]
+    fn struct_methods() {
+        let code = "struct Vec2 {\n    x: f64,\n    y: f64,\n    fn dot(other) { x * other.x + self.y * other.y }\n    fn norm() { dot(self).sqrt }\n}\nVec2(3, 4).norm.print";
+        assert_eq!(run(code).unwrap(), "5\n");
+    }
+
+    #[test]
+    fn set_field_copies_value() {
+        let code = "struct Line {\n    head: Point,\n    tail: Point,\n}\nstruct Point {\n    x: f64,\n    y: f64,\n}\nmut a = Line(Point(0, 0), Point(1, 1))\nb = a\na.tail.x += 2\na.head = Point(5, 5)\na.print\nb.print";
+        assert_eq!(run(code).unwrap(), "Line { head: Point { x: 5, y: 5 }, tail: Point { x: 3, y: 1 } }\nLine { head: Point { x: 0, y: 0 }, tail: Point { x: 1, y: 1 } }\n");
+    }
+
+    #[test]
+    fn options_and_results() {
+        let code = "half = fn(n: i64): Result<i64, Text> {\n    if (n % 2 == 0) {\n        return Ok(n / 2)\n    }\n    Err(\"{n} is odd\")\n}\n\
+            quarter = fn(n: i64) {\n    h = half(n)?\n    Ok(half(h)?)\n}\n\
+            quarter(8).print\nquarter(6).otherwise { it.upper }.print\nquarter(4).otherwise { 0 }.print\nquarter(2).otherwise { -1 }.print";
+        assert_eq!(run(code).unwrap(), "Ok { value: 2 }\n3 IS ODD\n1\n-1\n");
+        let code = "for (n in [3, 5, 7]) {\n    (n == 3).then { \"three\" }.alternatively(n == 5) { \"five\" }.otherwise { n.to_text }.print\n}\n\
+            found = [1, 2, 3].iter.filter { it > 1 }.collect\n(found.len > 0).then { found[0] }.print";
+        assert_eq!(run(code).unwrap(), "three\nfive\n7\nSome { value: 2 }\n");
+        assert_eq!(run("x = None?\n\"not reached\".print").unwrap(), "");
+        assert_eq!(run_err("f = fn(x) { x? }\nf(1)"), "expected an option or result, got integer");
+    }
+
+    #[test]
+    fn non_local_return() {
+        let code = "find = fn(items) {\n    items.iter.for_each {\n        if (it > 2) {\n            return it\n        }\n    }\n    0\n}\nfind([1, 5, 7]).print\nfind([1]).print";
+        assert_eq!(run(code).unwrap(), "5\n0\n");
+        let code = "[1, 2, 3].iter.for_each {\n    it.print\n    if (it == 2) {\n        return 0\n    }\n}\n\"not reached\".print";
+        assert_eq!(run(code).unwrap(), "1\n2\n");
+    }
+}
@@ -0,0 +1,76 @@
+use std::rc::Rc;
+
+use tel_common::SString;
+use tel_hir as hir;
+use tel_hir::Builtin;
+use tel_hir::Variable;
+
+use crate::execute::env::Env;
+
+/// Runtime value. Anything that is not a number or boolean is reference-counted,
+/// so that values can be cloned freely while evaluating.
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Void,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(Rc<SString>),
+    Iter(Rc<Sequence<'a>>),
+    List(Rc<Vec<Value<'a>>>),
+    /// Entries in the order they were added, where each key is different.
+    Map(Rc<Vec<(Value<'a>, Value<'a>)>>),
+    Closure(Rc<ClosureValue<'a>>),
+    Struct(Rc<StructValue<'a>>),
+    /// The name of a struct or enum, which for structs can be called to construct an instance.
+    Type(Variable),
+    Builtin(Builtin),
+}
+
+/// Lazy description of the values of an iterator, which are only computed when it is consumed.
+/// It can be consumed more than once, which repeats the computation.
+#[derive(Debug)]
+pub enum Sequence<'a> {
+    /// Integers from the start (inclusive) to the end (exclusive).
+    Range(i64, i64),
+    Chars(Rc<SString>),
+    Items(Rc<Vec<Value<'a>>>),
+    Keys(Rc<Vec<(Value<'a>, Value<'a>)>>),
+    Values(Rc<Vec<(Value<'a>, Value<'a>)>>),
+    Map(Rc<Sequence<'a>>, Value<'a>),
+    Filter(Rc<Sequence<'a>>, Value<'a>),
+    Take(Rc<Sequence<'a>>, usize),
+}
+
+#[derive(Debug)]
+pub struct ClosureValue<'a> {
+    pub closure: &'a hir::Closure,
+    /// Scope where the closure was created, so it can access variables from there.
+    pub env: Rc<Env<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructValue<'a> {
+    pub typ: Variable,
+    /// In the order of declaration.
+    pub fields: Box<[Value<'a>]>,
+}
+
+impl Value<'_> {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Void => "void",
+            Value::Int(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Text(_) => "text",
+            Value::Iter(_) => "iterator",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Closure(_) => "closure",
+            Value::Struct(_) => "struct",
+            Value::Type(_) => "type",
+            Value::Builtin(_) => "function",
+        }
+    }
+}
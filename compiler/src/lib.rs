@@ -1,4 +1,12 @@
+use crate::execute::execute;
+pub use crate::lsp::tel_lsp;
+use crate::modules::load_modules;
+use crate::modules::locate_err;
+use crate::modules::Module;
+use crate::modules::ModuleErr;
 use crate::scoping::ast_to_api;
+use crate::typing::check_types;
+use crate::typing::Types;
 use std::fs;
 use std::io::stdout;
 use std::io::BufWriter;
@@ -9,25 +17,91 @@ use std::path::PathBuf;
 use log::debug;
 use log::warn;
 use serde::Serialize;
-use tel_ast::{ParseErr, TelFile};
+use tel_ast::ParseErr;
+use tel_common::Diagnostic;
 use tel_common::TelErr;
+use tel_hir::TelFile;
 use tel_parser::str_to_ast;
+use tel_ast_to_code::format_code;
 
+mod execute;
+mod lsp;
+mod modules;
 mod scoping;
+mod typing;
+#[cfg(test)]
 mod examples;
 
 pub fn parse_str(src_pth: PathBuf, code: String) -> Result<TelFile, TelErr> {
-    let ast = str_to_ast(src_pth, code).map_err(parse_err_to_tel_err)?;
-    ast_to_api(ast)
+    Ok(check_str(src_pth, code).map_err(|err| err.err)?.1)
+}
+
+/// Check the file and the files it imports, which are found next to it.
+fn check_str(src_pth: PathBuf, code: String) -> Result<(Vec<Module>, TelFile, Types), ModuleErr> {
+    let ast = str_to_ast(src_pth.clone(), code.clone())
+        .map_err(|err| ModuleErr::new(parse_err_to_tel_err(err), &src_pth, &code))?;
+    let modules = load_modules(src_pth, code, ast)?;
+    let (file, types) = check_modules(&modules)?;
+    Ok((modules, file, types))
+}
+
+/// Check the modules together, with errors reported for the file that has them.
+/// Returns the last module, together with everything before it.
+fn check_modules(modules: &[Module]) -> Result<(TelFile, Types), ModuleErr> {
+    let file = ast_to_api(modules).map_err(|err| locate_err(modules, err))?;
+    let types = check_types(&file).map_err(|err| locate_err(modules, err))?;
+    Ok((file, types))
+}
+
+/// Error together with the source code it is about, so it can be shown with the relevant code.
+#[derive(Debug)]
+pub struct ErrReport {
+    pub diagnostics: Vec<Diagnostic>,
+    code: String,
+}
+
+impl ErrReport {
+    fn new(err: TelErr, path: &Path, code: &str) -> Self {
+        let mut diagnostics = err.diagnostics();
+        for diagnostic in &mut diagnostics {
+            if diagnostic.file.is_none() {
+                diagnostic.file = Some(path.to_owned());
+            }
+        }
+        ErrReport { diagnostics, code: code.to_owned() }
+    }
+
+    /// All the diagnostics, separated by an empty line.
+    pub fn render_human(&self, color: bool) -> String {
+        self.diagnostics.iter()
+            .map(|diagnostic| diagnostic.render_human(&self.code, color))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// One json object per line for each diagnostic.
+    pub fn render_json(&self) -> String {
+        self.diagnostics.iter()
+            .map(|diagnostic| diagnostic.render_json(&self.code))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl From<TelErr> for ErrReport {
+    /// For errors that are not about the content of a file.
+    fn from(err: TelErr) -> Self {
+        ErrReport { diagnostics: err.diagnostics(), code: String::new() }
+    }
 }
 
 fn parse_err_to_tel_err(err: ParseErr) -> TelErr {
     match err {
         ParseErr::FileNotFound { file } => TelErr::FileNotFound { file },
         ParseErr::CouldNotRead(path, msg) => TelErr::CouldNotRead(path, msg),
-        ParseErr::ParseErr { file, line, msg } => TelErr::ParseErr { file, line, msg },
-        ParseErr::ScopeErr { msg } => TelErr::ScopeErr { msg },
-        ParseErr::UnknownIdentifier(iden) => TelErr::UnknownIdentifier(iden),
+        ParseErr::ParseErr { file, diagnostics } => TelErr::ParseErr { file, diagnostics },
+        ParseErr::ScopeErr { span, previous, msg } => TelErr::ScopeErr { span, previous, msg },
+        ParseErr::UnknownIdentifier { iden, span } => TelErr::UnknownIdentifier { iden, span },
     }
 }
 
@@ -37,30 +111,74 @@ pub struct BuildArgs {
     pub verbose: bool,
 }
 
-pub fn tel_build(args: &BuildArgs) -> Result<(), TelErr> {
+pub fn tel_build(args: &BuildArgs) -> Result<(), ErrReport> {
     let path = find_main_file(&args.path)?;
     let source = fs::read_to_string(&path)
         .map_err(|err| TelErr::CouldNotRead(path.clone(), err.to_string()))?;
     tel_build_str(path, source, false)
 }
 
+#[derive(Debug)]
+pub struct FmtArgs {
+    pub path: PathBuf,
+    /// Only report whether the file is formatted, instead of changing it.
+    pub check: bool,
+}
+
+/// Rewrite the file in canonical formatting, keeping comments.
+pub fn tel_fmt(args: &FmtArgs) -> Result<(), ErrReport> {
+    let path = find_main_file(&args.path)?;
+    let source = fs::read_to_string(&path)
+        .map_err(|err| TelErr::CouldNotRead(path.clone(), err.to_string()))?;
+    let formatted = fmt_str(path.clone(), source.clone())
+        .map_err(|err| ErrReport::new(err, &path, &source))?;
+    if formatted == source {
+        debug!("'{}' is already formatted", path.display());
+        return Ok(());
+    }
+    if args.check {
+        return Err(TelErr::NotFormatted { file: path }.into());
+    }
+    fs::write(&path, formatted)
+        .map_err(|err| TelErr::CouldNotWrite(path.clone(), err.to_string()))?;
+    Ok(())
+}
+
+/// Canonical formatting of the code. Only needs the syntax to be valid, not the names or types.
+pub fn fmt_str(path: PathBuf, code: String) -> Result<String, TelErr> {
+    let ast = str_to_ast(path, code.clone()).map_err(parse_err_to_tel_err)?;
+    Ok(format_code(&ast, &code))
+}
+
 #[derive(Debug, Serialize)]
 struct DebugInfo<'a> {
-    ast: &'a TelFile,
+    hir: &'a TelFile,
+    types: Vec<String>,
 }
 
-pub fn tel_build_str(path: PathBuf, code: String, debug: bool) -> Result<(), TelErr> {
-    let prog = parse_str(path, code)?;
-    print_debug(debug, &prog);
+pub fn tel_build_str(path: PathBuf, code: String, debug: bool) -> Result<(), ErrReport> {
+    let (_, prog, types) = check_str(path, code)?;
+    print_debug(debug, &prog, &types);
     Ok(())
 }
 
-fn print_debug(debug: bool, file: &TelFile) {
+/// Build the code and run it with the interpreter, printing to stdout.
+pub fn tel_run_str(path: PathBuf, code: String, debug: bool) -> Result<(), ErrReport> {
+    let (modules, prog, types) = check_str(path, code)?;
+    print_debug(debug, &prog, &types);
+    execute(&prog, stdout())
+        .map_err(|err| locate_err(&modules, err).into())
+}
+
+fn print_debug(debug: bool, file: &TelFile, types: &Types) {
     if !debug {
         return;
     }
     let mut out = BufWriter::new(stdout().lock());
-    serde_json::to_writer_pretty(&mut out, &DebugInfo { ast: file }).unwrap();
+    serde_json::to_writer_pretty(&mut out, &DebugInfo {
+        hir: file,
+        types: types.describe(&file.variables),
+    }).unwrap();
     out.write_all(b"\n").unwrap();
     out.flush().unwrap()
 }
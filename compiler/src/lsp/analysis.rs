@@ -0,0 +1,199 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use tel_common::Diagnostic;
+use tel_common::Span;
+use tel_hir::Reference;
+use tel_hir::TelFile;
+use tel_hir::Variable;
+use tel_parser::str_to_ast_partial;
+
+use crate::check_modules;
+use crate::modules::load_modules;
+use crate::modules::ModuleErr;
+use crate::scoping::ast_to_api;
+use crate::typing::check_types;
+use crate::typing::Types;
+
+/// Everything the language server knows about one open document.
+/// Navigation only works if scoping succeeded, but that is attempted even if the syntax
+/// is broken, by using the statements that did parse.
+pub struct Analysis {
+    pub code: String,
+    pub diagnostics: Vec<Diagnostic>,
+    file: Option<TelFile>,
+    types: Option<Types>,
+}
+
+impl Analysis {
+    pub fn new(path: PathBuf, code: String) -> Self {
+        let (ast, parse_diagnostics) = str_to_ast_partial(code.clone());
+        let mut diagnostics = parse_diagnostics.clone();
+        // the imported files are checked by themselves first, so their errors are not reported as part of this file
+        let modules = load_modules(path.clone(), code.clone(), ast)
+            .and_then(|modules| match modules.split_last() {
+                Some((_, imported)) if !imported.is_empty() => check_modules(imported).map(|_| modules),
+                _ => Ok(modules),
+            });
+        let modules = match modules {
+            Ok(modules) => modules,
+            Err(err) => {
+                diagnostics.extend(module_diagnostics(err, &path));
+                return Analysis { code, diagnostics, file: None, types: None }
+            }
+        };
+        let (file, types) = match ast_to_api(&modules) {
+            Ok(file) => {
+                let types = match check_types(&file) {
+                    Ok(types) => Some(types),
+                    Err(err) => {
+                        diagnostics.extend(err.diagnostics());
+                        None
+                    }
+                };
+                (Some(file), types)
+            }
+            Err(err) => {
+                // unknown names are expected while the broken statements are missing
+                if parse_diagnostics.is_empty() {
+                    diagnostics.extend(err.diagnostics());
+                }
+                (None, None)
+            }
+        };
+        for diagnostic in &mut diagnostics {
+            diagnostic.file.get_or_insert_with(|| path.clone());
+        }
+        Analysis { code, diagnostics, file, types }
+    }
+
+    /// The innermost variable use or assignment at the byte offset.
+    fn reference_at(&self, offset: usize) -> Option<Reference> {
+        let offset = offset as u32;
+        self.file.as_ref()?.references.iter()
+            .filter(|re| re.span.start <= offset && offset <= re.span.end)
+            .min_by_key(|re| re.span.len())
+            .copied()
+    }
+
+    /// Where the variable at the offset is declared.
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        let file = self.file.as_ref()?;
+        let reference = self.reference_at(offset)?;
+        //TODO @mark: go to the declaration in the imported file
+        if !file.declares(reference.var) {
+            return None;
+        }
+        Some(reference.var.span(&file.variables))
+    }
+
+    /// The name and type of the variable at the offset, and the span of the reference.
+    pub fn hover(&self, offset: usize) -> Option<(Span, String)> {
+        let reference = self.reference_at(offset)?;
+        Some((reference.span, self.describe(reference.var)))
+    }
+
+    /// Variables that are visible at the offset, as name and type. Shadowed variables are skipped.
+    pub fn completions(&self, offset: usize) -> Vec<(String, String)> {
+        let Some(file) = &self.file else {
+            return Vec::new();
+        };
+        let offset = offset as u32;
+        let mut completions: Vec<(String, String)> = Vec::new();
+        // later declarations shadow earlier ones, so iterate in reverse
+        for data in file.variables.iter().rev() {
+            if data.visible.start > offset || offset > data.visible.end || !file.can_use(data.refer()) {
+                continue;
+            }
+            let name = data.iden.as_str();
+            if completions.iter().any(|(existing, _)| existing == name) {
+                continue;
+            }
+            completions.push((name.to_owned(), self.type_of(data.refer())));
+        }
+        completions.sort();
+        completions
+    }
+
+    fn describe(&self, var: Variable) -> String {
+        let variables = &self.file.as_ref().expect("variable without file").variables;
+        format!("{}: {}", var.iden(variables), self.type_of(var))
+    }
+
+    fn type_of(&self, var: Variable) -> String {
+        let variables = &self.file.as_ref().expect("variable without file").variables;
+        if let Some(typ) = self.types.as_ref().and_then(|types| types.of(var)) {
+            return typ.show(variables);
+        }
+        match var.type_annotation(variables) {
+            Some(typ) => typ.iden.as_str().to_owned(),
+            None => "?".to_owned(),
+        }
+    }
+}
+
+/// Errors in other files are shown without position, since the positions are in those files.
+fn module_diagnostics(err: ModuleErr, path: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = err.err.diagnostics();
+    for diagnostic in &mut diagnostics {
+        diagnostic.file.get_or_insert_with(|| err.path.clone());
+        if diagnostic.file.as_deref() != Some(path) {
+            diagnostic.notes.push(format!("in imported file '{}'", err.path.display()));
+            diagnostic.labels.clear();
+        }
+    }
+    diagnostics
+}
+
+/// Zero-based line and UTF-16 column, which is how the protocol counts positions.
+pub fn to_position(code: &str, offset: usize) -> (u32, u32) {
+    let mut line = 0;
+    let mut character = 0;
+    for (ix, ch) in code.char_indices() {
+        if ix >= offset {
+            break;
+        }
+        if ch == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += ch.len_utf16() as u32;
+        }
+    }
+    (line, character)
+}
+
+/// Byte offset of a protocol position, clamped to the end of the line.
+pub fn to_offset(code: &str, line: u32, character: u32) -> usize {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match code[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return code.len(),
+        }
+    }
+    let mut remaining = character;
+    for (ix, ch) in code[line_start..].char_indices() {
+        if remaining == 0 || ch == '\n' {
+            return line_start + ix;
+        }
+        remaining = remaining.saturating_sub(ch.len_utf16() as u32);
+    }
+    code.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let code = "é = 1\nab = é\n";
+        assert_eq!(to_position(code, 0), (0, 0));
+        assert_eq!(to_position(code, 3), (0, 2));
+        assert_eq!(to_position(code, 9), (1, 2));
+        assert_eq!(to_offset(code, 0, 2), 3);
+        assert_eq!(to_offset(code, 1, 2), 9);
+        assert_eq!(to_offset(code, 1, 99), 14);
+    }
+}
@@ -0,0 +1,357 @@
+//! Language server over stdio, speaking the JSON-RPC based language server protocol.
+//!
+//! Documents are analyzed from scratch on every change, which is fast enough for single files.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use log::debug;
+use log::warn;
+use serde_json::json;
+use serde_json::Value;
+use tel_common::Diagnostic;
+use tel_common::Severity;
+use tel_common::Span;
+
+use self::analysis::to_offset;
+use self::analysis::to_position;
+use self::analysis::Analysis;
+use self::rpc::read_message;
+use self::rpc::write_message;
+
+mod analysis;
+mod rpc;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serve requests from `input` until the client sends `exit` or closes the stream.
+pub fn tel_lsp(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut server = Server { documents: HashMap::new() };
+    while let Some(msg) = read_message(&mut input)? {
+        let method = msg["method"].as_str().unwrap_or("");
+        debug!("lsp message '{method}'");
+        if method == "exit" {
+            break;
+        }
+        let id = msg.get("id").cloned();
+        let outgoing = server.handle(method, &msg["params"]);
+        for notification in outgoing.notifications {
+            write_message(&mut output, &notification)?;
+        }
+        // notifications have no id and get no response
+        let Some(id) = id else {
+            continue;
+        };
+        let response = match outgoing.result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        };
+        write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+struct Server {
+    documents: HashMap<String, Analysis>,
+}
+
+struct Outgoing {
+    result: Result<Value, (i64, String)>,
+    notifications: Vec<Value>,
+}
+
+impl Outgoing {
+    fn reply(result: Value) -> Self {
+        Outgoing { result: Ok(result), notifications: Vec::new() }
+    }
+}
+
+impl Server {
+    fn handle(&mut self, method: &str, params: &Value) -> Outgoing {
+        match method {
+            "initialize" => Outgoing::reply(json!({
+                "capabilities": {
+                    // full document sync
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "tel", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "initialized" | "$/cancelRequest" | "$/setTrace" => Outgoing::reply(Value::Null),
+            "shutdown" => Outgoing::reply(Value::Null),
+            "textDocument/didOpen" => {
+                let doc = &params["textDocument"];
+                self.update(doc["uri"].as_str(), doc["text"].as_str())
+            }
+            "textDocument/didChange" => {
+                let text = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                self.update(params["textDocument"]["uri"].as_str(), text)
+            }
+            "textDocument/didClose" => {
+                let Some(uri) = params["textDocument"]["uri"].as_str() else {
+                    return invalid_params();
+                };
+                self.documents.remove(uri);
+                Outgoing {
+                    result: Ok(Value::Null),
+                    notifications: vec![publish_diagnostics(uri, Path::new(""), "", &[])],
+                }
+            }
+            "textDocument/definition" => self.at_position(params, |uri, doc, offset| {
+                match doc.definition(offset) {
+                    Some(span) => json!({ "uri": uri, "range": range(&doc.code, span) }),
+                    None => Value::Null,
+                }
+            }),
+            "textDocument/hover" => self.at_position(params, |_, doc, offset| {
+                match doc.hover(offset) {
+                    Some((span, text)) => json!({
+                        "contents": { "kind": "markdown", "value": format!("```tel\n{text}\n```") },
+                        "range": range(&doc.code, span),
+                    }),
+                    None => Value::Null,
+                }
+            }),
+            "textDocument/completion" => self.at_position(params, |_, doc, offset| {
+                let items = doc.completions(offset).into_iter()
+                    // kind 6 is variable
+                    .map(|(label, detail)| json!({ "label": label, "kind": 6, "detail": detail }))
+                    .collect::<Vec<_>>();
+                Value::Array(items)
+            }),
+            _ => {
+                warn!("unsupported lsp method '{method}'");
+                Outgoing {
+                    result: Err((METHOD_NOT_FOUND, format!("method '{method}' is not supported"))),
+                    notifications: Vec::new(),
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, uri: Option<&str>, text: Option<&str>) -> Outgoing {
+        let (Some(uri), Some(text)) = (uri, text) else {
+            return invalid_params();
+        };
+        let Some(path) = file_path(uri) else {
+            return Outgoing {
+                result: Err((INVALID_PARAMS, format!("'{uri}' is not a file uri"))),
+                notifications: Vec::new(),
+            }
+        };
+        let doc = Analysis::new(path.clone(), text.to_owned());
+        let notification = publish_diagnostics(uri, &path, &doc.code, &doc.diagnostics);
+        self.documents.insert(uri.to_owned(), doc);
+        Outgoing {
+            result: Ok(Value::Null),
+            notifications: vec![notification],
+        }
+    }
+
+    fn at_position(&self, params: &Value, answer: impl FnOnce(&str, &Analysis, usize) -> Value) -> Outgoing {
+        let uri = params["textDocument"]["uri"].as_str();
+        let line = params["position"]["line"].as_u64();
+        let character = params["position"]["character"].as_u64();
+        let (Some(uri), Some(line), Some(character)) = (uri, line, character) else {
+            return invalid_params();
+        };
+        let Some(doc) = self.documents.get(uri) else {
+            // documents that were never opened have no information
+            return Outgoing::reply(Value::Null);
+        };
+        let offset = to_offset(&doc.code, line as u32, character as u32);
+        Outgoing::reply(answer(uri, doc, offset))
+    }
+}
+
+fn invalid_params() -> Outgoing {
+    Outgoing {
+        result: Err((INVALID_PARAMS, "missing document or position".to_owned())),
+        notifications: Vec::new(),
+    }
+}
+
+/// Path of a `file://` uri, which may have a host and percent-encoded bytes, like `file:///my%20dir/main.tel`.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // the host is empty or 'localhost' for local files
+    let encoded = &rest[rest.find('/')?..];
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+fn range(code: &str, span: Span) -> Value {
+    let (start_line, start_char) = to_position(code, span.start as usize);
+    let (end_line, end_char) = to_position(code, span.end as usize);
+    json!({
+        "start": { "line": start_line, "character": start_char },
+        "end": { "line": end_line, "character": end_char },
+    })
+}
+
+fn publish_diagnostics(uri: &str, path: &Path, code: &str, diagnostics: &[Diagnostic]) -> Value {
+    let diagnostics = diagnostics.iter()
+        .filter_map(|diagnostic| {
+            // errors in imported files have no position here, so they are shown at the start of the file
+            let span = match diagnostic.primary_span() {
+                Some(span) => span,
+                None if diagnostic.file.as_deref() != Some(path) => Span::new(0, 0),
+                None => {
+                    warn!("diagnostic {} has no position", diagnostic.code);
+                    return None
+                }
+            };
+            let mut message = diagnostic.msg.clone();
+            for note in &diagnostic.notes {
+                message.push_str("\nhelp: ");
+                message.push_str(note);
+            }
+            Some(json!({
+                "range": range(code, span),
+                "severity": match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                },
+                "code": diagnostic.code,
+                "source": "tel",
+                "message": message,
+            }))
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const URI: &str = "file:///tmp/main.tel";
+
+    /// Send the messages to the server as a client would, and collect everything it writes.
+    fn run_client(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for msg in messages {
+            write_message(&mut input, msg).unwrap();
+        }
+        let mut output = Vec::new();
+        tel_lsp(Cursor::new(input), &mut output).unwrap();
+        let mut output = Cursor::new(output);
+        let mut received = Vec::new();
+        while let Some(msg) = read_message(&mut output).unwrap() {
+            received.push(msg);
+        }
+        received
+    }
+
+    fn open(code: &str) -> Value {
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": URI, "languageId": "tel", "version": 1, "text": code },
+        }})
+    }
+
+    fn request(id: u32, method: &str, line: u32, character: u32) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        }})
+    }
+
+    fn response(received: &[Value], id: u32) -> &Value {
+        &received.iter().find(|msg| msg["id"] == id).expect("no response")["result"]
+    }
+
+    #[test]
+    fn session() {
+        let code = "x = 1\ny = x + 2\n";
+        let received = run_client(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            open(code),
+            request(2, "textDocument/definition", 1, 4),
+            request(3, "textDocument/hover", 1, 4),
+            request(4, "textDocument/completion", 1, 0),
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+        assert_eq!(response(&received, 1)["capabilities"]["hoverProvider"], true);
+        let published = received.iter().find(|msg| msg["method"] == "textDocument/publishDiagnostics").unwrap();
+        assert_eq!(published["params"]["diagnostics"], json!([]));
+        assert_eq!(response(&received, 2)["range"], json!({
+            "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 },
+        }));
+        assert_eq!(response(&received, 3)["contents"]["value"], "```tel\nx: i64\n```");
+        let labels = response(&received, 4).as_array().unwrap().iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["Err", "None", "Ok", "Option", "Result", "Some", "x", "y"]);
+        assert_eq!(response(&received, 5), &Value::Null);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let received = run_client(&[open("a = 1\nb = a + c\n")]);
+        let diagnostics = &received[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "E0201");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 8 }));
+    }
+
+    #[test]
+    fn file_uris() {
+        assert_eq!(file_path("file:///tmp/my%20dir/main.tel"), Some(PathBuf::from("/tmp/my dir/main.tel")));
+        assert_eq!(file_path("file://localhost/tmp/%C3%A9.tel"), Some(PathBuf::from("/tmp/é.tel")));
+        assert_eq!(file_path("file:///tmp/%2"), None);
+        assert_eq!(file_path("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn imports_next_to_encoded_path() {
+        let dir = std::env::temp_dir().join(format!("tel-lsp dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("util.tel"), "pub twice = fn(x: i64): i64 { x * 2 }\n").unwrap();
+        let uri = format!("file://{}/main.tel", dir.display()).replace(' ', "%20");
+        let received = run_client(&[json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": uri, "languageId": "tel", "version": 1, "text": "import util { twice }\nx = twice(1)\n" },
+        }})]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(received[0]["params"]["uri"], uri);
+        assert_eq!(received[0]["params"]["diagnostics"], json!([]));
+    }
+
+    #[test]
+    fn syntax_errors_keep_navigation() {
+        let code = "a = 1\nb = = 2\nc = a\n";
+        let received = run_client(&[open(code), request(1, "textDocument/definition", 2, 4)]);
+        assert_eq!(received[0]["params"]["diagnostics"][0]["code"], "E0102");
+        assert_eq!(response(&received, 1)["range"]["start"], json!({ "line": 0, "character": 0 }));
+    }
+
+    #[test]
+    fn unknown_method() {
+        let received = run_client(&[json!({ "jsonrpc": "2.0", "id": 1, "method": "workspace/symbol", "params": {} })]);
+        assert_eq!(received[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,306 @@
+//! Loading the files that a file imports, and the files those import.
+//!
+//! Each file is parsed once, even if several files import it. Files that import each other,
+//! directly or through other files, are rejected, because their top-level code has no order to run in.
+//!
+//! The files are resolved and checked together, so the positions in imported files are moved
+//! to after the file itself. That way every position belongs to one file, which is how errors
+//! are reported for the file that has them.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tel_ast::Ast;
+use tel_ast::MapSpans;
+use tel_common::Span;
+use tel_common::TelErr;
+use tel_parser::str_to_ast;
+
+use crate::parse_err_to_tel_err;
+use crate::ErrReport;
+
+/// A parsed file, which knows which of the other loaded files it imports.
+#[derive(Debug)]
+pub struct Module {
+    pub path: PathBuf,
+    pub code: String,
+    pub ast: Ast,
+    /// Index of the module for each import in the ast, in the same order.
+    pub imports: Vec<usize>,
+    /// How far the positions in the ast are moved, which is zero for the file that was loaded first.
+    pub offset: u32,
+}
+
+impl Module {
+    fn contains(&self, span: Span) -> bool {
+        span.start >= self.offset && span.start as usize <= self.offset as usize + self.code.len()
+    }
+}
+
+/// Error in one of the files, which is not necessarily the file that was loaded first.
+#[derive(Debug)]
+pub struct ModuleErr {
+    pub err: TelErr,
+    pub path: PathBuf,
+    pub code: String,
+}
+
+impl ModuleErr {
+    pub fn new(err: TelErr, path: &Path, code: &str) -> Self {
+        ModuleErr { err, path: path.to_owned(), code: code.to_owned() }
+    }
+}
+
+impl From<ModuleErr> for ErrReport {
+    fn from(err: ModuleErr) -> Self {
+        ErrReport::new(err.err, &err.path, &err.code)
+    }
+}
+
+/// Load the files that the parsed file imports, directly or indirectly. Every module comes after
+/// the modules it imports, so the file itself is last.
+pub fn load_modules(path: PathBuf, code: String, ast: Ast) -> Result<Vec<Module>, ModuleErr> {
+    let mut loader = Loader { modules: Vec::new(), loaded: HashMap::new(), graph: ImportGraph::default() };
+    loader.load(path, code, ast)?;
+    let mut modules = loader.modules;
+    let (main, imported) = modules.split_last_mut().expect("the file itself is loaded");
+    let mut offset = main.code.len() as u32 + 1;
+    for module in imported {
+        module.offset = offset;
+        module.ast.map_spans(&mut |span| Span::new((span.start + offset) as usize, (span.end + offset) as usize));
+        offset += module.code.len() as u32 + 1;
+    }
+    Ok(modules)
+}
+
+/// The error together with the file that its position is in, with the position in that file.
+/// Errors without a position are reported for the last module.
+pub fn locate_err(modules: &[Module], mut err: TelErr) -> ModuleErr {
+    let span = err.diagnostics().first().and_then(|diagnostic| diagnostic.primary_span());
+    let module = span.and_then(|span| modules.iter().find(|module| module.contains(span)))
+        .unwrap_or_else(|| modules.last().expect("there is at least the main file"));
+    let offset = module.offset;
+    err.map_spans(|span| Span::new(span.start.saturating_sub(offset) as usize, span.end.saturating_sub(offset) as usize));
+    ModuleErr::new(err, &module.path, &module.code)
+}
+
+/// The file for a module name, which is next to the file that imports it.
+fn module_path(importer: &Path, module: &str) -> PathBuf {
+    importer.parent()
+        .unwrap_or(Path::new(""))
+        .join(format!("{module}.tel"))
+}
+
+struct Loader {
+    modules: Vec<Module>,
+    /// Index in `modules` of each file that was loaded.
+    loaded: HashMap<PathBuf, usize>,
+    graph: ImportGraph,
+}
+
+impl Loader {
+    fn load(&mut self, path: PathBuf, code: String, ast: Ast) -> Result<usize, ModuleErr> {
+        let mut imports = Vec::with_capacity(ast.imports.len());
+        for import in &ast.imports {
+            let file = module_path(&path, import.module.as_str());
+            // the graph had no cycles before, so a new one goes through this file
+            self.graph.register_dependency(path.clone(), file.clone());
+            if let Some(cycle) = self.graph.find_cycle(&path) {
+                return Err(ModuleErr::new(TelErr::ImportCycle { cycle, span: import.span }, &path, &code))
+            }
+            if let Some(&known) = self.loaded.get(&file) {
+                imports.push(known);
+                continue
+            }
+            let import_code = fs::read_to_string(&file).map_err(|err| {
+                let err = match err.kind() {
+                    io::ErrorKind::NotFound => TelErr::ImportNotFound { module: import.module.clone(), file: file.clone(), span: import.span },
+                    _ => TelErr::CouldNotRead(file.clone(), err.to_string()),
+                };
+                ModuleErr::new(err, &path, &code)
+            })?;
+            let import_ast = str_to_ast(file.clone(), import_code.clone())
+                .map_err(|err| ModuleErr::new(parse_err_to_tel_err(err), &file, &import_code))?;
+            imports.push(self.load(file, import_code, import_ast)?);
+        }
+        self.loaded.insert(path.clone(), self.modules.len());
+        self.modules.push(Module { path, code, ast, imports, offset: 0 });
+        Ok(self.modules.len() - 1)
+    }
+}
+
+/// Which files import which other files.
+#[derive(Debug, Default)]
+struct ImportGraph {
+    dependencies: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl ImportGraph {
+    fn register_dependency(&mut self, importer: PathBuf, imported: PathBuf) {
+        self.dependencies.entry(importer)
+            .or_default()
+            .insert(imported);
+    }
+
+    /// Find a cycle of imports that can be reached from the file. Returns the files in the cycle,
+    /// where the first file is repeated at the end.
+    fn find_cycle(&self, target: &Path) -> Option<Vec<PathBuf>> {
+        let mut visited = HashSet::new();
+        let mut stack = Vec::new();
+        if !self.dfs_find_cycle(target, &mut visited, &mut stack) {
+            return None
+        }
+        // the stack is the path from the target, which may not be part of the cycle
+        let last = stack.last().expect("cycle is not empty");
+        let start = stack.iter().position(|file| file == last).expect("last file is in the stack");
+        Some(stack[start..].iter().map(|file| file.to_path_buf()).collect())
+    }
+
+    fn dfs_find_cycle<'a>(
+        &'a self,
+        current: &'a Path,
+        visited: &mut HashSet<&'a Path>,
+        stack: &mut Vec<&'a Path>,
+    ) -> bool {
+        if stack.contains(&current) {
+            stack.push(current);
+            return true
+        }
+        // already explored from somewhere else, without finding a cycle
+        if !visited.insert(current) {
+            return false
+        }
+        stack.push(current);
+        if let Some(dependencies) = self.dependencies.get(current) {
+            for dependency in dependencies {
+                if self.dfs_find_cycle(dependency, visited, stack) {
+                    return true
+                }
+            }
+        }
+        stack.pop();
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+    use crate::check_modules;
+    use crate::execute::execute;
+
+    /// Write the files to a new directory, and load the first one.
+    fn load(name: &str, files: &[(&str, &str)]) -> Result<Vec<Module>, ModuleErr> {
+        let dir = env::temp_dir().join(format!("tel-modules-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, code) in files {
+            fs::write(dir.join(file), code).unwrap();
+        }
+        let path = dir.join(files[0].0);
+        let code = files[0].1.to_owned();
+        let ast = str_to_ast(path.clone(), code.clone()).unwrap();
+        let modules = load_modules(path, code, ast);
+        fs::remove_dir_all(&dir).unwrap();
+        modules
+    }
+
+    fn file_names(modules: &[Module]) -> Vec<&str> {
+        modules.iter()
+            .map(|module| module.path.file_name().unwrap().to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn imports_come_first() {
+        let modules = load("order", &[
+            ("main.tel", "import shapes { area }\nimport util { twice }\n"),
+            ("shapes.tel", "import util { twice }\n"),
+            ("util.tel", "x = 1\n"),
+        ]).unwrap();
+        assert_eq!(file_names(&modules), vec!["util.tel", "shapes.tel", "main.tel"]);
+        assert_eq!(modules[1].imports, vec![0]);
+        assert_eq!(modules[2].imports, vec![1, 0]);
+    }
+
+    #[test]
+    fn missing_module() {
+        let err = load("missing", &[("main.tel", "import nope { a }\n")]).unwrap_err();
+        assert!(matches!(&err.err, TelErr::ImportNotFound { file, .. } if file.ends_with("nope.tel")));
+        assert!(err.path.ends_with("main.tel"));
+    }
+
+    #[test]
+    fn import_cycle() {
+        let err = load("cycle", &[
+            ("main.tel", "import a { x }\n"),
+            ("a.tel", "import b { y }\n"),
+            ("b.tel", "import a { x }\n"),
+        ]).unwrap_err();
+        let TelErr::ImportCycle { cycle, .. } = &err.err else { panic!("expected a cycle, got {:?}", err.err) };
+        let names = cycle.iter().map(|file| file.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["b.tel", "a.tel", "b.tel"]);
+        assert!(err.path.ends_with("b.tel"));
+        let err = load("self", &[("itself.tel", "import itself { x }\n")]).unwrap_err();
+        assert!(matches!(&err.err, TelErr::ImportCycle { cycle, .. } if cycle.len() == 2));
+    }
+
+    #[test]
+    fn imported_code_runs() {
+        let modules = load("run", &[
+            ("main.tel", "import shapes { Square, area }\n(area(Square(3)) + 1).print\n"),
+            ("shapes.tel", "pub struct Square {\n    side: i64\n}\npub area = fn(s: Square): i64 { s.side * s.side }\n\"shapes\".print\n"),
+        ]).unwrap();
+        let (file, _) = check_modules(&modules).unwrap();
+        let mut out = Vec::new();
+        execute(&file, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "shapes\n10\n");
+    }
+
+    #[test]
+    fn runtime_error_in_imported_file() {
+        let modules = load("runtime", &[
+            ("main.tel", "import util { half }\nhalf(3).print\n"),
+            ("util.tel", "pub half = fn(n: i64): i64 {\n    n / 0\n}\n"),
+        ]).unwrap();
+        let (file, _) = check_modules(&modules).unwrap();
+        let err = locate_err(&modules, execute(&file, Vec::new()).unwrap_err());
+        assert!(err.path.ends_with("util.tel"));
+        let span = err.err.diagnostics()[0].primary_span().unwrap();
+        assert_eq!(&err.code[span.start as usize..span.end as usize], "n / 0");
+    }
+
+    #[test]
+    fn errors_in_imported_file() {
+        let modules = load("errors", &[
+            ("main.tel", "import util { x }\n"),
+            ("util.tel", "pub x: i64 = \"text\"\n"),
+        ]).unwrap();
+        let err = check_modules(&modules).unwrap_err();
+        assert!(err.path.ends_with("util.tel"));
+        assert_eq!(err.err.diagnostics()[0].primary_span(), Some(Span::new(13, 19)));
+        let modules = load("main-errors", &[
+            ("main.tel", "import util { x }
+y: Text = x
+"),
+            ("util.tel", "pub x: i64 = 1
+"),
+        ]).unwrap();
+        let err = check_modules(&modules).unwrap_err();
+        assert!(err.path.ends_with("main.tel"));
+        assert_eq!(err.err.diagnostics()[0].primary_span(), Some(Span::new(28, 29)));
+        let modules = load("private", &[
+            ("main.tel", "import util { x }\n"),
+            ("util.tel", "x = 1\n"),
+        ]).unwrap();
+        let err = check_modules(&modules).unwrap_err();
+        assert!(matches!(err.err, TelErr::NotPublic { .. }));
+        assert!(err.path.ends_with("main.tel"));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use tel_common::Identifier;
+use tel_common::TelErr;
+use tel_hir as hir;
+use tel_hir::Builtin;
+use tel_hir::TelFile;
+use tel_hir::Variable;
+
+/// Closures like `{ ... }` with a `return` return from the function around them, which only works while
+/// that function runs. So they can only be passed directly to functions that do not keep them.
+pub fn check_escaping_returns(file: &TelFile) -> Result<(), TelErr> {
+    let mut escapes = Escapes::new(file);
+    escapes.check_block(&file.main)?;
+    for function in file.functions.iter().chain(methods(file)) {
+        escapes.check_block(&function.closure.body)?;
+    }
+    Ok(())
+}
+
+/// Who is called with an argument, which decides whether that argument can outlive the call.
+#[derive(Clone, Copy)]
+enum Callee<'f> {
+    Var(Variable),
+    /// Method or field `name` of a value, with this many arguments besides the value.
+    Member(&'f Identifier, usize),
+}
+
+struct Escapes<'f> {
+    file: &'f TelFile,
+    /// Immutable variables that are assigned a closure, and so are known to call that closure.
+    functions: HashMap<Variable, &'f hir::Closure>,
+    /// Whether the parameter at an index of the closure (by address) is kept, when given a closure with or without parameters.
+    kept: HashMap<ParamKey, bool>,
+    /// Parameters that are being checked, which are assumed not to be kept by recursive calls.
+    checking: Vec<ParamKey>,
+}
+
+type ParamKey = (usize, usize, bool);
+
+impl<'f> Escapes<'f> {
+    fn new(file: &'f TelFile) -> Self {
+        let mut functions: HashMap<_, _> = file.functions.iter()
+            .map(|function| (function.var, &function.closure))
+            .collect();
+        let mut add = |block: &'f hir::Block| for stmt in &block.stmts {
+            if let hir::Stmt::Assign(hir::Assignment { var, value: hir::Expr { kind: hir::ExprKind::Closure(closure), .. } }) = stmt {
+                if !var.mutable(&file.variables) {
+                    functions.insert(*var, closure);
+                }
+            }
+        };
+        each_block(&file.main, &mut add);
+        for function in file.functions.iter().chain(methods(file)) {
+            each_block(&function.closure.body, &mut add);
+        }
+        Escapes { file, functions, kept: HashMap::new(), checking: Vec::new() }
+    }
+
+    /// Find closures that return outward where they may outlive the function they return from.
+    fn check_block(&mut self, block: &'f hir::Block) -> Result<(), TelErr> {
+        for expr in stmt_exprs(block) {
+            self.check_expr(expr, None)?;
+        }
+        Ok(())
+    }
+
+    /// Like [check_block], for an expression that is the argument at an index of a call, if it is one.
+    fn check_expr(&mut self, expr: &'f hir::Expr, argument: Option<(Callee<'f>, usize)>) -> Result<(), TelErr> {
+        match &expr.kind {
+            hir::ExprKind::Closure(closure) => {
+                if closure.lambda && block_returns(&closure.body) {
+                    let kept = match argument {
+                        Some((callee, ix)) => self.keeps(callee, ix, closure.params.is_empty()),
+                        None => true,
+                    };
+                    if kept {
+                        return Err(TelErr::EscapingReturn { span: closure.span })
+                    }
+                }
+                self.check_block(&closure.body)
+            }
+            hir::ExprKind::Invoke(hir::Invoke { var, args }) => {
+                for (ix, arg) in args.iter().enumerate() {
+                    self.check_expr(arg, Some((Callee::Var(*var), ix)))?;
+                }
+                Ok(())
+            }
+            hir::ExprKind::Dot(target, hir::Member { iden, args }) => {
+                self.check_expr(target, None)?;
+                for (ix, arg) in args.iter().enumerate() {
+                    self.check_expr(arg, Some((Callee::Member(iden, args.len()), ix)))?;
+                }
+                Ok(())
+            }
+            _ => {
+                let (exprs, blocks) = parts(expr);
+                for expr in exprs {
+                    self.check_expr(expr, None)?;
+                }
+                for block in blocks {
+                    self.check_block(block)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the callee may keep the argument at the index, so that it can be used after the call.
+    /// Only calls to builtins, and to known functions and methods that just call the argument, do not.
+    fn keeps(&mut self, callee: Callee<'f>, ix: usize, nullary: bool) -> bool {
+        match callee {
+            Callee::Var(var) => {
+                if let Some(builtin) = var.builtin() {
+                    return builtin.keeps_arguments()
+                }
+                match self.functions.get(&var) {
+                    Some(closure) => self.param_kept(closure, ix, nullary),
+                    None => true,
+                }
+            }
+            Callee::Member(iden, arg_count) => {
+                // a field can hold any closure, which is then called with the arguments
+                if self.file.structs.iter().any(|strct| strct.fields.iter().any(|field| &field.iden == iden)) {
+                    return true
+                }
+                let builtin = Builtin::method(iden.as_str(), arg_count);
+                let methods: Vec<_> = methods(self.file)
+                    .filter(|method| method.var.iden(&self.file.variables) == iden)
+                    .collect();
+                if builtin.is_none() && methods.is_empty() {
+                    return true
+                }
+                // the value the method is called on is the first parameter
+                builtin.is_some_and(Builtin::keeps_arguments)
+                    || methods.into_iter().any(|method| self.param_kept(&method.closure, ix + 1, nullary))
+            }
+        }
+    }
+
+    fn param_kept(&mut self, closure: &'f hir::Closure, ix: usize, nullary: bool) -> bool {
+        let Some(&param) = closure.params.get(ix) else {
+            return false
+        };
+        let key = (closure as *const hir::Closure as usize, ix, nullary);
+        if let Some(&kept) = self.kept.get(&key) {
+            return kept
+        }
+        // recursive calls that pass the parameter on do not keep it by themselves
+        if self.checking.contains(&key) {
+            return false
+        }
+        self.checking.push(key);
+        let kept = self.block_keeps(&closure.body, param, nullary);
+        self.checking.pop();
+        // not being kept may depend on that assumption for a parameter that turns out to be kept
+        if kept || self.checking.is_empty() {
+            self.kept.insert(key, kept);
+        }
+        kept
+    }
+
+    /// Whether the closure in `var` can be used after the block finished. Reading a closure
+    /// without parameters calls it, so only calls with arguments are safe for other closures.
+    fn block_keeps(&mut self, block: &'f hir::Block, var: Variable, nullary: bool) -> bool {
+        stmt_exprs(block).any(|expr| self.expr_keeps(expr, var, nullary, None))
+    }
+
+    fn expr_keeps(&mut self, expr: &'f hir::Expr, var: Variable, nullary: bool, argument: Option<(Callee<'f>, usize)>) -> bool {
+        match &expr.kind {
+            hir::ExprKind::Invoke(hir::Invoke { var: read, args }) if *read == var && args.is_empty() && !nullary => match argument {
+                Some((callee, ix)) => self.keeps(callee, ix, nullary),
+                None => true,
+            },
+            hir::ExprKind::Invoke(hir::Invoke { var: callee, args }) => args.iter()
+                .enumerate()
+                .any(|(ix, arg)| self.expr_keeps(arg, var, nullary, Some((Callee::Var(*callee), ix)))),
+            hir::ExprKind::Dot(target, hir::Member { iden, args }) => self.expr_keeps(target, var, nullary, None)
+                || args.iter()
+                    .enumerate()
+                    .any(|(ix, arg)| self.expr_keeps(arg, var, nullary, Some((Callee::Member(iden, args.len()), ix)))),
+            // a closure that uses the variable keeps it, unless the closure itself is not kept
+            hir::ExprKind::Closure(closure) => {
+                if !mentions(&closure.body, var) {
+                    return false
+                }
+                let kept = match argument {
+                    Some((callee, ix)) => self.keeps(callee, ix, closure.params.is_empty()),
+                    None => true,
+                };
+                kept || self.block_keeps(&closure.body, var, nullary)
+            }
+            _ => {
+                let (exprs, blocks) = parts(expr);
+                exprs.into_iter().any(|expr| self.expr_keeps(expr, var, nullary, None))
+                    || blocks.into_iter().any(|block| self.block_keeps(block, var, nullary))
+            }
+        }
+    }
+}
+
+fn methods(file: &TelFile) -> impl Iterator<Item = &hir::Function> {
+    file.structs.iter()
+        .flat_map(|strct| strct.methods.iter())
+        .chain(file.impls.iter().flat_map(|imp| imp.methods.iter()))
+}
+
+/// Whether the code returns from the function around it, which includes lambdas inside it, but not other closures.
+fn block_returns(block: &hir::Block) -> bool {
+    block.stmts.iter().any(|stmt| matches!(stmt, hir::Stmt::Return(_))) || stmt_exprs(block).any(expr_returns)
+}
+
+fn expr_returns(expr: &hir::Expr) -> bool {
+    match &expr.kind {
+        hir::ExprKind::Propagate(_) => true,
+        hir::ExprKind::Closure(closure) => closure.lambda && block_returns(&closure.body),
+        _ => {
+            let (exprs, blocks) = parts(expr);
+            exprs.into_iter().any(expr_returns) || blocks.into_iter().any(block_returns)
+        }
+    }
+}
+
+fn mentions(block: &hir::Block, var: Variable) -> bool {
+    fn expr_mentions(expr: &hir::Expr, var: Variable) -> bool {
+        if let hir::ExprKind::Invoke(invoke) = &expr.kind {
+            if invoke.var == var {
+                return true
+            }
+        }
+        let (exprs, blocks) = parts(expr);
+        exprs.into_iter().any(|expr| expr_mentions(expr, var)) || blocks.into_iter().any(|block| mentions(block, var))
+    }
+    stmt_exprs(block).any(|expr| expr_mentions(expr, var))
+}
+
+/// Call `f` for the block and every block inside it, including closure bodies.
+fn each_block<'f>(block: &'f hir::Block, f: &mut impl FnMut(&'f hir::Block)) {
+    fn each_expr_block<'f>(expr: &'f hir::Expr, f: &mut impl FnMut(&'f hir::Block)) {
+        let (exprs, blocks) = parts(expr);
+        for expr in exprs {
+            each_expr_block(expr, f);
+        }
+        for block in blocks {
+            each_block(block, f);
+        }
+    }
+    f(block);
+    for expr in stmt_exprs(block) {
+        each_expr_block(expr, f);
+    }
+}
+
+fn stmt_exprs(block: &hir::Block) -> impl Iterator<Item = &hir::Expr> {
+    block.stmts.iter().map(|stmt| match stmt {
+        hir::Stmt::Assign(hir::Assignment { value, .. }) | hir::Stmt::SetField(hir::SetField { value, .. }) => value,
+        hir::Stmt::Expression(expr) | hir::Stmt::Return(expr) => expr,
+    })
+}
+
+/// The expressions and blocks directly inside the expression, where a closure has its body.
+fn parts(expr: &hir::Expr) -> (Vec<&hir::Expr>, Vec<&hir::Block>) {
+    match &expr.kind {
+        hir::ExprKind::Int(_) | hir::ExprKind::Float(_) | hir::ExprKind::Text(_) => (vec![], vec![]),
+        hir::ExprKind::Interpolate(exprs) | hir::ExprKind::List(exprs) => (exprs.iter().collect(), vec![]),
+        hir::ExprKind::Map(entries) => (entries.iter().flat_map(|(key, value)| [key, value]).collect(), vec![]),
+        hir::ExprKind::Index(base, index) => (vec![base, index], vec![]),
+        hir::ExprKind::Propagate(inner) => (vec![inner], vec![]),
+        hir::ExprKind::Invoke(invoke) => (invoke.args.iter().collect(), vec![]),
+        hir::ExprKind::Dot(target, member) => (std::iter::once(&**target).chain(member.args.iter()).collect(), vec![]),
+        hir::ExprKind::Closure(closure) => (vec![], vec![&closure.body]),
+        hir::ExprKind::If(branches, otherwise) => (
+            branches.iter().map(|(condition, _)| condition).collect(),
+            branches.iter().map(|(_, body)| body).chain(otherwise).collect(),
+        ),
+        hir::ExprKind::While(condition, body) => (vec![condition], vec![body]),
+        hir::ExprKind::ForEach(_, iterable, body) => (vec![iterable], vec![body]),
+        hir::ExprKind::Match(value, arms, otherwise) => (
+            vec![value],
+            arms.iter().map(|arm| &arm.body).chain(otherwise).collect(),
+        ),
+    }
+}
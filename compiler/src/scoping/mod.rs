@@ -1,199 +1,1237 @@
-use tel_ast::TelFile;
-use tel_ast::Variables;
+use std::path::PathBuf;
+
 use tel_ast as ast;
+use tel_common::Identifier;
+use tel_common::Span;
 use tel_common::TelErr;
+use tel_hir as hir;
+use tel_hir::Builtin;
+use tel_hir::TelFile;
+use tel_hir::Variable;
+use tel_hir::Variables;
+use tel_parser::str_to_ast;
+use crate::modules::Module;
 pub use self::scope::Scope;
+pub use self::scope::ScopeKind;
+use self::escape::check_escaping_returns;
 
+mod escape;
 mod scope;
 
-pub fn ast_to_api(ast: ast::Ast) -> Result<TelFile, TelErr> {
-    let ast::Ast { blocks } = ast;
+/// Types that every file can use, which are resolved together with the file.
+const PRELUDE: &str = include_str!("prelude.tel");
+
+/// Types and functions, which are collected from anywhere in the file.
+#[derive(Debug, Default)]
+struct Items {
+    structs: Vec<hir::Struct>,
+    enums: Vec<hir::Enum>,
+    interfaces: Vec<hir::Interface>,
+    impls: Vec<hir::Impl>,
+    functions: Vec<hir::Function>,
+    references: Vec<hir::Reference>,
+}
+
+/// Resolve the last module, together with the modules before it, which each come after the modules
+/// they import. The imported files are included, so that the file can be checked and run.
+pub fn ast_to_api(modules: &[Module]) -> Result<TelFile, TelErr> {
+    let (main, _) = modules.split_last().expect("there is at least the main file");
     let mut variables = Variables::new();
-    let mut global_scope = Scope::new_root(&mut variables);
-    let blocks = blocks.into_vec();  //TODO @mark: TEMPORARY! REMOVE THIS!
-    for block in blocks.into_iter() {
-        // let block: Block = block;  // enforce that `block` is not borrowed
-        //TODO @mark: ^ enable this and remove clones
+    let mut global_scope = Scope::new_root();
+    let mut items = Items::default();
+    let prelude_blocks = prelude_ast();
+    declare_types(&prelude_blocks, &mut variables, &mut global_scope)?;
+    let prelude = prelude_vars(&variables, &global_scope);
+    let mut stmts = Vec::new();
+    let mut exports = vec![Vec::new(); modules.len()];
+    let imported_start = variables.len();
+    for ix in 0..modules.len() - 1 {
+        // without references, since those are positions in the other file
+        let file_references = items.references.len();
+        exports[ix] = module_to_api(&modules[ix], &exports, &mut stmts, &mut variables, &mut global_scope, &mut items)?;
+        items.references.truncate(file_references);
+    }
+    let imported_variables = imported_start..variables.len();
+    let imported_types = (items.structs.len(), items.enums.len(), items.interfaces.len());
+    module_to_api(main, &exports, &mut stmts, &mut variables, &mut global_scope, &mut items)?;
+    // type annotations are not scoped, so they should find the types from this file before imported ones
+    //TODO @mark: a type in an imported file can still be confused with one of the same name in another imported file
+    items.structs.rotate_left(imported_types.0);
+    items.enums.rotate_left(imported_types.1);
+    items.interfaces.rotate_left(imported_types.2);
+    let imports = main.ast.imports.iter().zip(&main.imports)
+        .flat_map(|(import, &module)| import.names.iter().map(move |name| (&name.iden, module)))
+        .map(|(iden, module)| exports[module].iter()
+            .copied()
+            .find(|var| var.iden(&variables) == iden)
+            .expect("imported names were resolved"))
+        .collect();
+    // after the files, so that type annotations find types from the files first,
+    // and without references, since those are positions in the prelude instead of the file
+    let file_references = items.references.len();
+    for block in prelude_blocks.into_vec() {
+        block_to_api(block, &mut Vec::new(), &mut variables, &mut global_scope, &mut items)?;
+    }
+    items.references.truncate(file_references);
+    let file = TelFile {
+        variables,
+        structs: items.structs.into_boxed_slice(),
+        enums: items.enums.into_boxed_slice(),
+        interfaces: items.interfaces.into_boxed_slice(),
+        impls: items.impls.into_boxed_slice(),
+        functions: items.functions.into_boxed_slice(),
+        references: items.references.into_boxed_slice(),
+        main: hir::Block { stmts: stmts.into_boxed_slice() },
+        prelude,
+        imports,
+        imported_variables,
+    };
+    check_escaping_returns(&file)?;
+    Ok(file)
+}
+
+/// Resolve a file in its own scope, adding its top-level code to `stmts`. The modules it imports
+/// must already be resolved, with their public variables in `exports`. Returns the public variables of this file.
+fn module_to_api(
+    module: &Module,
+    exports: &[Vec<Variable>],
+    stmts: &mut Vec<hir::Stmt>,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<Vec<Variable>, TelErr> {
+    let ast::Ast { imports, blocks } = module.ast.clone();
+    let mut public = Vec::new();
+    for block in blocks.iter() {
+        public_names(block, &mut public)?;
+    }
+    // the file is inside the prelude scope, so it can declare types with the same names
+    scope.enter_child(ScopeKind::Function, Span { start: 0, end: u32::MAX });
+    declare_types(&blocks, variables, scope)?;
+    // top-level functions are declared first, so they can be used before the declaration, and recursively
+    for block in blocks.iter() {
+        if let Some(dest) = function_declaration(block) {
+            scope.declare_in_scope(variables, &dest.target, dest.typ.as_ref(), false, dest.span)?;
+        }
+    }
+    // after the declarations, so that a name that is both imported and declared is reported at the import
+    imports_to_api(&imports, &module.imports, exports, variables, scope, items)?;
+    let mut functions = Vec::new();
+    for block in impls_last(blocks) {
+        if let Some(dest) = function_declaration(&block) {
+            let var = scope.find_in_scope(variables, &dest.target)
+                .expect("declared above");
+            let ast::Block::Assigns(ast::Assignments { value, .. }) = block else { unreachable!() };
+            let ast::ExprKind::Closure(closure) = value.kind else { unreachable!() };
+            functions.push((var, closure));
+            continue
+        }
+        block_to_api(block, stmts, variables, scope, items)?;
+    }
+    // bodies are resolved after the top-level code, so that they can use any global
+    for (var, closure) in functions {
+        let mut closure = closure_to_api(closure, variables, scope, items)?;
+        // named functions return by themselves, even if they are written like `{ ... }`
+        closure.lambda = false;
+        items.functions.push(hir::Function { var, closure });
+    }
+    let exported = public.iter()
+        .map(|iden| scope.find_in_scope(variables, iden).expect("public names are declared at the top level"))
+        .collect();
+    scope.exit_child();
+    Ok(exported)
+}
+
+/// Make the imported names from other files available in the current scope.
+fn imports_to_api(
+    imports: &[ast::Import],
+    modules: &[usize],
+    exports: &[Vec<Variable>],
+    variables: &Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<(), TelErr> {
+    let mut imported: Vec<(&Identifier, Span)> = Vec::new();
+    for (import, &module) in imports.iter().zip(modules) {
+        for ast::ImportName { iden, span } in &import.names {
+            if let Some((_, previous)) = imported.iter().find(|(known, _)| *known == iden) {
+                return Err(TelErr::ScopeErr {
+                    span: *span,
+                    previous: *previous,
+                    msg: format!("'{iden}' imported twice"),
+                })
+            }
+            let Some(&var) = exports[module].iter().find(|var| var.iden(variables) == iden) else {
+                return Err(TelErr::NotPublic { iden: iden.clone(), module: import.module.clone(), span: *span })
+            };
+            scope.import(variables, var, *span)?;
+            items.references.push(hir::Reference { span: *span, var });
+            imported.push((iden, *span));
+        }
+    }
+    Ok(())
+}
+
+/// Add the names that a top-level declaration makes public, after checking that it can be public.
+/// For enums, the variants are also public.
+fn public_names(block: &ast::Block, public: &mut Vec<Identifier>) -> Result<(), TelErr> {
+    match block {
+        ast::Block::Struct(strct) if strct.public => {
+            check_public_methods(&strct.methods)?;
+            public.push(strct.iden.clone());
+        }
+        ast::Block::Enum(enm) if enm.public => public_enum_names(enm, public)?,
+        ast::Block::Interface(interface) if interface.public => {
+            for method in &interface.methods {
+                if !is_typed_signature(&method.params, method.ret.as_ref()) {
+                    return Err(TelErr::MissingPublicType { iden: method.iden.clone(), span: method.span })
+                }
+            }
+            public.push(interface.iden.clone());
+        }
+        ast::Block::Assigns(assign) if assign.public => {
+            let [dest] = &*assign.dest else {
+                return Err(TelErr::InvalidPublic { span: assign.span, msg: "public assignment can only have one name".to_owned() })
+            };
+            if assign.op.is_some() || dest.kw != ast::AssignmentKw::None {
+                return Err(TelErr::InvalidPublic { span: assign.span, msg: "public variables cannot be changed".to_owned() })
+            }
+            let typed = match &assign.value.kind {
+                ast::ExprKind::Closure(closure) => is_typed_signature(&closure.params, closure.ret.as_ref()),
+                _ => dest.typ.is_some(),
+            };
+            if !typed {
+                return Err(TelErr::MissingPublicType { iden: dest.target.clone(), span: assign.span })
+            }
+            public.push(dest.target.clone());
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn public_enum_names(enm: &ast::Enum, public: &mut Vec<Identifier>) -> Result<(), TelErr> {
+    public.push(enm.iden.clone());
+    for variant in &enm.variants {
+        match variant {
+            ast::EnumVariant::Struct(strct) => {
+                check_public_methods(&strct.methods)?;
+                public.push(strct.iden.clone());
+            }
+            ast::EnumVariant::Enum(nested) => public_enum_names(nested, public)?,
+            ast::EnumVariant::Existing(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_public_methods(methods: &[ast::Method]) -> Result<(), TelErr> {
+    for method in methods {
+        if !is_typed_signature(&method.closure.params, method.closure.ret.as_ref()) {
+            return Err(TelErr::MissingPublicType { iden: method.iden.clone(), span: method.span })
+        }
+    }
+    Ok(())
+}
+
+/// Public functions need the types of all parameters and of the result.
+fn is_typed_signature(params: &[ast::AssignmentDest], ret: Option<&ast::Type>) -> bool {
+    ret.is_some() && params.iter().all(|param| param.typ.is_some())
+}
+
+/// Only top-level declarations can be public, since other files cannot use anything else.
+fn public_span(block: &ast::Block) -> Option<Span> {
+    match block {
+        ast::Block::Struct(strct) if strct.public => Some(strct.span),
+        ast::Block::Enum(enm) if enm.public => Some(enm.span),
+        ast::Block::Interface(interface) if interface.public => Some(interface.span),
+        ast::Block::Assigns(assign) if assign.public => Some(assign.span),
+        _ => None,
+    }
+}
+
+/// The prelude declarations, which have no position, since they are not in the file.
+fn prelude_ast() -> Box<[ast::Block]> {
+    let mut blocks = str_to_ast(PathBuf::from("prelude.tel"), PRELUDE.to_owned())
+        .expect("prelude should be valid")
+        .blocks;
+    for block in &mut blocks {
+        let ast::Block::Enum(enm) = block else {
+            continue
+        };
+        enm.span = Span::default();
+        for variant in &mut enm.variants {
+            if let ast::EnumVariant::Struct(strct) = variant {
+                strct.span = Span::default();
+            }
+        }
+    }
+    blocks
+}
+
+fn prelude_vars(variables: &Variables, scope: &Scope) -> hir::Prelude {
+    let find = |name: &str| scope.find_in_scope(variables, &Identifier::new(name).expect("valid prelude name"))
+        .expect("declared in prelude");
+    hir::Prelude {
+        option: find("Option"),
+        some: find("Some"),
+        none: find("None"),
+        result: find("Result"),
+        ok: find("Ok"),
+        err: find("Err"),
+    }
+}
+
+/// Immutable assignment of a closure, like `f = fn(x) { ... }`, is a function declaration.
+fn function_declaration(block: &ast::Block) -> Option<&ast::AssignmentDest> {
+    let ast::Block::Assigns(ast::Assignments { dest, op: None, value, .. }) = block else {
+        return None
+    };
+    match (&**dest, &value.kind) {
+        ([single], ast::ExprKind::Closure(_)) if single.kw == ast::AssignmentKw::None => Some(single),
+        _ => None,
+    }
+}
+
+/// Types can be used anywhere in the scope they are declared in, so declare them before anything else.
+fn declare_types(
+    blocks: &[ast::Block],
+    variables: &mut Variables,
+    scope: &mut Scope,
+) -> Result<(), TelErr> {
+    for block in blocks {
         match block {
-            ast::Block::Assigns(assign) => { assignments_to_api(assign, &mut variables, &mut global_scope)?; }
-            ast::Block::Expression(expression) => { expression_to_api(&expression, &mut variables, &mut global_scope)?; }
-            ast::Block::Return(_expression) => todo!("Return"),
-            //TODO @mark: return ^
-            ast::Block::Struct(_struct) => todo!("Struct"),
-            ast::Block::Enum(_enum) => todo!("Enum"),
+            ast::Block::Struct(strct) => { scope.declare_in_scope(variables, &strct.iden, None, false, strct.span)?; }
+            ast::Block::Enum(enm) => declare_enum(enm, variables, scope)?,
+            ast::Block::Interface(interface) => { scope.declare_in_scope(variables, &interface.iden, None, false, interface.span)?; }
+            ast::Block::Assigns(_) | ast::Block::SetField(_) | ast::Block::Expression(_) | ast::Block::Return(_)
+                | ast::Block::Impl(_) => {}
         }
     }
-    Ok(TelFile {})
+    Ok(())
 }
 
-fn expression_to_api(
-    expr: &ast::Expr,
-    _variables: &mut Variables,
-    _scope: &mut Scope,
-) -> Result<ast::Expr, TelErr> {
-    //TODO @mark: to owned expression?
-    Ok(match expr {
-        ast::Expr::Num(num) => ast::Expr::Num(*num),
-        ast::Expr::Text(_text) => todo!("Text"),
-        //ast::Expr::BinOp(op, left, right) => invoke_binary_to_api(*op, left, right, variables, scope)?,
-        //ast::Expr::UnaryOp(op, expr) => invoke_unary_to_api(*op, expr, variables, scope)?,
-        //ast::Expr::Invoke(invoke) => invoke_to_api(invoke, variables, scope)?,
-        ast::Expr::BinOp(_op, _left, _right) => unimplemented!(),
-        ast::Expr::UnaryOp(_op, _expr) => unimplemented!(),
-        ast::Expr::Invoke(_invoke) => unimplemented!(),
-        ast::Expr::Dot(_dot, _) => todo!("Dot"),
-        ast::Expr::Closure(_closure) => todo!("Closure"),
-        ast::Expr::If(_if, _) => todo!("If"),
-        ast::Expr::While(_while, _) => todo!("While"),
-        ast::Expr::ForEach(_for_each, _, _) => todo!("ForEach"),
+fn declare_enum(
+    enm: &ast::Enum,
+    variables: &mut Variables,
+    scope: &mut Scope,
+) -> Result<(), TelErr> {
+    scope.declare_in_scope(variables, &enm.iden, None, false, enm.span)?;
+    for variant in &enm.variants {
+        match variant {
+            ast::EnumVariant::Struct(strct) => { scope.declare_in_scope(variables, &strct.iden, None, false, strct.span)?; }
+            ast::EnumVariant::Enum(nested) => declare_enum(nested, variables, scope)?,
+            ast::EnumVariant::Existing(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the blocks inside the current scope (callers should create a new scope if needed).
+fn blocks_to_api(
+    blocks: Box<[ast::Block]>,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Block, TelErr> {
+    if let Some(span) = blocks.iter().find_map(public_span) {
+        return Err(TelErr::InvalidPublic { span, msg: "only top-level declarations can be public".to_owned() })
+    }
+    declare_types(&blocks, variables, scope)?;
+    let mut stmts = Vec::with_capacity(blocks.len());
+    for block in impls_last(blocks) {
+        block_to_api(block, &mut stmts, variables, scope, items)?;
+    }
+    Ok(hir::Block { stmts: stmts.into_boxed_slice() })
+}
+
+/// Impl blocks are resolved after the other blocks, so that the members of the types they are for are known.
+fn impls_last(blocks: Box<[ast::Block]>) -> impl Iterator<Item = ast::Block> {
+    let (impls, others): (Vec<_>, Vec<_>) = blocks.into_vec().into_iter()
+        .partition(|block| matches!(block, ast::Block::Impl(_)));
+    others.into_iter().chain(impls)
+}
+
+fn block_to_api(
+    block: ast::Block,
+    stmts: &mut Vec<hir::Stmt>,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<(), TelErr> {
+    match block {
+        ast::Block::Assigns(assign) => stmts.extend(assignments_to_api(assign, variables, scope, items)?
+            .into_iter()
+            .map(hir::Stmt::Assign)),
+        ast::Block::Expression(expression) => stmts.push(hir::Stmt::Expression(expression_to_api(expression, variables, scope, items)?)),
+        ast::Block::Return(expression) => {
+            stmts.push(hir::Stmt::Return(expression_to_api(expression, variables, scope, items)?))
+        }
+        ast::Block::SetField(set) => stmts.push(hir::Stmt::SetField(set_field_to_api(set, variables, scope, items)?)),
+        ast::Block::Struct(strct) => {
+            let strct = struct_to_api(strct, variables, scope, items)?;
+            items.structs.push(strct)
+        }
+        ast::Block::Enum(enm) => enum_to_api(enm, variables, scope, items)?,
+        ast::Block::Interface(interface) => {
+            let interface = interface_to_api(interface, variables, scope);
+            items.interfaces.push(interface)
+        }
+        ast::Block::Impl(imp) => {
+            let imp = impl_to_api(imp, variables, scope, items)?;
+            items.impls.push(imp)
+        }
+    }
+    Ok(())
+}
+
+fn struct_to_api(
+    strct: ast::Struct,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Struct, TelErr> {
+    let ast::Struct { iden, fields, methods, generics, span, .. } = strct;
+    let var = scope.find_in_scope(variables, &iden)
+        .expect("types are declared before resolving the block");
+    let members = unique_members(
+        fields.iter().map(|(name, typ)| (name, typ.span))
+            .chain(methods.iter().map(|method| (&method.iden, method.span))),
+        &format!("struct '{iden}'"),
+    )?;
+    let self_type = ast::Type {
+        iden: iden.clone(),
+        generics: generics.iter()
+            .map(|generic| ast::Type { iden: generic.target.clone(), generics: Box::new([]), span: generic.span })
+            .collect(),
+        span,
+    };
+    let methods = methods_to_api(methods, self_type, members, span, variables, scope, items)?;
+    Ok(hir::Struct {
+        var,
+        generics: generics_to_api(generics),
+        fields: fields.into_iter()
+            .map(|(iden, typ)| hir::Field { iden, typ })
+            .collect(),
+        methods,
     })
 }
 
+/// Names of the members, which must all be different.
+fn unique_members<'a>(
+    names: impl Iterator<Item = (&'a Identifier, Span)>,
+    owner: &str,
+) -> Result<Vec<Identifier>, TelErr> {
+    let mut members: Vec<(&Identifier, Span)> = Vec::new();
+    for (name, member_span) in names {
+        if let Some((_, previous)) = members.iter().find(|(known, _)| *known == name) {
+            return Err(TelErr::ScopeErr {
+                span: member_span,
+                previous: *previous,
+                msg: format!("member '{name}' declared twice in {owner}"),
+            })
+        }
+        members.push((name, member_span));
+    }
+    Ok(members.into_iter().map(|(name, _)| name.clone()).collect())
+}
+
+/// Resolve methods with `self` of the given type, where `members` can be used without `self`.
+fn methods_to_api(
+    methods: Vec<ast::Method>,
+    self_type: ast::Type,
+    members: Vec<Identifier>,
+    span: Span,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<Box<[hir::Function]>, TelErr> {
+    let mut api_methods = Vec::with_capacity(methods.len());
+    for ast::Method { iden: method_iden, closure, span: method_span } in methods {
+        // methods are found through the type of the value, so they are not declared in any scope
+        let method_var = variables.add(method_iden, None, false, method_span, span);
+        let receiver = scope.enter_method(variables, self_type.clone(), members.clone(), closure.span);
+        let closure = function_body_to_api(closure, vec![receiver], variables, scope, items);
+        scope.exit_child();
+        api_methods.push(hir::Function { var: method_var, closure: closure? });
+    }
+    Ok(api_methods.into_boxed_slice())
+}
+
+fn interface_to_api(
+    interface: ast::Interface,
+    variables: &mut Variables,
+    scope: &mut Scope,
+) -> hir::Interface {
+    let ast::Interface { iden, methods, .. } = interface;
+    let var = scope.find_in_scope(variables, &iden)
+        .expect("types are declared before resolving the block");
+    hir::Interface {
+        var,
+        methods: methods.into_iter()
+            .map(|ast::Signature { iden, params, ret, .. }| hir::Signature {
+                iden,
+                params: params.into_vec().into_iter().map(|param| param.typ).collect(),
+                ret,
+            })
+            .collect(),
+    }
+}
+
+fn impl_to_api(
+    imp: ast::Impl,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Impl, TelErr> {
+    let ast::Impl { interface, target, methods, span } = imp;
+    let interface_var = type_name_to_api(&interface, variables, scope, items)?;
+    let target_var = type_name_to_api(&target, variables, scope, items)?;
+    // the fields and methods of the struct can be used by name, like inside the struct
+    let mut members = match items.structs.iter().find(|strct| strct.var == target_var) {
+        Some(strct) => strct.fields.iter().map(|field| field.iden.clone())
+            .chain(strct.methods.iter().map(|method| method.var.iden(variables).clone()))
+            .collect(),
+        None => Vec::new(),
+    };
+    members.extend(unique_members(
+        methods.iter().map(|method| (&method.iden, method.span)),
+        &format!("impl of '{}' for '{}'", interface.iden, target.iden),
+    )?);
+    let methods = methods_to_api(methods, target, members, span, variables, scope, items)?;
+    Ok(hir::Impl { interface: interface_var, target: target_var, methods, span })
+}
+
+/// Look up a type that is referred to by name, outside of a type annotation.
+fn type_name_to_api(
+    typ: &ast::Type,
+    variables: &Variables,
+    scope: &Scope,
+    items: &mut Items,
+) -> Result<Variable, TelErr> {
+    let Some(var) = scope.lookup(variables, &typ.iden) else {
+        return Err(TelErr::UnknownIdentifier { iden: typ.iden.clone(), span: typ.span })
+    };
+    items.references.push(hir::Reference { span: typ.span, var });
+    Ok(var)
+}
+
+fn enum_to_api(
+    enm: ast::Enum,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<(), TelErr> {
+    let ast::Enum { iden, variants, generics, .. } = enm;
+    let var = scope.find_in_scope(variables, &iden)
+        .expect("types are declared before resolving the block");
+    let mut api_variants = Vec::with_capacity(variants.len());
+    for variant in variants.into_vec() {
+        api_variants.push(match variant {
+            ast::EnumVariant::Struct(strct) => {
+                let strct = struct_to_api(strct, variables, scope, items)?;
+                let variant_var = strct.var;
+                items.structs.push(strct);
+                hir::EnumVariant::Struct(variant_var)
+            }
+            ast::EnumVariant::Enum(nested) => {
+                let nested_var = scope.find_in_scope(variables, &nested.iden)
+                    .expect("types are declared before resolving the block");
+                enum_to_api(nested, variables, scope, items)?;
+                hir::EnumVariant::Enum(nested_var)
+            }
+            ast::EnumVariant::Existing(typ) => hir::EnumVariant::Existing(typ),
+        })
+    }
+    items.enums.push(hir::Enum {
+        var,
+        generics: generics_to_api(generics),
+        variants: api_variants.into_boxed_slice(),
+    });
+    Ok(())
+}
+
+fn generics_to_api(generics: Box<[ast::AssignmentDest]>) -> Box<[hir::TypeParam]> {
+    generics.into_vec().into_iter()
+        .map(|ast::AssignmentDest { target, typ, .. }| hir::TypeParam { iden: target, bound: typ })
+        .collect()
+}
+
+fn expression_to_api(
+    expr: ast::Expr,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Expr, TelErr> {
+    let span = expr.span;
+    let kind = match expr.kind {
+        ast::ExprKind::Int(nr) => hir::ExprKind::Int(nr),
+        ast::ExprKind::Float(nr) => hir::ExprKind::Float(nr),
+        ast::ExprKind::Text(text) => hir::ExprKind::Text(text),
+        ast::ExprKind::Interpolate(parts) => hir::ExprKind::Interpolate(parts.into_vec().into_iter()
+            .map(|part| match part {
+                ast::TextPart::Text(text) => Ok(hir::Expr::new(hir::ExprKind::Text(text), span)),
+                ast::TextPart::Code(code) => expression_to_api(code, variables, scope, items),
+            })
+            .collect::<Result<_, _>>()?),
+        ast::ExprKind::List(elements) => hir::ExprKind::List(expressions_to_api(elements, variables, scope, items)?),
+        ast::ExprKind::Map(entries) => hir::ExprKind::Map(entries.into_vec().into_iter()
+            .map(|(key, value)| Ok((
+                expression_to_api(key, variables, scope, items)?,
+                expression_to_api(value, variables, scope, items)?,
+            )))
+            .collect::<Result<_, TelErr>>()?),
+        ast::ExprKind::Index(base, index) => hir::ExprKind::Index(
+            Box::new(expression_to_api(*base, variables, scope, items)?),
+            Box::new(expression_to_api(*index, variables, scope, items)?),
+        ),
+        ast::ExprKind::Propagate(inner) => hir::ExprKind::Propagate(Box::new(expression_to_api(*inner, variables, scope, items)?)),
+        ast::ExprKind::BinOp(op, left, right) => invoke_binary_to_api(op, *left, *right, variables, scope, items)?,
+        ast::ExprKind::UnaryOp(op, expr) => invoke_unary_to_api(op, *expr, variables, scope, items)?,
+        ast::ExprKind::Invoke(invoke) => invoke_to_api(invoke, variables, scope, items)?,
+        ast::ExprKind::Dot(target, ast::Invoke { iden, args, .. }) => hir::ExprKind::Dot(
+            Box::new(expression_to_api(*target, variables, scope, items)?),
+            hir::Member { iden, args: arguments_to_api(args, variables, scope, items)? },
+        ),
+        ast::ExprKind::Closure(closure) => hir::ExprKind::Closure(closure_to_api(closure, variables, scope, items)?),
+        ast::ExprKind::If(branches, otherwise) => {
+            let mut api_branches = Vec::with_capacity(branches.len());
+            for (condition, body) in branches.into_vec() {
+                let condition = expression_to_api(condition, variables, scope, items)?;
+                let body = child_blocks_to_api(body, span, variables, scope, items)?;
+                api_branches.push((condition, body));
+            }
+            let otherwise = match otherwise {
+                Some(body) => Some(child_blocks_to_api(body, span, variables, scope, items)?),
+                None => None,
+            };
+            hir::ExprKind::If(api_branches.into_boxed_slice(), otherwise)
+        }
+        ast::ExprKind::While(condition, body) => hir::ExprKind::While(
+            Box::new(expression_to_api(*condition, variables, scope, items)?),
+            child_blocks_to_api(body, span, variables, scope, items)?,
+        ),
+        ast::ExprKind::ForEach(dest, iterable, body) => {
+            let iterable = expression_to_api(*iterable, variables, scope, items)?;
+            scope.enter_child(ScopeKind::Block, span);
+            let ast::AssignmentDest { kw, target, typ, span: dest_span } = dest;
+            let var = scope.declare_in_scope(variables, &target, typ.as_ref(), kw == ast::AssignmentKw::Mut, dest_span)?;
+            let body = blocks_to_api(body, variables, scope, items)?;
+            scope.exit_child();
+            hir::ExprKind::ForEach(var, Box::new(iterable), body)
+        }
+        ast::ExprKind::Match(value, arms, otherwise) => {
+            let value = expression_to_api(*value, variables, scope, items)?;
+            let mut api_arms = Vec::with_capacity(arms.len());
+            for arm in arms.into_vec() {
+                api_arms.push(match_arm_to_api(arm, variables, scope, items)?);
+            }
+            let otherwise = match otherwise {
+                Some(body) => Some(child_blocks_to_api(body, span, variables, scope, items)?),
+                None => None,
+            };
+            hir::ExprKind::Match(Box::new(value), api_arms.into_boxed_slice(), otherwise)
+        }
+    };
+    Ok(hir::Expr::new(kind, span))
+}
+
+fn match_arm_to_api(
+    arm: ast::MatchArm,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::MatchArm, TelErr> {
+    let ast::MatchArm { variant: variant_iden, binding, body, span } = arm;
+    let variant_span = Span::new(span.start as usize, span.start as usize + variant_iden.as_str().len());
+    let Some(variant) = scope.lookup(variables, &variant_iden) else {
+        return Err(TelErr::UnknownIdentifier { iden: variant_iden, span: variant_span })
+    };
+    items.references.push(hir::Reference { span: variant_span, var: variant });
+    scope.enter_child(ScopeKind::Block, span);
+    let binding = match binding {
+        Some(iden) => Some(scope.declare_in_scope(variables, &iden, None, false, span)?),
+        None => None,
+    };
+    let body = blocks_to_api(body, variables, scope, items)?;
+    scope.exit_child();
+    Ok(hir::MatchArm { variant, binding, body })
+}
+
+fn expressions_to_api(
+    exprs: Box<[ast::Expr]>,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<Box<[hir::Expr]>, TelErr> {
+    exprs.into_vec().into_iter()
+        .map(|e| expression_to_api(e, variables, scope, items))
+        .collect()
+}
+
+/// Like [expressions_to_api], but closures can `return` from the function around them.
+fn arguments_to_api(
+    args: Box<[ast::Expr]>,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<Box<[hir::Expr]>, TelErr> {
+    args.into_vec().into_iter()
+        .map(|arg| match arg.kind {
+            ast::ExprKind::Closure(closure) => Ok(hir::Expr::new(hir::ExprKind::Closure(closure_to_api(closure, variables, scope, items)?), arg.span)),
+            _ => expression_to_api(arg, variables, scope, items),
+        })
+        .collect()
+}
+
+/// Resolve the blocks in a new scope, where `span` is the code in which the scope is visible.
+fn child_blocks_to_api(
+    blocks: Box<[ast::Block]>,
+    span: Span,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Block, TelErr> {
+    scope.enter_child(ScopeKind::Block, span);
+    let block = blocks_to_api(blocks, variables, scope, items)?;
+    scope.exit_child();
+    Ok(block)
+}
+
+/// Closures with a `return` that is for the function around them can only be used as arguments, since
+/// other uses, like storing them in a variable, make it easy to call them after that function finished.
+fn closure_to_api(
+    closure: ast::Closure,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Closure, TelErr> {
+    let span = closure.span;
+    scope.enter_child(ScopeKind::Function, span);
+    // closures written like `{ ... }` take one argument if they use `it`, and none otherwise
+    let implicit = if closure.lambda && closure.generics.is_empty() && closure.params.is_empty() && closure.ret.is_none() {
+        Some(scope.declare_in_scope(variables, &Identifier::implicit_param(), None, false, closure.span)?)
+    } else {
+        None
+    };
+    let first_reference = items.references.len();
+    let closure = function_body_to_api(closure, Vec::new(), variables, scope, items);
+    scope.exit_child();
+    let mut closure = closure?;
+    if let Some(it) = implicit {
+        if items.references[first_reference..].iter().any(|reference| reference.var == it) {
+            closure.params = Box::new([it]);
+        }
+    }
+    Ok(closure)
+}
+
+/// Declare the parameters in the current scope (after any that are already declared, like `self`) and resolve the body.
+fn function_body_to_api(
+    closure: ast::Closure,
+    mut api_params: Vec<Variable>,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::Closure, TelErr> {
+    let ast::Closure { blocks, generics, params, ret, lambda, span } = closure;
+    for ast::AssignmentDest { kw, target, typ, span } in params.into_vec() {
+        api_params.push(scope.declare_in_scope(variables, &target, typ.as_ref(), kw == ast::AssignmentKw::Mut, span)?);
+    }
+    let body = blocks_to_api(blocks, variables, scope, items)?;
+    Ok(hir::Closure { generics: generics_to_api(generics), params: api_params.into_boxed_slice(), ret, body, lambda, span })
+}
+
 fn assignments_to_api(
     assign: ast::Assignments,
     variables: &mut Variables,
     scope: &mut Scope,
-) -> Result<Vec<ast::Assignment>, TelErr> {
+    items: &mut Items,
+) -> Result<Vec<hir::Assignment>, TelErr> {
     //TODO @mark: use more efficient vec
-    let ast::Assignments { dest: dests, op, value: ast_value } = assign;
+    let ast::Assignments { dest: dests, op, value: ast_value, .. } = assign;
     debug_assert!(!dests.is_empty());
-    if let Some(_op) = op {
-        todo!()
+    if let Some(op) = op {
+        debug_assert!(dests.len() == 1, "the grammar only allows one target for compound assignment");
+        let dest = dests.into_vec().pop().expect("checked above");
+        return compound_assignment_to_api(dest, op, *ast_value, variables, scope, items)
     }
     let mut api_assignments = Vec::with_capacity(dests.len());
-    let mut value = expression_to_api(&ast_value, variables, scope)?;
-    for dest in dests.iter().rev() {
-        // let dest: AssignmentDest = dest;  // enforce that `dest` is not borrowed
-        //TODO @mark: ^ enable this and pass owned values to scope
-        let ast::AssignmentDest { kw, target, typ } = dest;
-        let (allow_outer, is_mutable) = match (kw, typ) {
-            (ast::AssignmentKw::None, None) => (true, false),
-            (ast::AssignmentKw::None, Some(_)) => (false, false),
-            (ast::AssignmentKw::Outer, _) => (true, false),
-            (ast::AssignmentKw::Local, _) => (false, false),
-            (ast::AssignmentKw::Mut, _) => (false, true),
-        };
-        let binding = if allow_outer {
-            scope.declare_in_scope(
+    let mut value = expression_to_api(*ast_value, variables, scope, items)?;
+    for dest in dests.into_vec().into_iter().rev() {
+        let ast::AssignmentDest { kw, target, typ, span } = dest;
+        let binding = match (kw, &typ) {
+            (ast::AssignmentKw::None, None) => scope.assign_or_declare(variables, &target, span)?,
+            (ast::AssignmentKw::Outer, _) => scope.assign_outer(variables, &target, span)?,
+            (ast::AssignmentKw::None, Some(_)) | (ast::AssignmentKw::Local, _) => scope.declare_in_scope(
                 variables,
-                target,
+                &target,
                 typ.as_ref(),
-                is_mutable,
-            )?
-        } else {
-            scope.assign_or_declare(
+                false,
+                span,
+            )?,
+            (ast::AssignmentKw::Mut, _) => scope.declare_in_scope(
                 variables,
-                target,
+                &target,
                 typ.as_ref(),
-                is_mutable,
-            )
+                true,
+                span,
+            )?,
         };
-        api_assignments.push(ast::Assignment {
+        items.references.push(hir::Reference { span, var: binding });
+        api_assignments.push(hir::Assignment {
             var: binding,
             value,
         });
-        value = ast::Expr::Invoke(ast::Invoke {
-            iden: binding.iden(variables).clone(),
+        value = hir::Expr::new(hir::ExprKind::Invoke(hir::Invoke {
+            var: binding,
             args: Box::new([]),
-        });
+        }), span);
     }
     Ok(api_assignments)
 }
 
+/// Compound assignment like `x += 1` is the same as `x = x + 1`, but `x` must already exist.
+fn compound_assignment_to_api(
+    dest: ast::AssignmentDest,
+    op: ast::BinOpCode,
+    ast_value: ast::Expr,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<Vec<hir::Assignment>, TelErr> {
+    let ast::AssignmentDest { kw, target, span, .. } = dest;
+    let value_span = ast_value.span;
+    let value = expression_to_api(ast_value, variables, scope, items)?;
+    let var = match kw {
+        ast::AssignmentKw::None => scope.reassign(variables, &target, span)?,
+        ast::AssignmentKw::Outer => scope.assign_outer(variables, &target, span)?,
+        ast::AssignmentKw::Local | ast::AssignmentKw::Mut => {
+            return Err(TelErr::CompoundAssignNew { iden: target, span })
+        }
+    };
+    items.references.push(hir::Reference { span, var });
+    let current = hir::Expr::new(hir::ExprKind::Invoke(hir::Invoke { var, args: Box::new([]) }), span);
+    Ok(vec![hir::Assignment {
+        var,
+        value: hir::Expr::new(hir::ExprKind::Invoke(hir::Invoke {
+            var: Builtin::binary(op).var(),
+            args: Box::new([current, value]),
+        }), span.join(value_span)),
+    }])
+}
+
+/// Field assignment like `a.b = 1`, where the target must be a variable followed by field names.
+fn set_field_to_api(
+    set: ast::SetField,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::SetField, TelErr> {
+    let ast::SetField { target, field, op, value: ast_value, .. } = set;
+    let mut fields = vec![field];
+    let mut current = *target;
+    let (root, root_span) = loop {
+        let span = current.span;
+        match current.kind {
+            ast::ExprKind::Dot(inner, ast::Invoke { iden, args, .. }) if args.is_empty() => {
+                fields.push(iden);
+                current = *inner;
+            }
+            ast::ExprKind::Invoke(ast::Invoke { iden, args, .. }) if args.is_empty() => break (iden, span),
+            _ => return Err(TelErr::InvalidAssignTarget { span }),
+        }
+    };
+    let var = match scope.lookup_member(variables, &root) {
+        // a bare field name in a method is a field of `self`, which cannot be changed
+        Some(_) => {
+            fields.push(root);
+            scope.assign_field(variables, &Identifier::receiver(), root_span)?
+        }
+        None => scope.assign_field(variables, &root, root_span)?,
+    };
+    fields.reverse();
+    items.references.push(hir::Reference { span: root_span, var });
+    let mut value = expression_to_api(*ast_value, variables, scope, items)?;
+    if let Some(op) = op {
+        let current = fields.iter().fold(
+            hir::Expr::new(hir::ExprKind::Invoke(hir::Invoke { var, args: Box::new([]) }), root_span),
+            |target, field| hir::Expr::new(
+                hir::ExprKind::Dot(Box::new(target), hir::Member { iden: field.clone(), args: Box::new([]) }),
+                root_span,
+            ),
+        );
+        let span = root_span.join(value.span);
+        value = hir::Expr::new(hir::ExprKind::Invoke(hir::Invoke {
+            var: Builtin::binary(op).var(),
+            args: Box::new([current, value]),
+        }), span);
+    }
+    Ok(hir::SetField { var, fields: fields.into_boxed_slice(), value })
+}
+
 fn invoke_to_api(
-    invoke: &ast::Invoke,
+    invoke: ast::Invoke,
     variables: &mut Variables,
     scope: &mut Scope,
-) -> Result<ast::Expr, TelErr> {
-    //TODO @mark: remove borrow ^
-    let ast::Invoke { iden: ast_iden, args: ast_args } = invoke;
-    let Some(api_iden) = scope.lookup(variables, ast_iden) else {
-        return Err(TelErr::UnknownIdentifier(ast_iden.clone()))
+    items: &mut Items,
+) -> Result<hir::ExprKind, TelErr> {
+    let ast::Invoke { iden: ast_iden, args: ast_args, span } = invoke;
+    if let Some(receiver) = scope.lookup_member(variables, &ast_iden) {
+        let args = arguments_to_api(ast_args, variables, scope, items)?;
+        let name_span = Span::new(span.start as usize, span.start as usize + ast_iden.as_str().len());
+        return Ok(hir::ExprKind::Dot(
+            Box::new(hir::Expr::new(hir::ExprKind::Invoke(hir::Invoke { var: receiver, args: Box::new([]) }), name_span)),
+            hir::Member { iden: ast_iden, args },
+        ))
+    }
+    let Some(var) = scope.lookup(variables, &ast_iden) else {
+        return Err(TelErr::UnknownIdentifier { iden: ast_iden, span })
     };
-    let api_args: Box<[ast::Expr]> = ast_args.into_iter()
-        .map(|e| expression_to_api(e, variables, scope))
-        .collect::<Result<Vec<_>, _>>()?
-        .into_boxed_slice();
-    Ok(ast::Expr::Invoke(ast::Invoke { iden: api_iden.iden(variables).clone(), args: api_args }))
+    // only the name, not the arguments
+    let name_span = Span::new(span.start as usize, span.start as usize + ast_iden.as_str().len());
+    items.references.push(hir::Reference { span: name_span, var });
+    let args = arguments_to_api(ast_args, variables, scope, items)?;
+    Ok(hir::ExprKind::Invoke(hir::Invoke { var, args }))
 }
 
+/// Operators are calls to the builtin operator functions.
 fn invoke_unary_to_api(
     op: ast::UnaryOpCode,
-    ast_expr: &ast::Expr,
+    ast_expr: ast::Expr,
     variables: &mut Variables,
     scope: &mut Scope,
-) -> Result<ast::Expr, TelErr> {
-    match op {
-        //UnaryOpCode::Not => Identifier::new(builtins.NEG).expect("built-in must be valid"),
-        //TODO @mark:
-        ast::UnaryOpCode::Not => {},
-        ast::UnaryOpCode::Min => {},
-        //TODO @mark: how to impl preamble? always add to root scope? should have some constants, not lookup each time
-    };
-    let _api_expr = expression_to_api(ast_expr, variables, scope)?;
-    todo!("invoke_unary_to_api not yet implemented")
+    items: &mut Items,
+) -> Result<hir::ExprKind, TelErr> {
+    let api_expr = expression_to_api(ast_expr, variables, scope, items)?;
+    Ok(hir::ExprKind::Invoke(hir::Invoke {
+        var: Builtin::unary(op).var(),
+        args: Box::new([api_expr]),
+    }))
 }
 
 fn invoke_binary_to_api(
     op: ast::BinOpCode,
-    ast_left: &ast::Expr,
-    ast_right: &ast::Expr,
-    variables: &mut Variables,
-    scope: &mut Scope,
-) -> Result<ast::Expr, TelErr> {
-    match op {
-        ast::BinOpCode::Add => {}
-        ast::BinOpCode::Sub => {}
-        ast::BinOpCode::Mul => {}
-        ast::BinOpCode::Div => {}
-        ast::BinOpCode::Modulo => {}
-        ast::BinOpCode::Eq => {}
-        ast::BinOpCode::Neq => {}
-        ast::BinOpCode::Lt => {}
-        ast::BinOpCode::Gt => {}
-        ast::BinOpCode::Le => {}
-        ast::BinOpCode::Ge => {}
-        ast::BinOpCode::And => {}
-        ast::BinOpCode::Or => {}
-        ast::BinOpCode::Xor => {}
-    };
-    let _api_left = expression_to_api(ast_left, variables, scope)?;
-    let _api_right = expression_to_api(ast_right, variables, scope)?;
-    todo!("invoke_binary_to_api not yet implemented")
+    ast_left: ast::Expr,
+    ast_right: ast::Expr,
+    variables: &mut Variables,
+    scope: &mut Scope,
+    items: &mut Items,
+) -> Result<hir::ExprKind, TelErr> {
+    let api_left = expression_to_api(ast_left, variables, scope, items)?;
+    let api_right = expression_to_api(ast_right, variables, scope, items)?;
+    Ok(hir::ExprKind::Invoke(hir::Invoke {
+        var: Builtin::binary(op).var(),
+        args: Box::new([api_left, api_right]),
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
     use tel_common::Identifier;
+    use tel_hir::Variable;
+    use tel_parser::str_to_ast;
+    use crate::modules::load_modules;
+
+    fn resolve(code: &str) -> Result<TelFile, TelErr> {
+        let ast = str_to_ast(PathBuf::new(), code.to_owned()).unwrap();
+        ast_to_api(&load_modules(PathBuf::new(), code.to_owned(), ast).unwrap())
+    }
 
     #[test]
     fn repeated_assign() {
         let mut variables = Variables::new();
-        let mut global_scope = Scope::new_root(&mut variables);
+        let mut global_scope = Scope::new_root();
         let assign = ast::Assignments {
             dest: Box::new([ast::AssignmentDest {
                 kw: ast::AssignmentKw::None,
                 target: Identifier::new("a").unwrap(),
                 typ: None,
+                span: Span::default(),
             }, ast::AssignmentDest {
                 kw: ast::AssignmentKw::None,
                 target: Identifier::new("b").unwrap(),
                 typ: None,
+                span: Span::default(),
             }]),
             op: None,
-            value: Box::new(ast::Expr::Num(1.0)),
+            value: Box::new(ast::Expr::new(ast::ExprKind::Int(1), Span::default())),
+            public: false,
+            span: Span::default(),
         };
-        let res = assignments_to_api(assign, &mut variables, &mut global_scope).unwrap();
+        let res = assignments_to_api(assign, &mut variables, &mut global_scope, &mut Items::default()).unwrap();
         assert_eq!(res.len(), 2);
-        let ast::Assignment { var: var1, value: value1 } = &res[0];
+        let hir::Assignment { var: var1, value: value1 } = &res[0];
         assert_eq!(var1.iden(&variables).to_string(), "b");
-        assert!(matches!(value1, ast::Expr::Num(1.0)));
-        let ast::Assignment { var: var2, value: value2 } = &res[1];
+        assert!(matches!(value1, hir::Expr { kind: hir::ExprKind::Int(1), .. }));
+        let hir::Assignment { var: var2, value: value2 } = &res[1];
         assert_eq!(var2.iden(&variables).to_string(), "a");
-        let ast::Expr::Invoke(ast::Invoke { iden: read_iden, args: read_args }) = value2 else { panic!() };
-        assert_eq!(read_iden, var1.iden(&variables));
+        let hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var: read_var, args: read_args }), .. } = value2 else { panic!() };
+        assert_eq!(read_var, var1);
         assert_eq!(read_args.len(), 0);
-        //res[0].value
+    }
+
+    #[test]
+    fn reassign_reuses_variable() {
+        let file = resolve("mut a = 1\na = a + 1\n").unwrap();
+        let [hir::Stmt::Assign(first), hir::Stmt::Assign(second)] = &*file.main.stmts else { panic!() };
+        assert_eq!(first.var, second.var);
+    }
+
+    #[test]
+    fn unknown_identifier() {
+        let err = resolve("a = b + 1\n").unwrap_err();
+        let TelErr::UnknownIdentifier { iden, span, .. } = err else { panic!() };
+        assert_eq!(iden, Identifier::new("b").unwrap());
+        assert_eq!(span, Span::new(4, 5));
+    }
+
+    #[test]
+    fn declared_twice() {
+        let err = resolve("struct A {}\nenum B {\n    struct A {},\n}\n").unwrap_err();
+        let TelErr::ScopeErr { span, previous, msg } = err else { panic!() };
+        assert_eq!(msg, "variable 'A' declared twice in this scope");
+        assert_eq!(span, Span::new(25, 36));
+        assert_eq!(previous, Span::new(0, 11));
+    }
+
+    #[test]
+    fn block_variable_not_visible_outside() {
+        let err = resolve("if (1) {\n    a = 1\n}\na\n").unwrap_err();
+        let TelErr::UnknownIdentifier { iden, span, .. } = err else { panic!() };
+        assert_eq!(iden, Identifier::new("a").unwrap());
+        assert_eq!(span, Span::new(21, 22));
+    }
+
+    #[test]
+    fn function_used_before_declaration() {
+        let file = resolve("f()\nf = fn() {\n    f()\n}\n").unwrap();
+        assert_eq!(file.functions.len(), 1);
+        assert_eq!(file.main.stmts.len(), 1);
+    }
+
+    #[test]
+    fn enum_variants_are_types() {
+        let file = resolve("enum Option<T> {\n    struct Some<T> { value: T },\n    struct None {},\n}\n").unwrap();
+        // the prelude adds Option and Result, after the types of the file
+        assert_eq!(file.enums.len(), 3);
+        assert_eq!(file.structs.len(), 6);
+        assert!(matches!(file.enums[0].variants[0], hir::EnumVariant::Struct(var) if var == file.structs[0].var));
+    }
+
+    #[test]
+    fn operators_and_prelude() {
+        let file = resolve("x = -1 + 2
+print(x)
+len = 3
+").unwrap();
+        let [hir::Stmt::Assign(assign), hir::Stmt::Expression(print), hir::Stmt::Assign(len)] = &*file.main.stmts else { panic!() };
+        let hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var: add, args }), .. } = &assign.value else { panic!() };
+        assert_eq!(add.builtin(), Some(Builtin::Add));
+        assert!(matches!(&args[0], hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var, .. }), .. } if var.builtin() == Some(Builtin::Neg)));
+        assert!(matches!(print, hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var, .. }), .. } if var.builtin() == Some(Builtin::Print)));
+        assert_eq!(len.var.builtin(), None);
+    }
+
+    fn assigned_vars(block: &hir::Block) -> Vec<Variable> {
+        block.stmts.iter()
+            .filter_map(|stmt| match stmt {
+                hir::Stmt::Assign(assign) => Some(assign.var),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn block_reuses_outer_variable() {
+        let file = resolve("mut total = 0\nfor (i in 3.range) {\n    total = total + i\n}\n").unwrap();
+        let [hir::Stmt::Assign(outer), hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::ForEach(_, _, body), .. })] = &*file.main.stmts else { panic!() };
+        assert_eq!(assigned_vars(body), vec![outer.var]);
+    }
+
+    #[test]
+    fn local_shadows_in_block() {
+        let file = resolve("x = 1\nif (x == 1) {\n    local x = 2\n    mut y = x\n}\n").unwrap();
+        let [hir::Stmt::Assign(outer), hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::If(branches, _), .. })] = &*file.main.stmts else { panic!() };
+        let inner = assigned_vars(&branches[0].1);
+        assert_ne!(inner[0], outer.var);
+        assert!(*inner[1].mutable(&file.variables));
+    }
+
+    #[test]
+    fn function_shadows_unless_outer() {
+        let file = resolve("mut x = 1\nf = fn() {\n    x = 2\n}\ng = fn() {\n    outer x = 3\n}\n").unwrap();
+        let [hir::Stmt::Assign(global)] = &*file.main.stmts else { panic!() };
+        let [f, g] = &*file.functions else { panic!() };
+        assert_ne!(assigned_vars(&f.closure.body), vec![global.var]);
+        assert_eq!(assigned_vars(&g.closure.body), vec![global.var]);
+    }
+
+    #[test]
+    fn assign_immutable_twice() {
+        let err = resolve("x = 1\nwhile (x < 3) {\n    x = x + 1\n}\n").unwrap_err();
+        let TelErr::ImmutableAssign { iden, span, declared } = err else { panic!() };
+        assert_eq!(iden, Identifier::new("x").unwrap());
+        assert_eq!(span, Span::new(26, 27));
+        assert_eq!(declared, Span::new(0, 1));
+        assert!(matches!(resolve("for (i in 3.range) {\n    i = 1\n}\n"), Err(TelErr::ImmutableAssign { .. })));
+    }
+
+    #[test]
+    fn outer_without_variable() {
+        let err = resolve("f = fn() {\n    outer x = 1\n}\n").unwrap_err();
+        let TelErr::NoOuterVariable { iden, span } = err else { panic!() };
+        assert_eq!(iden, Identifier::new("x").unwrap());
+        assert_eq!(span, Span::new(15, 22));
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let file = resolve("mut x = 1\nx *= 3\n").unwrap();
+        let [hir::Stmt::Assign(first), hir::Stmt::Assign(second)] = &*file.main.stmts else { panic!() };
+        assert_eq!(first.var, second.var);
+        let hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var: mul, args }), .. } = &second.value else { panic!() };
+        assert_eq!(mul.builtin(), Some(Builtin::Mul));
+        assert!(matches!(&args[0], hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var, .. }), .. } if *var == first.var));
+    }
+
+    #[test]
+    fn compound_assignment_needs_mutable_variable() {
+        assert!(matches!(resolve("x = 1\nx += 1\n"), Err(TelErr::ImmutableAssign { .. })));
+        let err = resolve("mut x = 1\nf = fn() {\n    x -= 1\n}\n").unwrap_err();
+        assert_eq!(err, TelErr::CompoundAssignNew { iden: Identifier::new("x").unwrap(), span: Span::new(25, 26) });
+        assert!(resolve("mut x = 1\nf = fn() {\n    outer x -= 1\n}\n").is_ok());
+        assert!(matches!(resolve("mut x = 1\nmut x += 1\n"), Err(TelErr::CompoundAssignNew { .. })));
+    }
+
+    #[test]
+    fn match_arms() {
+        let file = resolve("enum E {\n    struct A {},\n    struct B {},\n}\nmatch (A) {\n    A a { a }\n    else { 1 }\n}\n").unwrap();
+        let [hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::Match(_, arms, Some(_)), .. })] = &*file.main.stmts else { panic!() };
+        assert_eq!(arms[0].variant, file.structs[0].var);
+        let [hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::Invoke(read), .. })] = &*arms[0].body.stmts else { panic!() };
+        assert_eq!(Some(read.var), arms[0].binding);
+        let err = resolve("match (1) {\n    C { 1 }\n}\n").unwrap_err();
+        assert_eq!(err, TelErr::UnknownIdentifier { iden: Identifier::new("C").unwrap(), span: Span::new(16, 17) });
+    }
+
+    #[test]
+    fn impl_before_struct() {
+        let file = resolve("impl Sized for Box {\n    fn size() { width * height }\n}\ninterface Sized {\n    fn size(): f64\n}\nstruct Box {\n    width: f64,\n    height: f64,\n}\n").unwrap();
+        let [imp] = &*file.impls else { panic!() };
+        assert_eq!(imp.interface, file.interfaces[0].var);
+        assert_eq!(imp.target, file.structs[0].var);
+        let [hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::Invoke(mul), .. })] = &*imp.methods[0].closure.body.stmts else { panic!() };
+        assert!(matches!(&mul.args[0], hir::Expr { kind: hir::ExprKind::Dot(_, member), .. } if member.iden.as_str() == "width"));
+        let err = resolve("impl Sized for Missing {}\ninterface Sized {}\n").unwrap_err();
+        assert_eq!(err, TelErr::UnknownIdentifier { iden: Identifier::new("Missing").unwrap(), span: Span::new(15, 22) });
+    }
+
+    #[test]
+    fn implicit_it_parameter() {
+        let file = resolve("f = { it * 2 }\ng = { 1 }\nh = {\n    k = { it }\n}\nw = fn(it) { it }\n").unwrap();
+        let [f, g, h, w] = &*file.functions else { panic!() };
+        assert_eq!(f.closure.params.len(), 1);
+        assert_eq!(f.closure.params[0].iden(&file.variables).as_str(), "it");
+        assert!(g.closure.params.is_empty());
+        assert!(h.closure.params.is_empty());
+        let [hir::Stmt::Assign(k)] = &*h.closure.body.stmts else { panic!() };
+        assert!(matches!(&k.value, hir::Expr { kind: hir::ExprKind::Closure(inner), .. } if inner.params.len() == 1));
+        assert_eq!(w.closure.params.len(), 1);
+        assert!(matches!(resolve("x = it\n"), Err(TelErr::UnknownIdentifier { .. })));
+        let err = resolve("f = fn() { it }\n").unwrap_err();
+        assert!(matches!(&err, TelErr::UnknownIdentifier { iden, .. } if iden.as_str() == "it"));
+        assert_eq!(err.diagnostics()[0].code, "E0201");
+        assert!(matches!(resolve("f = fn(): i64 { it }\n"), Err(TelErr::UnknownIdentifier { .. })));
+    }
+
+    #[test]
+    fn method_members_use_self() {
+        let file = resolve("struct P {\n    x: f64,\n    fn twice() { x * 2 }\n    fn shadow(x) { x + twice }\n}\n").unwrap();
+        let [twice, shadow] = &*file.structs[0].methods else { panic!() };
+        let receiver = twice.closure.params[0];
+        assert_eq!(receiver.iden(&file.variables), &Identifier::receiver());
+        let [hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::Invoke(mul), .. })] = &*twice.closure.body.stmts else { panic!() };
+        assert!(matches!(&mul.args[0], hir::Expr { kind: hir::ExprKind::Dot(target, member), .. }
+            if member.iden.as_str() == "x" && matches!(&**target, hir::Expr { kind: hir::ExprKind::Invoke(read), .. } if read.var == receiver)));
+        let [hir::Stmt::Expression(hir::Expr { kind: hir::ExprKind::Invoke(add), .. })] = &*shadow.closure.body.stmts else { panic!() };
+        assert!(matches!(&add.args[0], hir::Expr { kind: hir::ExprKind::Invoke(read), .. } if read.var == shadow.closure.params[1]));
+        assert!(matches!(&add.args[1], hir::Expr { kind: hir::ExprKind::Dot(_, member), .. } if member.iden.as_str() == "twice"));
+    }
+
+    #[test]
+    fn duplicate_member() {
+        let err = resolve("struct P {\n    x: f64,\n    fn x() { 1 }\n}\n").unwrap_err();
+        let TelErr::ScopeErr { msg, .. } = err else { panic!() };
+        assert_eq!(msg, "member 'x' declared twice in struct 'P'");
+        assert!(matches!(resolve("self\n"), Err(TelErr::UnknownIdentifier { .. })));
+    }
+
+    #[test]
+    fn set_field() {
+        let file = resolve("mut p = P(1)\np.a.b += 2\nstruct P {\n    a: f64,\n}\n").unwrap();
+        let [hir::Stmt::Assign(assign), hir::Stmt::SetField(set)] = &*file.main.stmts else { panic!() };
+        assert_eq!(set.var, assign.var);
+        assert_eq!(set.fields.iter().map(Identifier::as_str).collect::<Vec<_>>(), vec!["a", "b"]);
+        let hir::Expr { kind: hir::ExprKind::Invoke(hir::Invoke { var: add, args }), .. } = &set.value else { panic!() };
+        assert_eq!(add.builtin(), Some(Builtin::Add));
+        assert!(matches!(&args[0], hir::Expr { kind: hir::ExprKind::Dot(_, member), .. } if member.iden.as_str() == "b"));
+    }
+
+    #[test]
+    fn set_field_needs_mutable_variable() {
+        assert!(matches!(resolve("p = 1\np.a = 2\n"), Err(TelErr::ImmutableAssign { .. })));
+        assert_eq!(resolve("f(1).a = 2\nf = fn(x) { x }\n").unwrap_err(), TelErr::InvalidAssignTarget { span: Span::new(0, 4) });
+        let err = resolve("struct P {\n    a: f64,\n    fn reset() {\n        a = 0\n        self.a = 0\n    }\n}\n").unwrap_err();
+        let TelErr::ImmutableAssign { iden, .. } = err else { panic!() };
+        assert_eq!(iden, Identifier::receiver());
+    }
+
+    #[test]
+    fn escaping_return() {
+        resolve("f = fn() {\n    g { return 1 }\n    g { x = fn() { return 2 } }\n}\ng = fn(f) { f() }\n").unwrap();
+        let err = resolve("f = fn() {\n    h = { return 1 }\n    h\n}\n").unwrap_err();
+        assert_eq!(err, TelErr::EscapingReturn { span: Span::new(19, 31) });
+        assert!(matches!(resolve("g { [{ return 1 }] }\ng = fn(f) { f() }\n"), Err(TelErr::EscapingReturn { .. })));
+        assert!(matches!(resolve("h = [{ Some(1)? }]\n"), Err(TelErr::EscapingReturn { .. })));
+        let err = resolve("keep = fn(f) { f }\nmake = fn() { keep { return it } }\nlater = make()\nlater(1)").unwrap_err();
+        assert_eq!(err, TelErr::EscapingReturn { span: Span::new(38, 51) });
+        assert_eq!(err.diagnostics()[0].code, "E0206");
+    }
+
+    #[test]
+    fn escaping_return_through_functions() {
+        // parameters that are only called, also when passed on to other such functions
+        resolve("twice = fn(body) { body(1)\n    body(2) }\nf = fn() { twice { return it } }\n").unwrap();
+        resolve("repeat = fn(n, body) { if (n > 0) { repeat(n - 1, body) } else { body(n) } }\nf = fn() { repeat(3, { return it }) }\n").unwrap();
+        resolve("f = fn(items) { items.for_each { it.iter.map { it }.for_each { return 1 } } }\n").unwrap();
+        // kept in a collection, in a lazy iterator, or by another closure
+        assert!(matches!(resolve("store = fn(body) { [body] }\nf = fn() { store { return it } }\n"), Err(TelErr::EscapingReturn { .. })));
+        assert!(matches!(resolve("f = fn(items) { items.iter.map { return it } }\n"), Err(TelErr::EscapingReturn { .. })));
+        assert!(matches!(resolve("wrap = fn(body) { fn(x) { body(x) } }\nf = fn() { wrap { return it } }\n"), Err(TelErr::EscapingReturn { .. })));
+        // passed on to an unknown function, which is a parameter here
+        assert!(matches!(resolve("hand = fn(body, other) { other(body) }\nf = fn() { hand({ return it }, fn(g) { g(1) }) }\n"), Err(TelErr::EscapingReturn { .. })));
+    }
+
+    #[test]
+    fn prelude_types() {
+        let file = resolve("x = Some(1)\n").unwrap();
+        let [hir::Stmt::Assign(x)] = &*file.main.stmts else { panic!() };
+        assert!(matches!(&x.value, hir::Expr { kind: hir::ExprKind::Invoke(invoke), .. } if invoke.var == file.prelude.some));
+        let file = resolve("enum Option {\n    struct Some { value: i64 },\n}\nx = Some(1)\n").unwrap();
+        let [hir::Stmt::Assign(x)] = &*file.main.stmts else { panic!() };
+        assert!(matches!(&x.value, hir::Expr { kind: hir::ExprKind::Invoke(invoke), .. } if invoke.var == file.structs[0].var));
+    }
+
+    #[test]
+    fn public_declarations() {
+        resolve("pub struct P {\n    a: f64,\n    fn twice(): f64 { 2 * a }\n}\npub x: i64 = 1\npub f = fn(a: i64): i64 { a }\n").unwrap();
+        assert!(matches!(resolve("pub x = 1\n"), Err(TelErr::MissingPublicType { .. })));
+        assert!(matches!(resolve("pub f = fn(a: i64) { a }\n"), Err(TelErr::MissingPublicType { .. })));
+        assert!(matches!(resolve("pub struct P {\n    fn one() { 1 }\n}\n"), Err(TelErr::MissingPublicType { .. })));
+        assert!(matches!(resolve("pub mut x: i64 = 1\n"), Err(TelErr::InvalidPublic { .. })));
+        assert!(matches!(resolve("f = fn() {\n    pub x: i64 = 1\n}\n"), Err(TelErr::InvalidPublic { .. })));
     }
 }
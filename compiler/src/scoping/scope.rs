@@ -4,10 +4,24 @@
 //! This implementation stores the values linearly. There are alternative ways,
 //! most obviously a hashmap for fast lookup, but also a mix of the two.
 
+use std::mem;
+
 use tel_ast::Type;
-use tel_ast::Variable;
-use tel_ast::Variables;
-use tel_common::{Identifier, TelErr};
+use tel_common::{Identifier, Span, TelErr};
+use tel_hir::Builtin;
+use tel_hir::Variable;
+use tel_hir::Variables;
+
+/// Whether assignments can reuse variables from outside the scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// The file or a function body. Assigning to a name from outside creates a new local
+    /// variable, unless the assignment uses `outer`.
+    Function,
+    /// Blocks like loops and branches. Assigning to a name from outside reuses that variable,
+    /// unless the assignment uses `local` or `mut`.
+    Block,
+}
 
 #[derive(Debug)]
 pub struct Scope {
@@ -15,92 +29,249 @@ pub struct Scope {
     //TODO @mark: for now it seems ot make the code easier (and possibly faster), but might reconsider
     parent: Option<Box<Scope>>,
     items: Vec<Variable>,
+    kind: ScopeKind,
+    /// The code in which variables from this scope can be used.
+    span: Span,
+    /// For methods, the instance it is called on, and the names of its fields and methods.
+    members: Option<(Variable, Vec<Identifier>)>,
+    /// Variables from other files, with the span of the name in the import.
+    imported: Vec<(Variable, Span)>,
     //TODO @mark: smallvec
 }
 
 impl Scope {
     //TODO @mark: reconsider Rc here (won't be able to add variables if it's Rc anyway)
-    pub fn new_root(variables: &mut Variables) -> Self {
+    pub fn new_root() -> Self {
         Scope {
-            parent: Some(Box::new(Self::new_builtin(variables))),
-            items: vec![]
+            parent: Some(Box::new(Self::new_builtin())),
+            items: vec![],
+            kind: ScopeKind::Function,
+            span: Span { start: 0, end: u32::MAX },
+            members: None,
+            imported: vec![],
         }
     }
 
-    fn new_builtin(_variables: &mut Variables) -> Self {
-        //TODO @mark: perhaps not the best representation, could be enum or map
-        
-        // scope.declare_in_scope(variables, &Identifier::new("Negate.neg").unwrap(), Some(&Type { iden: Identifier::new("bool -> bool").unwrap(), generics: Box::new([]) }), false);
-        // for builtin_iden in BUILTINS {
-        //     scope.declare_in_scope(variables, &Identifier::new(builtin_iden).unwrap(), None, false);
-        // }
+    /// The prelude, which contains the builtins that can be used by name (including operator functions).
+    fn new_builtin() -> Self {
         Scope {
             parent: None,
-            items: vec![]
+            items: Builtin::all().map(Builtin::var).collect(),
+            kind: ScopeKind::Function,
+            span: Span { start: 0, end: u32::MAX },
+            members: None,
+            imported: vec![],
         }
     }
 
+    /// Make this a new empty scope, with the current one as parent.
+    pub fn enter_child(&mut self, kind: ScopeKind, span: Span) {
+        let parent = mem::replace(self, Scope { parent: None, items: vec![], kind, span, members: None, imported: vec![] });
+        self.parent = Some(Box::new(parent));
+    }
+
+    /// Enter the function scope of a method, where `self` is declared, and the fields and methods
+    /// of `self` can be used by name. Returns the variable for `self`.
+    pub fn enter_method(
+        &mut self,
+        variables: &mut Variables,
+        self_type: Type,
+        members: Vec<Identifier>,
+        span: Span,
+    ) -> Variable {
+        self.enter_child(ScopeKind::Function, span);
+        let receiver = variables.add(Identifier::receiver(), Some(self_type), false, span, span);
+        self.items.push(receiver);
+        self.members = Some((receiver, members));
+        receiver
+    }
+
+    /// Drop the current scope and continue with the parent, opposite of [Self::enter_child].
+    pub fn exit_child(&mut self) {
+        let parent = self.parent.take().expect("cannot exit the root scope");
+        *self = *parent;
+    }
+
     pub fn declare_in_scope(
         &mut self,
         variables: &mut Variables,
         iden: &Identifier,
         type_annotation: Option<&Type>,
-        mutable: bool
+        mutable: bool,
+        span: Span,
     ) -> Result<Variable, TelErr> {
-        for &known in &self.items {
-            if known.iden(variables) == iden {
-                // or should shadowing in the same scope be allowed? I occasionally use it in other languages
-                return Err(TelErr::ScopeErr {
-                    msg: format!("variable '{iden}' declared twice in this scope")
-                })
-            }
+        if let Some(existing) = self.find_in_scope(variables, iden) {
+            // or should shadowing in the same scope be allowed? I occasionally use it in other languages
+            return Err(TelErr::ScopeErr {
+                span,
+                previous: existing.span(variables),
+                msg: format!("variable '{iden}' declared twice in this scope")
+            })
         }
         let new_var = variables.add(
             iden.clone(),
             type_annotation.cloned(),
             mutable,
+            span,
+            self.span,
         );
         self.items.push(new_var);
         Ok(*self.items.last().expect("just added, cannot fail"))
     }
 
+    /// Make a variable from another file available by name. The name must not be declared in this scope yet.
+    pub fn import(
+        &mut self,
+        variables: &Variables,
+        var: Variable,
+        span: Span,
+    ) -> Result<(), TelErr> {
+        let iden = var.iden(variables);
+        if let Some(existing) = self.find_in_scope(variables, iden) {
+            return Err(TelErr::ScopeErr {
+                span,
+                previous: existing.span(variables),
+                msg: format!("'{iden}' is imported, but also declared in this file"),
+            })
+        }
+        self.items.push(var);
+        self.imported.push((var, span));
+        Ok(())
+    }
+
+    /// Assignment without keyword, which reuses a variable from the current function if there is one.
+    /// Otherwise, including if the name is only known outside the function, this declares a new variable.
     pub fn assign_or_declare(
         &mut self,
         variables: &mut Variables,
         iden: &Identifier,
-        type_annotation: Option<&Type>,
-        mutable: bool
-    ) -> Variable {
-        if let Some(_parent) = &self.parent {
-            todo!("nested scopes not yet implemented")
+        span: Span,
+    ) -> Result<Variable, TelErr> {
+        match self.lookup_in_function(variables, iden) {
+            Some(known) => self.check_mutable(variables, known, span),
+            None => self.declare_in_scope(variables, iden, None, false, span),
+        }
+    }
+
+    /// Compound assignment like `+=`, which must update an existing mutable variable in the current function.
+    pub fn reassign(
+        &self,
+        variables: &Variables,
+        iden: &Identifier,
+        span: Span,
+    ) -> Result<Variable, TelErr> {
+        match self.lookup_in_function(variables, iden) {
+            Some(known) => self.check_mutable(variables, known, span),
+            None => Err(TelErr::CompoundAssignNew { iden: iden.clone(), span }),
+        }
+    }
+
+    /// Assignment with `outer`, which must reuse an existing variable, even from outside the function.
+    pub fn assign_outer(
+        &self,
+        variables: &Variables,
+        iden: &Identifier,
+        span: Span,
+    ) -> Result<Variable, TelErr> {
+        match self.lookup(variables, iden).filter(|known| known.builtin().is_none()) {
+            Some(known) => self.check_mutable(variables, known, span),
+            None => Err(TelErr::NoOuterVariable { iden: iden.clone(), span }),
+        }
+    }
+
+    /// Field assignment like `a.b = 1`, which updates the existing mutable variable `a`. Since this
+    /// cannot declare a variable, it does not need `outer` to update variables from outside the function.
+    pub fn assign_field(
+        &self,
+        variables: &Variables,
+        iden: &Identifier,
+        span: Span,
+    ) -> Result<Variable, TelErr> {
+        match self.lookup(variables, iden).filter(|known| known.builtin().is_none()) {
+            Some(known) => self.check_mutable(variables, known, span),
+            None => Err(TelErr::UnknownIdentifier { iden: iden.clone(), span }),
         }
-        for &known in &self.items {
-            if known.iden(variables) == iden {
-                return known
+    }
+
+    /// If the name refers to a field or method of `self` in a method, and is not shadowed
+    /// by a variable, return the variable for `self`.
+    pub fn lookup_member(
+        &self,
+        variables: &Variables,
+        iden: &Identifier,
+    ) -> Option<Variable> {
+        if self.find_in_scope(variables, iden).is_some() {
+            return None
+        }
+        if let Some((receiver, members)) = &self.members {
+            if members.contains(iden) {
+                return Some(*receiver)
             }
         }
-        let new_var = variables.add(
-            iden.clone(),
-            type_annotation.cloned(),
-            mutable,
-        );
-        self.items.push(new_var);
-        *self.items.last().expect("just added, cannot fail")
+        self.parent.as_ref()?.lookup_member(variables, iden)
+    }
+
+    /// Variables can only be assigned again if they are mutable, and not from another file.
+    fn check_mutable(&self, variables: &Variables, var: Variable, span: Span) -> Result<Variable, TelErr> {
+        if let Some(imported) = self.import_span(var) {
+            return Err(TelErr::ScopeErr {
+                span,
+                previous: imported,
+                msg: format!("'{}' is imported, and cannot be assigned in this file", var.iden(variables)),
+            })
+        }
+        if !var.mutable(variables) {
+            return Err(TelErr::ImmutableAssign {
+                iden: var.iden(variables).clone(),
+                span,
+                declared: var.span(variables),
+            })
+        }
+        Ok(var)
+    }
+
+    fn import_span(&self, var: Variable) -> Option<Span> {
+        if let Some((_, span)) = self.imported.iter().find(|(known, _)| *known == var) {
+            return Some(*span)
+        }
+        self.parent.as_ref()?.import_span(var)
+    }
+
+    /// Find a variable declared in this scope, ignoring any parents.
+    pub fn find_in_scope(
+        &self,
+        variables: &Variables,
+        iden: &Identifier,
+    ) -> Option<Variable> {
+        self.items.iter()
+            .find(|known| known.iden(variables) == iden)
+            .copied()
     }
 
     pub fn lookup(
-        &mut self,
-        variables: &mut Variables,
+        &self,
+        variables: &Variables,
         iden: &Identifier,
     ) -> Option<Variable> {
-        for &known in &self.items {
-            if known.iden(variables) == iden {
-                return Some(known)
-            }
+        if let Some(known) = self.find_in_scope(variables, iden) {
+            return Some(known)
+        }
+        self.parent.as_ref()?.lookup(variables, iden)
+    }
+
+    /// Like [Self::lookup], but only up to and including the closest function scope.
+    fn lookup_in_function(
+        &self,
+        variables: &Variables,
+        iden: &Identifier,
+    ) -> Option<Variable> {
+        if let Some(known) = self.find_in_scope(variables, iden) {
+            return Some(known)
         }
-        if let Some(_parent) = &self.parent {
-            todo!("nested scopes not yet implemented")
+        match self.kind {
+            ScopeKind::Function => None,
+            ScopeKind::Block => self.parent.as_ref()?.lookup_in_function(variables, iden),
         }
-        None
     }
 }
+
@@ -0,0 +1,1668 @@
+//! Type checking of the resolved program.
+//!
+//! Inference is from expression to result: the type of an expression follows from the types
+//! of its parts, and an unannotated variable gets the type of the first value assigned to it.
+//! Anything that cannot be inferred this way is [Type::Unknown], which is not checked.
+
+use std::collections::HashMap;
+use std::slice;
+
+use tel_ast as ast;
+use tel_common::Identifier;
+use tel_common::Span;
+use tel_common::TelErr;
+use tel_hir as hir;
+use tel_hir::Builtin;
+use tel_hir::TelFile;
+use tel_hir::Variable;
+use tel_hir::Variables;
+
+pub use self::typ::Type;
+
+mod typ;
+
+/// Inferred types of all the variables in a file.
+#[derive(Debug)]
+pub struct Types {
+    variables: HashMap<Variable, Type>,
+}
+
+impl Types {
+    /// Inferred type of a variable, if it was reached by the checker.
+    pub fn of(&self, var: Variable) -> Option<&Type> {
+        self.variables.get(&var)
+    }
+
+    /// Readable `name: type` for each variable, in order of declaration.
+    pub fn describe(&self, variables: &Variables) -> Vec<String> {
+        let mut vars = self.variables.keys()
+            .copied()
+            .filter(|var| var.builtin().is_none())
+            .collect::<Vec<_>>();
+        vars.sort();
+        vars.into_iter()
+            .map(|var| format!("{}: {}", var.iden(variables), self.variables[&var].show(variables)))
+            .collect()
+    }
+}
+
+pub fn check_types(file: &TelFile) -> Result<Types, TelErr> {
+    let mut checker = Checker {
+        file,
+        structs: file.structs.iter().map(|strct| (strct.var, strct)).collect(),
+        enums: file.enums.iter().map(|enm| (enm.var, enm)).collect(),
+        interfaces: file.interfaces.iter().map(|interface| (interface.var, interface)).collect(),
+        types: Builtin::all().map(|builtin| (builtin.var(), builtin_type(builtin))).collect(),
+        returns: Vec::new(),
+        generics: Vec::new(),
+        generic_fns: HashMap::new(),
+    };
+    checker.check_declarations()?;
+    for function in &file.functions {
+        let signature = checker.signature(&function.closure)?;
+        checker.types.insert(function.var, signature);
+        checker.add_generic_fn(function.var, &function.closure);
+    }
+    for strct in &file.structs {
+        checker.generics = strct.generics.to_vec();
+        for method in &strct.methods {
+            let signature = checker.signature(&method.closure)?;
+            checker.types.insert(method.var, signature);
+            checker.add_generic_fn(method.var, &method.closure);
+        }
+    }
+    for imp in &file.impls {
+        checker.generics = checker.generics_of(imp.target).to_vec();
+        for method in &imp.methods {
+            let signature = checker.signature(&method.closure)?;
+            checker.types.insert(method.var, signature);
+            checker.add_generic_fn(method.var, &method.closure);
+        }
+    }
+    // check bodies before the top-level code, so that calls can use inferred return types
+    for strct in &file.structs {
+        checker.generics = strct.generics.to_vec();
+        for method in &strct.methods {
+            let name = format!("{}.{}", checker.name(strct.var), checker.name(method.var));
+            let typ = checker.closure_type(&method.closure, &name)?;
+            checker.types.insert(method.var, typ);
+        }
+    }
+    for imp in &file.impls {
+        checker.generics = checker.generics_of(imp.target).to_vec();
+        for method in &imp.methods {
+            let name = format!("{}.{}", checker.name(imp.target), checker.name(method.var));
+            let typ = checker.closure_type(&method.closure, &name)?;
+            checker.types.insert(method.var, typ);
+        }
+        checker.check_impl(imp)?;
+    }
+    checker.generics.clear();
+    for function in &file.functions {
+        let typ = checker.closure_type(&function.closure, function.var.iden(&file.variables).as_str())?;
+        checker.types.insert(function.var, typ);
+    }
+    checker.block_type(&file.main)?;
+    Ok(Types { variables: checker.types })
+}
+
+fn type_err<T>(span: Span, msg: impl Into<String>) -> Result<T, TelErr> {
+    Err(TelErr::TypeErr { span, declared: None, msg: msg.into() })
+}
+
+struct Checker<'a> {
+    file: &'a TelFile,
+    structs: HashMap<Variable, &'a hir::Struct>,
+    enums: HashMap<Variable, &'a hir::Enum>,
+    interfaces: HashMap<Variable, &'a hir::Interface>,
+    types: HashMap<Variable, Type>,
+    /// For each closure being checked, the types of the values it returns with `return`, and where.
+    returns: Vec<Vec<(Type, Span)>>,
+    /// Generic parameters that can be used in annotations: those of the struct in methods,
+    /// and those of the functions that are being checked.
+    generics: Vec<hir::TypeParam>,
+    /// Generic parameters of functions and methods that have them, inferred at each call.
+    generic_fns: HashMap<Variable, &'a [hir::TypeParam]>,
+}
+
+impl<'a> Checker<'a> {
+    fn check_declarations(&mut self) -> Result<(), TelErr> {
+        for strct in self.file.structs.iter() {
+            self.types.insert(strct.var, Type::Declaration(strct.var));
+            self.check_generics(&strct.generics)?;
+            for field in &strct.fields {
+                self.resolve(&field.typ, &strct.generics)?;
+            }
+        }
+        for enm in self.file.enums.iter() {
+            self.types.insert(enm.var, Type::Declaration(enm.var));
+            self.check_generics(&enm.generics)?;
+            for variant in &enm.variants {
+                if let hir::EnumVariant::Existing(typ) = variant {
+                    match self.resolve(typ, &enm.generics)? {
+                        Type::Named(_, _) => {}
+                        other => return type_err(typ.span, format!(
+                            "variant '{}' of enum '{}' must be a struct or enum",
+                            other.show(&self.file.variables),
+                            self.name(enm.var),
+                        )),
+                    }
+                }
+            }
+        }
+        for interface in self.file.interfaces.iter() {
+            self.types.insert(interface.var, Type::Declaration(interface.var));
+            for method in &interface.methods {
+                self.signature_type(method)?;
+            }
+        }
+        for (ix, imp) in self.file.impls.iter().enumerate() {
+            if !self.interfaces.contains_key(&imp.interface) {
+                return type_err(imp.span, format!("'{}' is not an interface", self.name(imp.interface)))
+            }
+            if !self.structs.contains_key(&imp.target) && !self.enums.contains_key(&imp.target) {
+                return type_err(imp.span, format!(
+                    "cannot implement '{}' for '{}', which is not a struct or enum",
+                    self.name(imp.interface),
+                    self.name(imp.target),
+                ))
+            }
+            if self.file.impls[..ix].iter().any(|other| other.interface == imp.interface && other.target == imp.target) {
+                return type_err(imp.span, format!("'{}' is implemented twice for '{}'", self.name(imp.interface), self.name(imp.target)))
+            }
+        }
+        Ok(())
+    }
+
+    fn check_generics(&self, generics: &[hir::TypeParam]) -> Result<(), TelErr> {
+        for param in generics {
+            if let Some(bound) = &param.bound {
+                let bound_name = bound.iden.as_str();
+                if Type::Unknown.satisfies_builtin_bound(bound_name).is_none() && self.find_interface(bound_name).is_none() {
+                    self.resolve(bound, &[])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The impl must have exactly the methods of the interface, with compatible types.
+    fn check_impl(&self, imp: &hir::Impl) -> Result<(), TelErr> {
+        let interface = self.interfaces[&imp.interface];
+        let interface_name = self.name(imp.interface);
+        let target = self.name(imp.target);
+        for method in &imp.methods {
+            let name = self.name(method.var);
+            let span = method.var.span(&self.file.variables);
+            let Some(signature) = interface.methods.iter().find(|signature| signature.iden.as_str() == name) else {
+                return type_err(span, format!("method '{name}' of '{target}' is not part of interface '{interface_name}'"))
+            };
+            let Type::Function(params, result) = self.types[&method.var].clone() else {
+                unreachable!("methods are functions")
+            };
+            let actual = Type::Function(params[1..].into(), result);
+            let expected = self.signature_type(signature)?;
+            if !self.accepts(&expected, &actual) {
+                return Err(TelErr::TypeErr {
+                    span,
+                    declared: self.local_span(imp.interface),
+                    msg: format!(
+                        "method '{name}' of '{target}' should be {} to implement '{interface_name}', but is {}",
+                        self.show(&expected),
+                        self.show(&actual),
+                    ),
+                })
+            }
+        }
+        if let Some(missing) = interface.methods.iter()
+                .find(|signature| !imp.methods.iter().any(|method| self.name(method.var) == signature.iden.as_str())) {
+            return Err(TelErr::TypeErr {
+                span: imp.span,
+                declared: self.local_span(imp.interface),
+                msg: format!("'{target}' does not implement method '{}' of interface '{interface_name}'", missing.iden),
+            })
+        }
+        Ok(())
+    }
+
+    /// Type of an interface method, without the instance it is called on.
+    fn signature_type(&self, signature: &hir::Signature) -> Result<Type, TelErr> {
+        let params = signature.params.iter()
+            .map(|param| match param {
+                Some(typ) => self.resolve(typ, &self.generics),
+                None => Ok(Type::Unknown),
+            })
+            .collect::<Result<Box<[_]>, TelErr>>()?;
+        let result = match &signature.ret {
+            Some(ret) => self.resolve(ret, &self.generics)?,
+            None => Type::Unknown,
+        };
+        Ok(Type::Function(params, Box::new(result)))
+    }
+
+    fn name(&self, var: Variable) -> &'a str {
+        var.iden(&self.file.variables).as_str()
+    }
+
+    fn show(&self, typ: &Type) -> String {
+        typ.show(&self.file.variables)
+    }
+
+    /// Where the variable is declared, unless that is in another file or not in the code.
+    fn local_span(&self, var: Variable) -> Option<Span> {
+        self.file.declares(var).then(|| var.span(&self.file.variables))
+    }
+
+    /// Struct or enum by name. Type annotations are not scoped, so this finds the first declaration.
+    fn find_named(&self, iden: &str) -> Option<Variable> {
+        self.file.structs.iter().map(|strct| strct.var)
+            .chain(self.file.enums.iter().map(|enm| enm.var))
+            .find(|var| self.name(*var) == iden)
+    }
+
+    fn find_interface(&self, iden: &str) -> Option<&'a hir::Interface> {
+        self.file.interfaces.iter().find(|interface| self.name(interface.var) == iden)
+    }
+
+    /// Impls for the type, or for enums that contain it.
+    fn impls_for(&self, var: Variable) -> impl Iterator<Item = &'a hir::Impl> + '_ {
+        self.file.impls.iter().filter(move |imp| imp.target == var || self.is_variant(var, imp.target))
+    }
+
+    fn implements(&self, typ: &Type, interface: Variable) -> bool {
+        match typ {
+            Type::Unknown => true,
+            Type::Named(var, _) => self.impls_for(*var).any(|imp| imp.interface == interface),
+            _ => false,
+        }
+    }
+
+    fn generics_of(&self, var: Variable) -> &'a [hir::TypeParam] {
+        if let Some(strct) = self.structs.get(&var) {
+            &strct.generics
+        } else {
+            &self.enums[&var].generics
+        }
+    }
+
+    /// Turn a type annotation into a type, where `params` are the generics that are in scope.
+    fn resolve(&self, typ: &ast::Type, params: &[hir::TypeParam]) -> Result<Type, TelErr> {
+        let ast::Type { iden, generics, .. } = typ;
+        if generics.is_empty() && params.iter().any(|param| &param.iden == iden) {
+            return Ok(Type::Param(iden.clone()))
+        }
+        if let Some(builtin) = Type::builtin(iden.as_str()) {
+            let expected = builtin.generic_count();
+            return match generics.len() {
+                0 => Ok(builtin),
+                count if count == expected => {
+                    let generics = generics.iter()
+                        .map(|generic| self.resolve(generic, params))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Type::collection(iden.as_str(), generics).expect("builtin collections take their generic count"))
+                }
+                count if expected > 0 => type_err(typ.span, format!("type '{iden}' takes {expected} generic parameters, got {count}")),
+                _ => type_err(typ.span, format!("type '{iden}' does not take generic parameters")),
+            }
+        }
+        let Some(var) = self.find_named(iden.as_str()) else {
+            if self.find_interface(iden.as_str()).is_some() {
+                return type_err(typ.span, format!("interface '{iden}' can only be used as a generic bound"))
+            }
+            return type_err(typ.span, format!("unknown type '{iden}'"))
+        };
+        let expected = self.generics_of(var);
+        let args = if generics.is_empty() {
+            vec![Type::Unknown; expected.len()]
+        } else if generics.len() == expected.len() {
+            generics.iter()
+                .map(|generic| self.resolve(generic, params))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            return type_err(typ.span, format!("type '{iden}' takes {} generic parameters, got {}", expected.len(), generics.len()))
+        };
+        for (arg, param) in args.iter().zip(expected) {
+            self.check_bound(arg, param, var, typ.span)?;
+        }
+        Ok(Type::Named(var, args.into_boxed_slice()))
+    }
+
+    /// The type must satisfy the bound of the parameter, where `span` is the code that makes it that type.
+    fn check_bound(&self, typ: &Type, param: &hir::TypeParam, owner: Variable, span: Span) -> Result<(), TelErr> {
+        let Some(bound) = &param.bound else {
+            return Ok(())
+        };
+        if self.satisfies(typ, bound)? {
+            return Ok(())
+        }
+        Err(TelErr::TypeErr {
+            span,
+            declared: self.local_span(owner).map(|_| bound.span),
+            msg: format!(
+                "type {} does not satisfy bound '{}: {}' of '{}'",
+                self.show(typ),
+                param.iden,
+                bound.iden,
+                self.name(owner),
+            ),
+        })
+    }
+
+    fn satisfies(&self, typ: &Type, bound: &ast::Type) -> Result<bool, TelErr> {
+        if let Type::Param(iden) = typ {
+            // a generic parameter only satisfies bounds that its own bound implies
+            let Some(own) = self.param_bound(iden) else {
+                return Ok(bound.iden.as_str() == "Any")
+            };
+            if own.iden == bound.iden || bound.iden.as_str() == "Any" || (own.iden.as_str(), bound.iden.as_str()) == ("Integer", "Number") {
+                return Ok(true)
+            }
+            if Type::Unknown.satisfies_builtin_bound(own.iden.as_str()).is_some() || self.find_interface(own.iden.as_str()).is_some() {
+                return Ok(false)
+            }
+            return self.satisfies(&self.resolve(own, &[])?, bound)
+        }
+        Ok(match (typ.satisfies_builtin_bound(bound.iden.as_str()), self.find_interface(bound.iden.as_str())) {
+            (Some(satisfied), _) => satisfied,
+            (None, Some(interface)) => self.implements(typ, interface.var),
+            (None, None) => self.accepts(&self.resolve(bound, &[])?, typ),
+        })
+    }
+
+    /// Bound of a generic parameter that is in scope, if it has one.
+    fn param_bound(&self, iden: &Identifier) -> Option<&ast::Type> {
+        self.generics.iter().rev().find(|generic| &generic.iden == iden)?.bound.as_ref()
+    }
+
+    /// The types that a generic parameter can be, as far as the checker knows them: the numbers for
+    /// builtin bounds, or the type it is bound by. `None` if it can be anything, or any type with some methods.
+    fn bound_types(&self, iden: &Identifier) -> Option<Vec<Type>> {
+        let bound = self.param_bound(iden)?;
+        match bound.iden.as_str() {
+            "Number" => Some(vec![Type::Int, Type::Float]),
+            "Integer" => Some(vec![Type::Int]),
+            _ if self.find_interface(bound.iden.as_str()).is_some() => None,
+            _ => self.resolve(bound, &[]).ok().map(|typ| vec![typ]),
+        }
+    }
+
+    /// Whether a value of type `value` can be used where `target` is expected.
+    fn accepts(&self, target: &Type, value: &Type) -> bool {
+        match (target, value) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Param(target), Type::Param(value)) if target == value => true,
+            // generic values can be used where every type their bound allows can be, generic targets only take themselves
+            (_, Type::Param(param)) => self.bound_types(param)
+                .is_some_and(|types| types.iter().all(|typ| self.accepts(target, typ))),
+            // integers can be used as floats, but not the other way around
+            (Type::Float, Type::Int) => true,
+            (Type::Iter(target), Type::Iter(value)) | (Type::List(target), Type::List(value)) => self.accepts(target, value),
+            (Type::Map(target_key, target_value), Type::Map(key, value)) =>
+                self.accepts(target_key, key) && self.accepts(target_value, value),
+            (Type::Named(target, target_args), Type::Named(value, value_args)) => if target == value {
+                target_args.iter().zip(value_args.iter())
+                    .all(|(target_arg, value_arg)| self.accepts(target_arg, value_arg))
+            } else {
+                self.is_variant(*value, *target)
+            },
+            (Type::Function(target_params, target_result), Type::Function(value_params, value_result)) =>
+                target_params.len() == value_params.len()
+                    && target_params.iter().zip(value_params.iter())
+                        .all(|(target_param, value_param)| self.accepts(value_param, target_param))
+                    && self.accepts(target_result, value_result),
+            _ => target == value,
+        }
+    }
+
+    /// Whether `var` is one of the variants of `enm`, possibly nested.
+    fn is_variant(&self, var: Variable, enm: Variable) -> bool {
+        let Some(decl) = self.enums.get(&enm) else {
+            return false
+        };
+        self.variants(decl).into_iter()
+            .any(|variant| variant == var || self.is_variant(var, variant))
+    }
+
+    /// The struct or enum of each direct variant (existing types that are invalid are skipped).
+    fn variants(&self, enm: &hir::Enum) -> Vec<Variable> {
+        enm.variants.iter()
+            .filter_map(|variant| match variant {
+                hir::EnumVariant::Struct(variant_var) | hir::EnumVariant::Enum(variant_var) => Some(*variant_var),
+                hir::EnumVariant::Existing(typ) => match self.resolve(typ, &enm.generics) {
+                    Ok(Type::Named(variant_var, _)) => Some(variant_var),
+                    _ => None,
+                },
+            })
+            .collect()
+    }
+
+    /// Type of the value in a match arm. Generic parameters of the variant are known if the enum
+    /// has a parameter with the same name, like `T` in `enum Option<T> { struct Some<T> { value: T } }`.
+    fn variant_type(&self, value: &Type, variant: Variable, span: Span) -> Result<Type, TelErr> {
+        if !self.structs.contains_key(&variant) && !self.enums.contains_key(&variant) {
+            return type_err(span, format!("'{}' in match is not a struct or enum", self.name(variant)))
+        }
+        let (value_var, value_args) = match value {
+            Type::Named(value_var, value_args) => (*value_var, &**value_args),
+            Type::Unknown => return Ok(Type::Named(variant, vec![Type::Unknown; self.generics_of(variant).len()].into_boxed_slice())),
+            other => return type_err(span, format!("cannot match on {}", self.show(other))),
+        };
+        if value_var != variant && !self.is_variant(variant, value_var) {
+            return type_err(span, format!("'{}' is not a variant of {}", self.name(variant), self.show(value)))
+        }
+        let value_params = self.generics_of(value_var);
+        let args = self.generics_of(variant).iter()
+            .map(|param| value_params.iter()
+                .position(|value_param| value_param.iden == param.iden)
+                .and_then(|ix| value_args.get(ix).cloned())
+                .unwrap_or(Type::Unknown))
+            .collect();
+        Ok(Type::Named(variant, args))
+    }
+
+    /// Every variant of the value must be handled by an arm, either directly or through an enum that contains it.
+    fn check_exhaustive(&self, value: &Type, arms: &[hir::MatchArm], span: Span) -> Result<(), TelErr> {
+        let Type::Named(var, _) = value else {
+            return Ok(())
+        };
+        let covered = arms.iter().map(|arm| arm.variant).collect::<Vec<_>>();
+        let mut missing = Vec::new();
+        match self.enums.get(var) {
+            Some(enm) => for variant in self.variants(enm) {
+                self.missing_variants(variant, &covered, &mut missing);
+            },
+            None => self.missing_variants(*var, &covered, &mut missing),
+        }
+        if missing.is_empty() {
+            return Ok(())
+        }
+        let names = missing.iter().map(|var| format!("'{}'", self.name(*var))).collect::<Vec<_>>();
+        type_err(span, format!("match on {} does not handle {}, add those or an 'else'", self.show(value), names.join(", ")))
+    }
+
+    /// Add the variants of `var` that no arm handles, or `var` itself if nothing inside it is handled.
+    fn missing_variants(&self, var: Variable, covered: &[Variable], missing: &mut Vec<Variable>) {
+        if covered.contains(&var) {
+            return
+        }
+        let Some(enm) = self.enums.get(&var) else {
+            missing.push(var);
+            return
+        };
+        let variants = self.variants(enm);
+        let mut inside = Vec::new();
+        for variant in &variants {
+            self.missing_variants(*variant, covered, &mut inside);
+        }
+        // an enum without variants has no values, so there is nothing to handle
+        if !variants.is_empty() && inside == variants {
+            missing.push(var)
+        } else {
+            missing.extend(inside)
+        }
+    }
+
+    /// Like [unify], but different variants give the innermost enum that contains them all,
+    /// like `Option<i64>` for `Some<i64>` and `None`. Generic parameters are matched by name.
+    fn common_type(&self, types: Vec<Type>) -> Type {
+        let unified = unify(types.clone());
+        if unified != Type::Unknown {
+            return unified
+        }
+        let named = types.iter()
+            .filter(|typ| **typ != Type::Unknown)
+            .map(|typ| match typ {
+                Type::Named(var, generics) => Some((*var, &**generics)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(named) = named.filter(|named| !named.is_empty()) else {
+            return Type::Unknown
+        };
+        let candidates = self.file.enums.iter()
+            .map(|enm| enm.var)
+            .filter(|enm| named.iter().all(|(var, _)| var == enm || self.is_variant(*var, *enm)))
+            .collect::<Vec<_>>();
+        let Some(enm) = candidates.iter().copied()
+            .find(|enm| !candidates.iter().any(|other| self.is_variant(*other, *enm))) else {
+            return Type::Unknown
+        };
+        let args = self.generics_of(enm).iter()
+            .map(|param| unify(named.iter()
+                .filter_map(|(var, generics)| {
+                    let ix = self.generics_of(*var).iter().position(|own| own.iden == param.iden)?;
+                    generics.get(ix).cloned()
+                })
+                .collect()))
+            .collect();
+        Type::Named(enm, args)
+    }
+
+    /// The value at `span` must fit the target type, which is required by the code at `declared`.
+    fn expect(
+        &self,
+        target: &Type,
+        value: &Type,
+        span: Span,
+        declared: Option<Span>,
+        context: impl FnOnce() -> String,
+    ) -> Result<(), TelErr> {
+        if self.accepts(target, value) {
+            return Ok(())
+        }
+        Err(TelErr::TypeErr {
+            span,
+            declared,
+            msg: format!("{} should be {}, but got {}", context(), self.show(target), self.show(value)),
+        })
+    }
+
+    fn annotation(&self, var: Variable) -> Result<Option<Type>, TelErr> {
+        var.type_annotation(&self.file.variables)
+            .map(|typ| self.resolve(typ, &self.generics))
+            .transpose()
+    }
+
+    fn add_generic_fn(&mut self, var: Variable, closure: &'a hir::Closure) {
+        if !closure.generics.is_empty() {
+            self.generic_fns.insert(var, &closure.generics);
+        }
+    }
+
+    /// Type of a function based only on annotations, used before the body has been checked.
+    fn signature(&mut self, closure: &hir::Closure) -> Result<Type, TelErr> {
+        self.check_generics(&closure.generics)?;
+        let outer = self.generics.len();
+        self.generics.extend(closure.generics.iter().cloned());
+        let signature = self.signature_in_scope(closure);
+        self.generics.truncate(outer);
+        signature
+    }
+
+    fn signature_in_scope(&self, closure: &hir::Closure) -> Result<Type, TelErr> {
+        let params = closure.params.iter()
+            .map(|param| Ok(self.annotation(*param)?.unwrap_or(Type::Unknown)))
+            .collect::<Result<Box<[_]>, TelErr>>()?;
+        let result = match &closure.ret {
+            Some(ret) => self.resolve(ret, &self.generics)?,
+            None => Type::Unknown,
+        };
+        Ok(Type::Function(params, Box::new(result)))
+    }
+
+    fn closure_type(&mut self, closure: &'a hir::Closure, name: &str) -> Result<Type, TelErr> {
+        let Type::Function(params, declared) = self.signature(closure)? else {
+            unreachable!("signature is always a function")
+        };
+        let outer = self.generics.len();
+        self.generics.extend(closure.generics.iter().cloned());
+        let result = self.closure_body_type(closure, params, *declared, name);
+        self.generics.truncate(outer);
+        result
+    }
+
+    fn closure_body_type(&mut self, closure: &'a hir::Closure, params: Box<[Type]>, declared: Type, name: &str) -> Result<Type, TelErr> {
+        for (param, typ) in closure.params.iter().zip(params.iter()) {
+            self.types.insert(*param, typ.clone());
+        }
+        let (body, mut results) = if closure.lambda {
+            // a `return` in a lambda is for the function around it
+            (self.block_type(&closure.body), Vec::new())
+        } else {
+            self.returns.push(Vec::new());
+            let body = self.block_type(&closure.body);
+            (body, self.returns.pop().expect("pushed above"))
+        };
+        results.push((body?, block_span(&closure.body).unwrap_or(closure.span)));
+        let result = match &closure.ret {
+            Some(ret) => {
+                for (result, span) in &results {
+                    self.expect(&declared, result, *span, Some(ret.span), || format!("result of '{name}'"))?;
+                }
+                declared
+            }
+            None => self.common_type(results.into_iter().map(|(result, _)| result).collect()),
+        };
+        Ok(Type::Function(params, Box::new(result)))
+    }
+
+    /// The type of the last statement, or [Type::Void] if that is not an expression.
+    fn block_type(&mut self, block: &'a hir::Block) -> Result<Type, TelErr> {
+        let mut last = Type::Void;
+        for stmt in &block.stmts {
+            last = match stmt {
+                hir::Stmt::Assign(hir::Assignment { var, value }) => {
+                    if let hir::ExprKind::Closure(closure) = &value.kind {
+                        self.add_generic_fn(*var, closure);
+                    }
+                    let typ = self.expr_type(value)?;
+                    self.assign(*var, typ, value.span)?;
+                    Type::Void
+                }
+                hir::Stmt::SetField(hir::SetField { var, fields, value }) => {
+                    let span = value.span;
+                    let value = self.expr_type(value)?;
+                    let mut target = self.types.get(var).cloned().unwrap_or(Type::Unknown);
+                    for field in fields {
+                        target = match self.field_type(&target, field.as_str())? {
+                            Some(field_type) => field_type,
+                            None if target == Type::Unknown => Type::Unknown,
+                            None => return type_err(span, format!("{} has no field '{field}'", self.show(&target))),
+                        };
+                    }
+                    let name = fields.last().expect("field assignment has at least one field");
+                    self.expect(&target, &value, span, None, || format!("value assigned to field '{name}'"))?;
+                    Type::Void
+                }
+                hir::Stmt::Expression(expr) => self.expr_type(expr)?,
+                hir::Stmt::Return(expr) => {
+                    let value = self.expr_type(expr)?;
+                    if let Some(returns) = self.returns.last_mut() {
+                        returns.push((value, expr.span));
+                    }
+                    // the block does not produce a value if it returns
+                    Type::Unknown
+                }
+            }
+        }
+        Ok(last)
+    }
+
+    /// Assign the value at `span` to the variable, which gets its type if it does not have one yet.
+    fn assign(&mut self, var: Variable, value: Type, span: Span) -> Result<(), TelErr> {
+        if let Some(existing) = self.types.get(&var) {
+            let declared = self.local_span(var);
+            return self.expect(existing, &value, span, declared, || format!("value assigned to '{}'", self.name(var)))
+        }
+        let typ = match self.annotation(var)? {
+            Some(annotated) => {
+                let declared = var.type_annotation(&self.file.variables).map(|typ| typ.span);
+                self.expect(&annotated, &value, span, declared, || format!("value assigned to '{}'", self.name(var)))?;
+                annotated
+            }
+            None => value,
+        };
+        self.types.insert(var, typ);
+        Ok(())
+    }
+
+    fn expr_type(&mut self, expr: &'a hir::Expr) -> Result<Type, TelErr> {
+        let span = expr.span;
+        Ok(match &expr.kind {
+            hir::ExprKind::Int(_) => Type::Int,
+            hir::ExprKind::Float(_) => Type::Float,
+            hir::ExprKind::Text(_) => Type::Text,
+            hir::ExprKind::Interpolate(parts) => {
+                self.expr_types(parts)?;
+                Type::Text
+            }
+            hir::ExprKind::List(elements) => Type::List(Box::new(unify(self.expr_types(elements)?))),
+            hir::ExprKind::Map(entries) => {
+                let mut keys = Vec::with_capacity(entries.len());
+                let mut values = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    keys.push(self.expr_type(key)?);
+                    values.push(self.expr_type(value)?);
+                }
+                Type::Map(Box::new(unify(keys)), Box::new(unify(values)))
+            }
+            hir::ExprKind::Index(base, index) => {
+                let base_type = self.expr_type(base)?;
+                let index_type = self.expr_type(index)?;
+                self.index_type(&base_type, &index_type, base.span, index.span)?
+            }
+            hir::ExprKind::Propagate(inner) => {
+                let inner = self.expr_type(inner)?;
+                let (value, returned) = self.propagate_type(&inner, span)?;
+                if let Some(returns) = self.returns.last_mut() {
+                    returns.push((returned, span));
+                }
+                value
+            }
+            hir::ExprKind::Invoke(hir::Invoke { var, args }) => {
+                let callee = self.types.get(var).cloned().unwrap_or(Type::Unknown);
+                if args.is_empty() && !self.is_nullary_callable(&callee) {
+                    return Ok(callee)
+                }
+                let call = Call { span, args: &spans(args), declared: self.local_span(*var) };
+                let args = self.expr_types(args)?;
+                match (var.builtin(), self.generic_fns.get(var).copied()) {
+                    (Some(builtin), _) => self.builtin_call_type(builtin.with_arity(args.len()), &args, &call)?,
+                    (None, Some(generics)) => self.generic_call_type(*var, generics, callee, &args, &call)?,
+                    (None, None) => self.call_type(callee, &args, &call, self.name(*var))?,
+                }
+            }
+            hir::ExprKind::Dot(target, hir::Member { iden, args }) => {
+                // the target is the first argument of methods
+                let mut arg_spans = vec![target.span];
+                arg_spans.extend(spans(args));
+                let target = self.expr_type(target)?;
+                let args = self.expr_types(args)?;
+                self.member_type(target, iden.as_str(), &args, &Call { span, args: &arg_spans, declared: None })?
+            }
+            hir::ExprKind::Closure(closure) => self.closure_type(closure, "closure")?,
+            hir::ExprKind::If(branches, otherwise) => {
+                let mut results = Vec::with_capacity(branches.len() + 1);
+                for (condition, body) in branches {
+                    let condition_type = self.expr_type(condition)?;
+                    self.expect(&Type::Bool, &condition_type, condition.span, None, || "condition of 'if'".to_owned())?;
+                    results.push(self.block_type(body)?);
+                }
+                match otherwise {
+                    Some(body) => results.push(self.block_type(body)?),
+                    None => results.push(Type::Void),
+                }
+                self.common_type(results)
+            }
+            hir::ExprKind::While(condition, body) => {
+                let condition_type = self.expr_type(condition)?;
+                self.expect(&Type::Bool, &condition_type, condition.span, None, || "condition of 'while'".to_owned())?;
+                self.block_type(body)?
+            }
+            hir::ExprKind::ForEach(var, iterable, body) => {
+                let iterable_type = self.expr_type(iterable)?;
+                let element = self.element_type(&iterable_type, iterable.span)?;
+                self.assign(*var, element, iterable.span)?;
+                self.block_type(body)?
+            }
+            hir::ExprKind::Match(value, arms, otherwise) => {
+                let value = self.expr_type(value)?;
+                let mut results = Vec::with_capacity(arms.len() + 1);
+                for arm in arms {
+                    let arm_type = self.variant_type(&value, arm.variant, span)?;
+                    if let Some(binding) = arm.binding {
+                        self.assign(binding, arm_type, span)?;
+                    }
+                    results.push(self.block_type(&arm.body)?);
+                }
+                match otherwise {
+                    Some(body) => results.push(self.block_type(body)?),
+                    None => self.check_exhaustive(&value, arms, span)?,
+                }
+                self.common_type(results)
+            }
+        })
+    }
+
+    fn expr_types(&mut self, exprs: &'a [hir::Expr]) -> Result<Vec<Type>, TelErr> {
+        exprs.iter()
+            .map(|expr| self.expr_type(expr))
+            .collect()
+    }
+
+    /// Like [Self::call_type], but operators describe their operands, and equality accepts
+    /// any two types that could be equal.
+    fn builtin_call_type(&self, builtin: Builtin, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        let Type::Function(params, result) = builtin_type(builtin) else {
+            unreachable!("builtins are functions")
+        };
+        match (builtin, args) {
+            (Builtin::Eq | Builtin::Neq, [left, right]) => {
+                let (left, right) = (&self.widest(left), &self.widest(right));
+                if !self.accepts(left, right) && !self.accepts(right, left) {
+                    return type_err(call.span, format!("cannot compare {} and {}", self.show(left), self.show(right)))
+                }
+            }
+            // texts can be concatenated and compared alphabetically
+            (Builtin::Add | Builtin::Lt | Builtin::Gt | Builtin::Le | Builtin::Ge, [left, right]) if self.are_texts(left, right) =>
+                return Ok(if builtin == Builtin::Add { Type::Text } else { Type::Bool }),
+            (_, [left, right]) if builtin.is_operator() => {
+                self.expect(&params[0], left, call.arg(0), None, || format!("left side of {builtin:?}"))?;
+                self.expect(&params[1], right, call.arg(1), None, || format!("right side of {builtin:?}"))?;
+                if builtin.is_arithmetic() {
+                    return Ok(arithmetic_type(left, right))
+                }
+            }
+            (_, [operand]) if builtin.is_operator() => {
+                self.expect(&params[0], operand, call.arg(0), None, || format!("operand of {builtin:?}"))?;
+                if builtin.is_arithmetic() {
+                    return Ok(arithmetic_type(operand, operand))
+                }
+            }
+            _ if builtin.is_collection() => return self.collection_call_type(builtin, args, call),
+            _ if builtin.is_iterator() => return self.iterator_call_type(builtin, args, call),
+            _ if builtin.is_optional() => return self.optional_call_type(builtin, args, call),
+            _ => return self.call_type(Type::Function(params, result), args, call, builtin.name()),
+        }
+        Ok(*result)
+    }
+
+    /// For generic parameters, the most general type that their bound allows, for comparisons.
+    fn widest(&self, typ: &Type) -> Type {
+        let Type::Param(iden) = typ else {
+            return typ.clone()
+        };
+        match self.bound_types(iden) {
+            Some(types) => unify(types),
+            None => Type::Unknown,
+        }
+    }
+
+    /// Whether the operands are texts, where one of them may be unknown.
+    fn are_texts(&self, left: &Type, right: &Type) -> bool {
+        (*left == Type::Text || *right == Type::Text) && self.accepts(&Type::Text, left) && self.accepts(&Type::Text, right)
+    }
+
+    /// Type of `base[index]`, which is an element of a list, a character of a text or a value of a map.
+    fn index_type(&self, base: &Type, index: &Type, base_span: Span, index_span: Span) -> Result<Type, TelErr> {
+        Ok(match base {
+            Type::List(element) => {
+                self.expect(&Type::Int, index, index_span, None, || "index of list".to_owned())?;
+                (**element).clone()
+            }
+            Type::Text => {
+                self.expect(&Type::Int, index, index_span, None, || "index of text".to_owned())?;
+                Type::Text
+            }
+            Type::Map(key, value) => {
+                self.expect(key, index, index_span, None, || "key of map".to_owned())?;
+                (**value).clone()
+            }
+            Type::Unknown => Type::Unknown,
+            other => return type_err(base_span, format!("cannot index into {}", self.show(other))),
+        })
+    }
+
+    /// The collection builtins are generic over the element, key and value types of the collection.
+    /// Adding to a collection can make those types known, like `[].push(1)` being a list of integers.
+    fn collection_call_type(&self, builtin: Builtin, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        let name = builtin.name();
+        if args.len() != builtin.params().len() {
+            return type_err(call.span, format!("'{name}' expects {} arguments, got {}", builtin.params().len(), args.len()))
+        }
+        let arg = |nr: usize| move || format!("argument {nr} of '{name}'");
+        let expect = |target: &Type, nr: usize| self.expect(target, &args[nr - 1], call.arg(nr - 1), None, arg(nr));
+        Ok(match (builtin, &args[0]) {
+            (_, Type::Unknown) => {
+                let Type::Function(_, result) = builtin_type(builtin) else {
+                    unreachable!("builtins are functions")
+                };
+                *result
+            }
+            (Builtin::Len, Type::Text | Type::List(_) | Type::Map(..)) => Type::Int,
+            (Builtin::Contains, Type::Text) => {
+                expect(&Type::Text, 2)?;
+                Type::Bool
+            }
+            (Builtin::Contains, Type::List(element) | Type::Map(element, _)) => {
+                expect(element, 2)?;
+                Type::Bool
+            }
+            (Builtin::Push, Type::List(element)) => {
+                expect(element, 2)?;
+                Type::List(Box::new(unify(vec![(**element).clone(), args[1].clone()])))
+            }
+            (Builtin::Insert, Type::Map(key, value)) => {
+                expect(key, 2)?;
+                expect(value, 3)?;
+                Type::Map(
+                    Box::new(unify(vec![(**key).clone(), args[1].clone()])),
+                    Box::new(unify(vec![(**value).clone(), args[2].clone()])),
+                )
+            }
+            (Builtin::Remove, Type::Map(key, _)) => {
+                expect(key, 2)?;
+                args[0].clone()
+            }
+            (Builtin::Keys, Type::Map(key, _)) => Type::Iter(key.clone()),
+            (Builtin::Values, Type::Map(_, value)) => Type::Iter(value.clone()),
+            (_, other) => return type_err(call.arg(0), format!("'{name}' cannot be used on {}", self.show(other))),
+        })
+    }
+
+    /// Type of the values that iterating over something gives, like in `for` or `map`.
+    /// For maps these are the keys.
+    fn element_type(&self, iterable: &Type, span: Span) -> Result<Type, TelErr> {
+        Ok(match iterable {
+            Type::Iter(element) | Type::List(element) | Type::Map(element, _) => (**element).clone(),
+            Type::Text => Type::Text,
+            Type::Unknown => Type::Unknown,
+            other => return type_err(span, format!("cannot iterate over {}", self.show(other))),
+        })
+    }
+
+    /// The iterator builtins are generic over the element type, which is tracked through the pipeline.
+    fn iterator_call_type(&self, builtin: Builtin, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        let name = builtin.name();
+        if args.len() != builtin.params().len() {
+            return type_err(call.span, format!("'{name}' expects {} arguments, got {}", builtin.params().len(), args.len()))
+        }
+        let element = self.element_type(&args[0], call.arg(0))?;
+        Ok(match builtin {
+            Builtin::Iter => Type::Iter(Box::new(element)),
+            Builtin::Map => Type::Iter(Box::new(self.callback_type(&args[1], &[element], name, call.arg(1))?)),
+            Builtin::Filter => {
+                let keep = self.callback_type(&args[1], slice::from_ref(&element), name, call.arg(1))?;
+                self.expect(&Type::Bool, &keep, call.arg(1), None, || format!("result of function passed to '{name}'"))?;
+                Type::Iter(Box::new(element))
+            }
+            Builtin::Take => {
+                self.expect(&Type::Int, &args[1], call.arg(1), None, || format!("argument 2 of '{name}'"))?;
+                Type::Iter(Box::new(element))
+            }
+            Builtin::Sum => {
+                self.expect(&Type::Float, &element, call.arg(0), None, || format!("elements passed to '{name}'"))?;
+                arithmetic_type(&element, &element)
+            }
+            Builtin::ForEach => {
+                self.callback_type(&args[1], &[element], name, call.arg(1))?;
+                Type::Void
+            }
+            Builtin::Collect => Type::List(Box::new(element)),
+            Builtin::Join => {
+                self.expect(&Type::Text, &args[1], call.arg(1), None, || format!("argument 2 of '{name}'"))?;
+                Type::Text
+            }
+            _ => unreachable!("not an iterator builtin: {builtin:?}"),
+        })
+    }
+
+    /// Result type of a function that a builtin calls with arguments of the given types, like each element.
+    /// The callback is the argument at `span`.
+    fn callback_type(&self, callback: &Type, inputs: &[Type], name: &str, span: Span) -> Result<Type, TelErr> {
+        match callback {
+            Type::Unknown => Ok(Type::Unknown),
+            Type::Function(params, result) => {
+                if params.len() != inputs.len() {
+                    let expected = match inputs.len() {
+                        1 => "1 argument".to_owned(),
+                        count => format!("{count} arguments"),
+                    };
+                    return type_err(span, format!("function passed to '{name}' should take {expected}, but takes {}", params.len()))
+                }
+                for (param, input) in params.iter().zip(inputs) {
+                    self.expect(param, input, span, None, || format!("argument of function passed to '{name}'"))?;
+                }
+                Ok((**result).clone())
+            }
+            other => type_err(span, format!("'{name}' expects a function, got {}", self.show(other))),
+        }
+    }
+
+    /// The value type and error type of an `Option` or `Result` or one of their variants.
+    /// Options have no error type.
+    fn optional_parts(&self, typ: &Type) -> Option<(Type, Option<Type>)> {
+        let prelude = &self.file.prelude;
+        let Type::Named(var, generics) = typ else {
+            return None
+        };
+        let generic = |ix: usize| generics.get(ix).cloned().unwrap_or(Type::Unknown);
+        Some(match *var {
+            var if var == prelude.option || var == prelude.some => (generic(0), None),
+            var if var == prelude.none => (Type::Unknown, None),
+            var if var == prelude.result || var == prelude.ok => (generic(0), Some(generic(1))),
+            var if var == prelude.err => (Type::Unknown, Some(generic(0))),
+            _ => return None,
+        })
+    }
+
+    /// Type of `value?`, and the type of what it returns if there is no value.
+    fn propagate_type(&self, typ: &Type, span: Span) -> Result<(Type, Type), TelErr> {
+        let prelude = &self.file.prelude;
+        Ok(match self.optional_parts(typ) {
+            Some((value, None)) => (value, Type::Named(prelude.option, Box::new([Type::Unknown]))),
+            Some((value, Some(error))) => (value, Type::Named(prelude.result, Box::new([Type::Unknown, error]))),
+            None if *typ == Type::Unknown => (Type::Unknown, Type::Unknown),
+            None => return type_err(span, format!("'?' can only be used on Option or Result, got {}", self.show(typ))),
+        })
+    }
+
+    /// `then` makes an option from a condition, `alternatively` tries another condition if there is no value,
+    /// and `otherwise` gives the value or the result of the fallback function, which can take the error of results.
+    fn optional_call_type(&self, builtin: Builtin, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        let name = builtin.name();
+        if args.len() != builtin.params().len() {
+            return type_err(call.span, format!("'{name}' expects {} arguments, got {}", builtin.params().len(), args.len()))
+        }
+        let option = |value: Type| Type::Named(self.file.prelude.option, Box::new([value]));
+        if builtin == Builtin::Then {
+            self.expect(&Type::Bool, &args[0], call.arg(0), None, || format!("argument 1 of '{name}'"))?;
+            return Ok(option(self.callback_type(&args[1], &[], name, call.arg(1))?))
+        }
+        let (value, error) = match (builtin, self.optional_parts(&args[0])) {
+            (_, None) if args[0] == Type::Unknown => return Ok(Type::Unknown),
+            (Builtin::Alternatively, Some((value, None))) => (value, None),
+            (Builtin::Otherwise, Some(parts)) => parts,
+            _ => return type_err(call.arg(0), format!("'{name}' cannot be used on {}", self.show(&args[0]))),
+        };
+        let fallback = match builtin {
+            Builtin::Alternatively => {
+                self.expect(&Type::Bool, &args[1], call.arg(1), None, || format!("argument 2 of '{name}'"))?;
+                self.callback_type(&args[2], &[], name, call.arg(2))?
+            }
+            _ => {
+                let takes_error = matches!(&args[1], Type::Function(params, _) if !params.is_empty());
+                let inputs = error.filter(|_| takes_error).into_iter().collect::<Vec<_>>();
+                self.callback_type(&args[1], &inputs, name, call.arg(1))?
+            }
+        };
+        let fallback_span = call.arg(args.len() - 1);
+        self.expect(&value, &fallback, fallback_span, None, || format!("result of function passed to '{name}'"))?;
+        let value = unify(vec![value, fallback]);
+        Ok(if builtin == Builtin::Alternatively { option(value) } else { value })
+    }
+
+    /// See the interpreter, functions without parameters are called even without `()`.
+    fn is_nullary_callable(&self, typ: &Type) -> bool {
+        match typ {
+            Type::Function(params, _) => params.is_empty(),
+            Type::Declaration(var) => self.structs.get(var).is_some_and(|strct| strct.fields.is_empty()),
+            _ => false,
+        }
+    }
+
+    /// Call of a generic function, with the generic parameters inferred from the arguments.
+    fn generic_call_type(
+        &mut self,
+        var: Variable,
+        generics: &[hir::TypeParam],
+        callee: Type,
+        args: &[Type],
+        call: &Call,
+    ) -> Result<Type, TelErr> {
+        let name = self.name(var);
+        let Type::Function(params, result) = callee else {
+            return self.call_type(callee, args, call, name)
+        };
+        if params.len() != args.len() {
+            return type_err(call.span, format!("'{name}' expects {} arguments, got {}", params.len(), args.len()))
+        }
+        let mut bindings = vec![Type::Unknown; generics.len()];
+        for (param, arg) in params.iter().zip(args) {
+            infer_bindings(param, arg, generics, &mut bindings);
+        }
+        for (binding, param) in bindings.iter().zip(generics) {
+            self.check_bound(binding, param, var, call.span)?;
+        }
+        //TODO @mark: generic code is checked once against its bounds, and runs the same for every type, without specialising it per use
+        let callee = substitute(Type::Function(params, result), generics, &bindings);
+        self.call_type(callee, args, call, name)
+    }
+
+    fn call_type(&self, callee: Type, args: &[Type], call: &Call, name: &str) -> Result<Type, TelErr> {
+        match callee {
+            Type::Unknown => Ok(Type::Unknown),
+            Type::Function(params, result) => {
+                if params.len() != args.len() {
+                    return Err(TelErr::TypeErr {
+                        span: call.span,
+                        declared: call.declared,
+                        msg: format!("'{name}' expects {} arguments, got {}", params.len(), args.len()),
+                    })
+                }
+                for (ix, (param, arg)) in params.iter().zip(args).enumerate() {
+                    self.expect(param, arg, call.arg(ix), call.declared, || format!("argument {} of '{name}'", ix + 1))?;
+                }
+                Ok(*result)
+            }
+            Type::Declaration(var) => self.construct_type(var, args, call),
+            other => type_err(call.span, format!("'{name}' has type {}, which cannot be called", self.show(&other))),
+        }
+    }
+
+    /// Positional construction of a struct, inferring the generic parameters from the field values.
+    fn construct_type(&self, var: Variable, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        let name = self.name(var);
+        if self.interfaces.contains_key(&var) {
+            return type_err(call.span, format!("cannot create interface '{name}', it can only be used as a generic bound"))
+        }
+        let Some(strct) = self.structs.get(&var) else {
+            return type_err(call.span, format!("cannot create '{name}' directly, use one of the variants"))
+        };
+        if strct.fields.len() != args.len() {
+            return Err(TelErr::TypeErr {
+                span: call.span,
+                declared: call.declared,
+                msg: format!("'{name}' has {} fields, got {} values", strct.fields.len(), args.len()),
+            })
+        }
+        let mut bindings = vec![Type::Unknown; strct.generics.len()];
+        for (ix, (field, arg)) in strct.fields.iter().zip(args).enumerate() {
+            let field_type = self.resolve(&field.typ, &strct.generics)?;
+            infer_bindings(&field_type, arg, &strct.generics, &mut bindings);
+            let field_type = substitute(field_type, &strct.generics, &bindings);
+            let declared = call.declared.map(|_| field.typ.span);
+            self.expect(&field_type, arg, call.arg(ix), declared, || format!("field '{}' of '{name}'", field.iden))?;
+        }
+        for (binding, param) in bindings.iter().zip(strct.generics.iter()) {
+            self.check_bound(binding, param, var, call.span)?;
+        }
+        Ok(Type::Named(var, bindings.into_boxed_slice()))
+    }
+
+    /// Type of the field of a struct instance, or `None` if it has no such field.
+    fn field_type(&self, target: &Type, iden: &str) -> Result<Option<Type>, TelErr> {
+        let Type::Named(var, generics) = target else {
+            return Ok(None)
+        };
+        let Some(strct) = self.structs.get(var) else {
+            return Ok(None)
+        };
+        let Some(field) = strct.fields.iter().find(|field| field.iden.as_str() == iden) else {
+            return Ok(None)
+        };
+        Ok(Some(substitute(self.resolve(&field.typ, &strct.generics)?, &strct.generics, generics)))
+    }
+
+    /// Call of a method, where the first argument of the call is the instance that is the target.
+    fn method_call_type(&mut self, method: Variable, typ: Type, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        let Type::Function(params, result) = typ else {
+            unreachable!("methods are functions")
+        };
+        // the first parameter is the instance, which is the target
+        let callee = Type::Function(params[1..].into(), result);
+        let call = Call { span: call.span, args: call.args.get(1..).unwrap_or(&[]), declared: self.local_span(method) };
+        match self.generic_fns.get(&method).copied() {
+            Some(generics) => self.generic_call_type(method, generics, callee, args, &call),
+            None => self.call_type(callee, args, &call, self.name(method)),
+        }
+    }
+
+    /// Method of the interface that a generic parameter in scope is bound by.
+    fn bound_method(&self, param: &Identifier, iden: &str) -> Option<&'a hir::Signature> {
+        let bound = self.generics.iter().rev().find(|generic| &generic.iden == param)?.bound.as_ref()?;
+        self.find_interface(bound.iden.as_str())?
+            .methods.iter()
+            .find(|signature| signature.iden.as_str() == iden)
+    }
+
+    /// Field or method of the target, where the call has the target as first argument.
+    fn member_type(&mut self, target: Type, iden: &str, args: &[Type], call: &Call) -> Result<Type, TelErr> {
+        if let Some(field_type) = self.field_type(&target, iden)? {
+            return if args.is_empty() {
+                Ok(field_type)
+            } else {
+                let call = Call { span: call.span, args: call.args.get(1..).unwrap_or(&[]), declared: None };
+                self.call_type(field_type, args, &call, iden)
+            }
+        }
+        if let Type::Named(var, generics) = &target {
+            let methods = self.structs.get(var).map_or(&[][..], |strct| &strct.methods);
+            if let Some(method) = methods.iter().find(|method| self.name(method.var) == iden) {
+                let typ = substitute(self.types[&method.var].clone(), self.generics_of(*var), generics);
+                return self.method_call_type(method.var, typ, args, call)
+            }
+            let impl_method = self.impls_for(*var)
+                .flat_map(|imp| imp.methods.iter().map(move |method| (imp.target, method)))
+                .find(|(_, method)| self.name(method.var) == iden);
+            if let Some((impl_target, method)) = impl_method {
+                // generic parameters are only known if the impl is for this type, not for an enum that contains it
+                let args_of_target = if impl_target == *var { &generics[..] } else { &[] };
+                let typ = substitute(self.types[&method.var].clone(), self.generics_of(impl_target), args_of_target);
+                return self.method_call_type(method.var, typ, args, call)
+            }
+        }
+        if let Type::Param(param) = &target {
+            if let Some(signature) = self.bound_method(param, iden) {
+                let callee = self.signature_type(signature)?;
+                let call = Call { span: call.span, args: call.args.get(1..).unwrap_or(&[]), declared: None };
+                return self.call_type(callee, args, &call, iden)
+            }
+        }
+        if target == Type::Unknown {
+            return Ok(Type::Unknown)
+        }
+        let Some(builtin) = Builtin::method(iden, args.len()) else {
+            return type_err(call.span, format!(
+                "{} has no field or method '{iden}' that takes {} arguments",
+                self.show(&target),
+                args.len(),
+            ))
+        };
+        let mut all_args = Vec::with_capacity(args.len() + 1);
+        all_args.push(target);
+        all_args.extend_from_slice(args);
+        self.builtin_call_type(builtin, &all_args, call)
+    }
+}
+
+/// Where a call is in the code, for errors about it.
+struct Call<'s> {
+    span: Span,
+    /// Each argument, which for methods starts with the instance.
+    args: &'s [Span],
+    /// Declaration of the function, if it is in this file.
+    declared: Option<Span>,
+}
+
+impl Call<'_> {
+    fn arg(&self, ix: usize) -> Span {
+        self.args.get(ix).copied().unwrap_or(self.span)
+    }
+}
+
+fn spans(exprs: &[hir::Expr]) -> Vec<Span> {
+    exprs.iter().map(|expr| expr.span).collect()
+}
+
+/// The span of the last statement, which gives the value of the block.
+fn block_span(block: &hir::Block) -> Option<Span> {
+    match block.stmts.last()? {
+        hir::Stmt::Expression(expr) | hir::Stmt::Return(expr) => Some(expr.span),
+        hir::Stmt::Assign(assign) => Some(assign.value.span),
+        hir::Stmt::SetField(set) => Some(set.value.span),
+    }
+}
+
+/// Function type of a builtin, from the type names in its signature.
+fn builtin_type(builtin: Builtin) -> Type {
+    let resolve = |name: &str| match name.split_once('<') {
+        Some((collection, generics)) => Type::collection(collection, generics.trim_end_matches('>')
+                .split(", ")
+                .map(|generic| Type::builtin(generic).unwrap())
+                .collect())
+            .expect("builtin signatures use builtin types"),
+        None => Type::builtin(name).expect("builtin signatures use builtin types"),
+    };
+    Type::Function(
+        builtin.params().iter().map(|param| resolve(param)).collect(),
+        Box::new(resolve(builtin.result())),
+    )
+}
+
+/// Replace generic parameters by the types they have for a specific instance.
+fn substitute(typ: Type, params: &[hir::TypeParam], args: &[Type]) -> Type {
+    match typ {
+        Type::Param(iden) => params.iter()
+            .position(|param| param.iden == iden)
+            .map(|ix| args.get(ix).cloned().unwrap_or(Type::Unknown))
+            // parameters of an enclosing function stay generic
+            .unwrap_or(Type::Param(iden)),
+        Type::Named(var, generics) => Type::Named(var, generics.into_vec().into_iter()
+            .map(|generic| substitute(generic, params, args))
+            .collect()),
+        Type::Iter(element) => Type::Iter(Box::new(substitute(*element, params, args))),
+        Type::List(element) => Type::List(Box::new(substitute(*element, params, args))),
+        Type::Map(key, value) => Type::Map(Box::new(substitute(*key, params, args)), Box::new(substitute(*value, params, args))),
+        Type::Function(fn_params, result) => Type::Function(
+            fn_params.into_vec().into_iter()
+                .map(|param| substitute(param, params, args))
+                .collect(),
+            Box::new(substitute(*result, params, args)),
+        ),
+        other => other,
+    }
+}
+
+/// Bind generic parameters by matching the declared type against the actual type.
+/// The first binding of a parameter wins, later uses are checked against it.
+fn infer_bindings(declared: &Type, actual: &Type, params: &[hir::TypeParam], bindings: &mut [Type]) {
+    match (declared, actual) {
+        (Type::Param(iden), _) => {
+            if let Some(ix) = params.iter().position(|param| &param.iden == iden) {
+                if bindings[ix] == Type::Unknown {
+                    bindings[ix] = actual.clone();
+                }
+            }
+        }
+        (Type::Named(declared_var, declared_args), Type::Named(actual_var, actual_args)) if declared_var == actual_var => {
+            for (declared_arg, actual_arg) in declared_args.iter().zip(actual_args.iter()) {
+                infer_bindings(declared_arg, actual_arg, params, bindings);
+            }
+        }
+        (Type::Iter(declared), Type::Iter(actual)) | (Type::List(declared), Type::List(actual)) =>
+            infer_bindings(declared, actual, params, bindings),
+        (Type::Map(declared_key, declared_value), Type::Map(actual_key, actual_value)) => {
+            infer_bindings(declared_key, actual_key, params, bindings);
+            infer_bindings(declared_value, actual_value, params, bindings);
+        }
+        (Type::Function(declared_params, declared_result), Type::Function(actual_params, actual_result)) => {
+            for (declared_param, actual_param) in declared_params.iter().zip(actual_params.iter()) {
+                infer_bindings(declared_param, actual_param, params, bindings);
+            }
+            infer_bindings(declared_result, actual_result, params, bindings);
+        }
+        _ => {}
+    }
+}
+
+/// Result of arithmetic, which stays an integer unless a float is involved.
+/// A generic number stays generic with integers, since it is a float if they become floats.
+fn arithmetic_type(left: &Type, right: &Type) -> Type {
+    match (left, right) {
+        (Type::Float, _) | (_, Type::Float) => Type::Float,
+        (Type::Param(left_param), Type::Param(right_param)) if left_param == right_param => left.clone(),
+        (Type::Param(_), Type::Int) => left.clone(),
+        (Type::Int, Type::Param(_)) => right.clone(),
+        (Type::Param(_), _) | (_, Type::Param(_)) => Type::Unknown,
+        (Type::Int, _) | (_, Type::Int) => Type::Int,
+        _ => Type::Unknown,
+    }
+}
+
+/// The common type if all are the same (ignoring unknowns), otherwise unknown.
+/// Integers and floats together are floats.
+fn unify(types: Vec<Type>) -> Type {
+    let mut known = types.into_iter().filter(|typ| *typ != Type::Unknown);
+    let Some(first) = known.next() else {
+        return Type::Unknown
+    };
+    known.try_fold(first, |common, typ| match (common, typ) {
+        (common, typ) if common == typ => Some(common),
+        (Type::Int | Type::Float, Type::Int | Type::Float) => Some(Type::Float),
+        _ => None,
+    }).unwrap_or(Type::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tel_parser::str_to_ast;
+
+    use crate::modules::load_modules;
+    use crate::scoping::ast_to_api;
+
+    use super::*;
+
+    fn check(code: &str) -> Result<(TelFile, Types), TelErr> {
+        let ast = str_to_ast(PathBuf::new(), code.to_owned()).unwrap();
+        let file = ast_to_api(&load_modules(PathBuf::new(), code.to_owned(), ast).unwrap())?;
+        let types = check_types(&file)?;
+        Ok((file, types))
+    }
+
+    fn check_err(code: &str) -> String {
+        match check(code) {
+            Err(TelErr::TypeErr { msg, .. }) => msg,
+            Err(other) => panic!("expected type error, got {other:?}"),
+            Ok(_) => panic!("expected type error, but type check passed"),
+        }
+    }
+
+    fn type_of(code: &str, name: &str) -> String {
+        let (file, types) = check(code).unwrap();
+        let var = types.variables.keys()
+            .filter(|var| var.iden(&file.variables).as_str() == name)
+            .max()
+            .unwrap();
+        types.variables[var].show(&file.variables)
+    }
+
+    #[test]
+    fn infer_from_expression() {
+        assert_eq!(type_of("x = 1 + 2\n", "x"), "i64");
+        assert_eq!(type_of("x = 1 < 2\n", "x"), "Bool");
+        assert_eq!(type_of("f = fn(a: f64) { a * 2 }\nx = f(3)\n", "x"), "f64");
+        assert_eq!(type_of("x = fn(a: f64) { a == 2 }\n", "x"), "fn(f64): Bool");
+    }
+
+    #[test]
+    fn implicit_it_closure() {
+        assert_eq!(type_of("f = { it * 2 }\n", "f"), "fn(Any): i64");
+        assert_eq!(type_of("f = { 1 }\n", "f"), "fn(): i64");
+        assert_eq!(check_err("f = { it * 2 }\nx = f(1, 2)\n"), "'f' expects 1 arguments, got 2");
+        assert_eq!(check_err("f = fn() {}\nx = f(1)\n"), "'f' expects 0 arguments, got 1");
+    }
+
+    #[test]
+    fn annotation_mismatch() {
+        assert_eq!(check_err("x: f64 = \"hello\"\n"), "value assigned to 'x' should be f64, but got Text");
+    }
+
+    #[test]
+    fn error_spans() {
+        let err = check("a = 1\nx: Text = a + 2\n").unwrap_err();
+        let diagnostic = &err.diagnostics()[0];
+        assert_eq!(diagnostic.primary_span(), Some(Span::new(16, 21)));
+        assert_eq!(err, TelErr::TypeErr {
+            span: Span::new(16, 21),
+            declared: Some(Span::new(9, 13)),
+            msg: "value assigned to 'x' should be Text, but got i64".to_owned(),
+        });
+        let err = check("f = fn(a: i64) { a }\nf(\"no\")\n").unwrap_err();
+        assert!(matches!(err, TelErr::TypeErr { span, declared: Some(declared), .. }
+            if span == Span::new(23, 27) && declared == Span::new(0, 1)));
+    }
+
+    #[test]
+    fn reassign_different_type() {
+        assert_eq!(check_err("mut x = 1\nx = \"hello\"\n"), "value assigned to 'x' should be i64, but got Text");
+    }
+
+    #[test]
+    fn operator_operands() {
+        assert_eq!(check_err("x = 1 + \"a\"\n"), "right side of Add should be f64, but got Text");
+        assert_eq!(check_err("if (1) {\n}\n"), "condition of 'if' should be Bool, but got i64");
+    }
+
+    #[test]
+    fn function_arguments_and_result() {
+        assert_eq!(check_err("f = fn(a: f64) { a }\nf(\"a\")\n"), "argument 1 of 'f' should be f64, but got Text");
+        assert_eq!(check_err("f = fn(a: f64): Text { a }\n"), "result of 'f' should be Text, but got f64");
+        assert!(check("f = fn(n: f64): f64 {\n    if (n < 1) {\n        return 1\n    }\n    n * f(n - 1)\n}\n").is_ok());
+    }
+
+    #[test]
+    fn struct_fields() {
+        let code = "struct Point {\n    x: f64,\n    y: f64,\n}\n";
+        assert_eq!(type_of(&format!("{code}p = Point(1, 2)\nx = p.x\n"), "x"), "f64");
+        assert_eq!(check_err(&format!("{code}p = Point(1, \"a\")\n")), "field 'y' of 'Point' should be f64, but got Text");
+        assert_eq!(check_err(&format!("{code}p = Point(1, 2).z\n")), "Point has no field or method 'z' that takes 0 arguments");
+        assert_eq!(check_err("struct S {\n    x: Unknown\n}\n"), "unknown type 'Unknown'");
+    }
+
+    #[test]
+    fn generic_bound() {
+        let code = "struct Point<N: Number> {\n    x: N,\n    y: N,\n}\n";
+        assert_eq!(type_of(&format!("{code}p = Point(1, 2)\n"), "p"), "Point<i64>");
+        assert_eq!(type_of(&format!("{code}x = Point(1, 2).x\n"), "x"), "i64");
+        assert_eq!(check_err(&format!("{code}p = Point(\"a\", \"b\")\n")), "type Text does not satisfy bound 'N: Number' of 'Point'");
+        assert_eq!(check_err(&format!("{code}struct Line {{\n    start: Point<Text>\n}}\n")), "type Text does not satisfy bound 'N: Number' of 'Point'");
+    }
+
+    #[test]
+    fn generic_function_inference() {
+        let code = "larger = fn<T: Number>(a: T, b: T): T {\n    if (a > b) {\n        return a\n    }\n    b\n}\n";
+        assert_eq!(type_of(&format!("{code}x = larger(1, 2)\n"), "larger"), "fn(T, T): T");
+        assert_eq!(type_of(&format!("{code}x = larger(1, 2)\n"), "x"), "i64");
+        assert_eq!(check_err(&format!("{code}x = larger(\"a\", \"b\")\n")), "type Text does not satisfy bound 'T: Number' of 'larger'");
+        assert_eq!(check_err(&format!("{code}x = larger(1, \"b\")\n")), "argument 2 of 'larger' should be i64, but got Text");
+        let code = "first = fn<T>(a: T, b: T): T { a }\n";
+        assert_eq!(type_of(&format!("{code}x = first(\"a\", \"b\")\n"), "x"), "Text");
+    }
+
+    #[test]
+    fn generic_body_uses_bound() {
+        assert_eq!(type_of("f = fn<T: Number>(a: T): T { a * 2 + a }\n", "f"), "fn(T): T");
+        assert_eq!(type_of("f = fn<T: Number>(a: T): f64 { a.sqrt }\n", "f"), "fn(T): f64");
+        assert_eq!(type_of("f = fn<T: Number>(a: T) { a == 0 }\n", "f"), "fn(T): Bool");
+        assert_eq!(type_of("g = fn<N: Number>(x: N) { x }\nf = fn<I: Integer>(a: I) { g(a) }\n", "f"), "fn(I): I");
+        assert_eq!(check_err("f = fn<T>(a: T): Text { a }\n"), "result of 'f' should be Text, but got T");
+        assert_eq!(check_err("f = fn<T: Number>(a: T): i64 { a }\n"), "result of 'f' should be i64, but got T");
+        assert_eq!(check_err("f = fn<T: Number>(a: T, b: i64): T { b }\n"), "result of 'f' should be T, but got i64");
+        assert_eq!(check_err("f = fn<T>(a: T) { a.upper }\n"), "argument 1 of 'upper' should be Text, but got T");
+        assert_eq!(check_err("f = fn<T: Number>(a: T) { a[0] }\n"), "cannot index into T");
+        assert_eq!(check_err("g = fn<I: Integer>(x: I) { x }\nf = fn<N: Number>(a: N) { g(a) }\n"), "type N does not satisfy bound 'I: Integer' of 'g'");
+        assert_eq!(check_err("struct P<N: Number> {\n    x: N,\n    fn label(): Text { x }\n}\n"), "result of 'P.label' should be Text, but got N");
+    }
+
+    #[test]
+    fn generic_declared_bound() {
+        let code = "enum Currency {\n    struct Euro { cents: f64 },\n    struct Dollar { cents: f64 },\n}\n\
+            struct Wallet<C: Currency> {\n    money: C,\n    fn swap<D: Currency>(other: D) { Wallet(other) }\n}\n\
+            wrap = fn<C: Currency>(money: C) { Wallet(money) }\n";
+        assert_eq!(type_of(&format!("{code}w = wrap(Euro(5))\n"), "w"), "Wallet<Euro>");
+        assert_eq!(type_of(&format!("{code}w = wrap(Euro(5)).swap(Dollar(1))\n"), "w"), "Wallet<Dollar>");
+        assert_eq!(check_err(&format!("{code}w = wrap(5)\n")), "type i64 does not satisfy bound 'C: Currency' of 'wrap'");
+        assert_eq!(check_err(&format!("{code}w = wrap(Euro(5)).swap(\"a\")\n")), "type Text does not satisfy bound 'D: Currency' of 'swap'");
+        assert_eq!(check_err("f = fn<T: Unknown>(a: T) { a }\n"), "unknown type 'Unknown'");
+    }
+
+    #[test]
+    fn interface_bounds() {
+        let code = "interface Priced {\n    fn cents(): f64\n    fn scaled(factor: f64): f64\n}\n\
+            enum Currency {\n    struct Euro { amount: f64 },\n    struct Dollar { amount: f64 },\n}\nstruct Coin {}\n\
+            impl Priced for Currency {\n    fn cents(): f64 { 100 }\n    fn scaled(factor) { cents * factor }\n}\n\
+            total = fn<P: Priced>(item: P) { item.scaled(2) }\nstruct Wallet<P: Priced> {\n    money: P,\n}\n";
+        assert_eq!(type_of(&format!("{code}x = total(Euro(1))\n"), "x"), "f64");
+        assert_eq!(type_of(&format!("{code}x = Dollar(1).scaled(3)\n"), "x"), "f64");
+        assert_eq!(type_of(&format!("{code}w = Wallet(Dollar(1))\n"), "w"), "Wallet<Dollar>");
+        assert_eq!(check_err(&format!("{code}x = total(Coin)\n")), "type Coin does not satisfy bound 'P: Priced' of 'total'");
+        assert_eq!(check_err(&format!("{code}w = Wallet(1)\n")), "type i64 does not satisfy bound 'P: Priced' of 'Wallet'");
+        assert_eq!(check_err(&format!("{code}f = fn<P: Priced>(item: P) {{ item.scaled(\"a\") }}\n")), "argument 1 of 'scaled' should be f64, but got Text");
+        assert_eq!(check_err(&format!("{code}p: Priced = Euro(1)\n")), "interface 'Priced' can only be used as a generic bound");
+        assert_eq!(check_err(&format!("{code}p = Priced(1)\n")), "cannot create interface 'Priced', it can only be used as a generic bound");
+    }
+
+    #[test]
+    fn impl_matches_interface() {
+        let code = "interface Sized {\n    fn size(): f64\n}\nstruct Box {}\n";
+        assert!(check(&format!("{code}impl Sized for Box {{\n    fn size() {{ 1 }}\n}}\n")).is_ok());
+        assert_eq!(check_err(&format!("{code}impl Sized for Box {{}}\n")), "'Box' does not implement method 'size' of interface 'Sized'");
+        assert_eq!(check_err(&format!("{code}impl Sized for Box {{\n    fn size() {{ 1 }}\n    fn more() {{ 2 }}\n}}\n")),
+            "method 'more' of 'Box' is not part of interface 'Sized'");
+        assert_eq!(check_err(&format!("{code}impl Sized for Box {{\n    fn size() {{ \"big\" }}\n}}\n")),
+            "method 'size' of 'Box' should be fn(): f64 to implement 'Sized', but is fn(): Text");
+        assert_eq!(check_err(&format!("{code}impl Box for Box {{}}\n")), "'Box' is not an interface");
+        assert_eq!(check_err(&format!("{code}impl Sized for Box {{\n    fn size() {{ 1 }}\n}}\nimpl Sized for Box {{\n    fn size() {{ 2 }}\n}}\n")),
+            "'Sized' is implemented twice for 'Box'");
+    }
+
+    #[test]
+    fn enum_variants() {
+        let code = "enum Shape {\n    struct Circle { radius: f64 },\n    struct Square { side: f64 },\n}\n";
+        assert!(check(&format!("{code}s: Shape = Circle(1)\n")).is_ok());
+        assert_eq!(check_err(&format!("{code}s: Circle = Square(1)\n")), "value assigned to 's' should be Circle, but got Square");
+        assert_eq!(check_err(&format!("{code}s = Shape(1)\n")), "cannot create 'Shape' directly, use one of the variants");
+    }
+
+    #[test]
+    fn prelude_signatures() {
+        assert_eq!(type_of("x = op_mul(2, 3)\n", "x"), "i64");
+        assert_eq!(type_of("x = \"a\".len\n", "x"), "i64");
+        assert_eq!(type_of("x = 1.range(5)\n", "x"), "Iter<i64>");
+        assert_eq!(type_of("x = print\n", "x"), "fn(Any): Void");
+        assert_eq!(check_err("x = !1\n"), "operand of Not should be Bool, but got i64");
+        assert_eq!(check_err("1.assert\n"), "argument 1 of 'assert' should be Bool, but got i64");
+        assert_eq!(check_err("x = \"a\".sqrt(2)\n"), "Text has no field or method 'sqrt' that takes 1 arguments");
+    }
+
+    #[test]
+    fn iterator_pipeline_types() {
+        assert_eq!(type_of("x = 5.range.map { it > 2 }\n", "x"), "Iter<Bool>");
+        assert_eq!(type_of("x = \"abc\".filter { it == \"b\" }.take(1).collect\n", "x"), "List<Text>");
+        assert_eq!(type_of("x = 5.range.collect.iter.sum\n", "x"), "i64");
+        assert_eq!(type_of("f = fn(items: Iter<f64>) { for (x in items) { x } }\ny = f(3.range)\n", "y"), "f64");
+        assert_eq!(check_err("x = 5.range.filter { it + 1 }\n"), "result of function passed to 'filter' should be Bool, but got i64");
+        assert_eq!(check_err("x = 5.range.map(fn(a, b) { a })\n"), "function passed to 'map' should take 1 argument, but takes 2");
+        assert_eq!(check_err("x = \"ab\".sum\n"), "elements passed to 'sum' should be f64, but got Text");
+        assert_eq!(check_err("x = 1.map(print)\n"), "cannot iterate over i64");
+        assert_eq!(check_err("f = fn(items: List<f64, Text>) { 1 }\n"), "type 'List' takes 1 generic parameters, got 2");
+    }
+
+    #[test]
+    fn text_operations() {
+        assert_eq!(type_of("x = \"a\" + \"b\"\n", "x"), "Text");
+        assert_eq!(type_of("f = fn(a) { a + \"b\" }\nx = f(1)\n", "x"), "Text");
+        assert_eq!(type_of("x = \"a\" < \"b\"\n", "x"), "Bool");
+        assert_eq!(type_of("n = 1\nx = \"n is {n + 1}\"\n", "x"), "Text");
+        assert_eq!(type_of("x = \"a,b\".split(\",\")\n", "x"), "List<Text>");
+        assert_eq!(type_of("x = 3.range.join(\", \")\n", "x"), "Text");
+        assert_eq!(type_of("x = 1.5.to_text\n", "x"), "Text");
+        assert_eq!(check_err("x = \"a\" + 1\n"), "left side of Add should be f64, but got Text");
+        assert_eq!(check_err("x = \"{!1}\"\n"), "operand of Not should be Bool, but got i64");
+        assert_eq!(check_err("x = \"abc\".slice(1)\n"), "Text has no field or method 'slice' that takes 1 arguments");
+    }
+
+    #[test]
+    fn collection_types() {
+        assert_eq!(type_of("x = [1, 2.5]\n", "x"), "List<f64>");
+        assert_eq!(type_of("x = [].push(\"a\")\n", "x"), "List<Text>");
+        assert_eq!(type_of("x = {\"a\": [1]}\n", "x"), "Map<Text, List<i64>>");
+        assert_eq!(type_of("m = {1: \"a\"}\nx = m[1]\n", "x"), "Text");
+        assert_eq!(type_of("m = {1: \"a\"}\nx = m.insert(2, \"b\").keys\n", "x"), "Iter<i64>");
+        assert_eq!(type_of("m: Map<Text, f64> = {:}\nfor (k in m) {\n    x = k\n}\n", "x"), "Text");
+        assert_eq!(type_of("x = [1].len + {:}.len\n", "x"), "i64");
+        assert_eq!(check_err("x = [1][\"a\"]\n"), "index of list should be i64, but got Text");
+        assert_eq!(check_err("x = {\"a\": 1}[1]\n"), "key of map should be Text, but got i64");
+        assert_eq!(check_err("x = [1].push(\"a\")\n"), "argument 2 of 'push' should be i64, but got Text");
+        assert_eq!(check_err("x = 1.len\n"), "'len' cannot be used on i64");
+        assert_eq!(check_err("x = 1[0]\n"), "cannot index into i64");
+        assert_eq!(check_err("x: Map<Text> = {:}\n"), "type 'Map' takes 2 generic parameters, got 1");
+    }
+
+    #[test]
+    fn numeric_types() {
+        assert_eq!(type_of("x = 3\n", "x"), "i64");
+        assert_eq!(type_of("x = 3f64\n", "x"), "f64");
+        assert_eq!(type_of("x = 1 + 2.5\n", "x"), "f64");
+        assert_eq!(type_of("x = 7 / 2\n", "x"), "i64");
+        assert_eq!(type_of("x = if (1 < 2) { 1 } else { 1.5 }\n", "x"), "f64");
+        assert_eq!(type_of("x = 2.7.to_int\n", "x"), "i64");
+        assert_eq!(type_of("x = 2.to_float\n", "x"), "f64");
+        assert_eq!(type_of("half = fn(a: f64): f64 { a / 2 }\nx = half(3)\n", "x"), "f64");
+        assert_eq!(check_err("f = fn(a: i64): i64 { a }\nx = f(1.5)\n"), "argument 1 of 'f' should be i64, but got f64");
+    }
+
+    #[test]
+    fn compound_assignment_types() {
+        assert!(check("mut x = 1\nx %= 2\n").is_ok());
+        assert_eq!(check_err("mut x = \"a\"\nx += 1\n"), "left side of Add should be f64, but got Text");
+    }
+
+    #[test]
+    fn match_exhaustive() {
+        let code = "struct Apple {}\nstruct Mushroom {}\nenum Option<T> {\n    struct Some<T> { value: T },\n    struct None {},\n}\n\
+            enum Produce {\n    Apple,\n    struct Banana {},\n    enum Vegetable {\n        struct Carrot {},\n        struct Leek {},\n    },\n    Option<Mushroom>,\n}\np: Produce = Banana\n";
+        assert!(check(&format!("{code}match (p) {{\n    Apple {{ 1 }}\n    Banana {{ 2 }}\n    Vegetable {{ 3 }}\n    Option {{ 4 }}\n}}\n")).is_ok());
+        assert!(check(&format!("{code}match (p) {{\n    Carrot {{ 1 }}\n    else {{ 2 }}\n}}\n")).is_ok());
+        assert_eq!(
+            check_err(&format!("{code}match (p) {{\n    Banana {{ 1 }}\n    Leek {{ 2 }}\n    Some {{ 3 }}\n}}\n")),
+            "match on Produce does not handle 'Apple', 'Carrot', 'None', add those or an 'else'",
+        );
+        assert_eq!(check_err(&format!("{code}match (p) {{\n    Banana {{ 1 }}\n}}\n")),
+            "match on Produce does not handle 'Apple', 'Vegetable', 'Option', add those or an 'else'");
+        assert_eq!(check_err(&format!("{code}match (Apple) {{\n    Banana {{ 1 }}\n}}\n")), "'Banana' is not a variant of Apple");
+        assert_eq!(check_err("struct A {}\nmatch (1) {\n    A { 1 }\n}\n"), "cannot match on i64");
+    }
+
+    #[test]
+    fn match_binding_types() {
+        let code = "enum Option<T> {\n    struct Some<T> { value: T },\n    struct None {},\n}\nopt: Option<f64> = Some(1)\n";
+        assert_eq!(type_of(&format!("{code}x = match (opt) {{\n    Some s {{ s.value }}\n    None {{ 0 }}\n}}\n"), "x"), "f64");
+        assert_eq!(type_of(&format!("{code}x = match (opt) {{\n    Some s {{ s }}\n    else {{ None }}\n}}\n"), "s"), "Some<f64>");
+    }
+
+    #[test]
+    fn method_types() {
+        let code = "struct Point<N: Number> {\n    x: N,\n    fn scale(factor: N): N { x * factor }\n    fn label() { \"point\" }\n}\n";
+        assert_eq!(type_of(&format!("{code}s = Point(1).scale(2)\n"), "s"), "i64");
+        assert_eq!(type_of(&format!("{code}l = Point(1).label\n"), "l"), "Text");
+        assert_eq!(check_err(&format!("{code}s = Point(1).scale(\"a\")\n")), "argument 1 of 'scale' should be i64, but got Text");
+        assert_eq!(check_err(&format!("{code}s = Point(1).scale\n")), "'scale' expects 1 arguments, got 0");
+        assert_eq!(check_err("struct P {\n    fn f(): Text { 1 }\n}\n"), "result of 'P.f' should be Text, but got i64");
+    }
+
+    #[test]
+    fn set_field_types() {
+        let code = "struct Point {\n    x: f64,\n}\nmut p = Point(1)\n";
+        assert!(check(&format!("{code}p.x = 2\n")).is_ok());
+        assert_eq!(check_err(&format!("{code}p.x = \"a\"\n")), "value assigned to field 'x' should be f64, but got Text");
+        assert_eq!(check_err(&format!("{code}p.x.y = 1\n")), "f64 has no field 'y'");
+    }
+
+    #[test]
+    fn optional_types() {
+        assert_eq!(type_of("x = Some(1)\n", "x"), "Some<i64>");
+        assert_eq!(type_of("x = if (1 < 2) { Some(1) } else { None }\n", "x"), "Option<i64>");
+        assert_eq!(type_of("x = (1 < 2).then { \"a\" }.alternatively(2 < 3) { \"b\" }\n", "x"), "Option<Text>");
+        let code = "half = fn(n: i64): Result<i64, Text> {\n    if (n % 2 == 0) {\n        return Ok(n / 2)\n    }\n    Err(\"odd\")\n}\n\
+            quarter = fn(n: i64) {\n    h = half(n)?\n    Ok(half(h)?)\n}\n";
+        assert_eq!(type_of(&format!("{code}x = quarter(8)\n"), "x"), "Result<i64, Text>");
+        assert_eq!(type_of(&format!("{code}x = half(8).otherwise {{ it.len }}\n"), "x"), "i64");
+        assert_eq!(type_of(&format!("{code}x = half(8).otherwise {{ 0 }}\n"), "x"), "i64");
+        assert_eq!(check_err(&format!("{code}x = half(8).otherwise {{ \"none\" }}\n")),
+            "result of function passed to 'otherwise' should be i64, but got Text");
+        assert_eq!(check_err(&format!("{code}f = fn(): i64 {{\n    half(2)?\n}}\n")), "result of 'f' should be i64, but got Result<Any, Text>");
+        assert_eq!(check_err("x = 1?\n"), "'?' can only be used on Option or Result, got i64");
+        assert_eq!(check_err("x = Ok(1).alternatively(1 < 2) { 2 }\n"), "'alternatively' cannot be used on Ok<i64>");
+    }
+
+    #[test]
+    fn lambda_return_types() {
+        let code = "find = fn(items: List<i64>) {\n    items.iter.for_each {\n        if (it > 2) {\n            return 1.5\n        }\n    }\n    0\n}\n";
+        assert_eq!(type_of(&format!("{code}x = find([1])\n"), "x"), "f64");
+        assert_eq!(check_err("f = fn(): Text {\n    [1].iter.for_each {\n        it.print\n        return 1\n    }\n    \"a\"\n}\n"), "result of 'f' should be Text, but got i64");
+    }
+}
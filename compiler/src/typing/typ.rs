@@ -0,0 +1,109 @@
+use tel_common::Identifier;
+use tel_hir::Variable;
+use tel_hir::Variables;
+
+/// Type of an expression or variable, as inferred by the type checker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    /// Not annotated and could not be inferred, so this is compatible with anything.
+    Unknown,
+    Void,
+    Bool,
+    /// Integers of all sizes, which are represented as 64 bits for now.
+    Int,
+    Float,
+    Text,
+    /// Lazy sequence of values of the element type.
+    Iter(Box<Type>),
+    List(Box<Type>),
+    /// Keys and values, where the keys are unique.
+    Map(Box<Type>, Box<Type>),
+    Function(Box<[Type]>, Box<Type>),
+    /// Instance of a struct or enum, with the types of the generic parameters.
+    Named(Variable, Box<[Type]>),
+    /// The struct or enum itself, which for structs can be called as a constructor.
+    Declaration(Variable),
+    /// Generic parameter, inside the declaration that has it.
+    Param(Identifier),
+}
+
+impl Type {
+    /// Type names that are always available.
+    pub fn builtin(iden: &str) -> Option<Type> {
+        Some(match iden {
+            //TODO @mark: separate integer sizes
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => Type::Int,
+            "f32" | "f64" => Type::Float,
+            "Bool" => Type::Bool,
+            "Text" => Type::Text,
+            "Void" => Type::Void,
+            "Iter" => Type::Iter(Box::new(Type::Unknown)),
+            "List" => Type::List(Box::new(Type::Unknown)),
+            "Map" => Type::Map(Box::new(Type::Unknown), Box::new(Type::Unknown)),
+            "Any" => Type::Unknown,
+            _ => return None,
+        })
+    }
+
+    /// The builtin type with the given generic parameters, if it is a collection like `List` or `Map`
+    /// and the number of parameters is right.
+    pub fn collection(iden: &str, generics: Vec<Type>) -> Option<Type> {
+        let mut generics = generics.into_iter().map(Box::new);
+        let typ = match iden {
+            "Iter" => Type::Iter(generics.next()?),
+            "List" => Type::List(generics.next()?),
+            "Map" => Type::Map(generics.next()?, generics.next()?),
+            _ => return None,
+        };
+        generics.next().is_none().then_some(typ)
+    }
+
+    /// Number of generic parameters of builtin collections, which is 0 for other types.
+    pub fn generic_count(&self) -> usize {
+        match self {
+            Type::Iter(_) | Type::List(_) => 1,
+            Type::Map(..) => 2,
+            _ => 0,
+        }
+    }
+
+    /// Bounds for generics that are built in, and whether the type satisfies them.
+    pub fn satisfies_builtin_bound(&self, bound: &str) -> Option<bool> {
+        Some(match bound {
+            "Number" => matches!(self, Type::Int | Type::Float | Type::Unknown),
+            "Integer" => matches!(self, Type::Int | Type::Unknown),
+            "Any" => true,
+            _ => return None,
+        })
+    }
+
+    pub fn show(&self, variables: &Variables) -> String {
+        match self {
+            Type::Unknown => "Any".to_owned(),
+            Type::Void => "Void".to_owned(),
+            Type::Bool => "Bool".to_owned(),
+            Type::Int => "i64".to_owned(),
+            Type::Float => "f64".to_owned(),
+            Type::Text => "Text".to_owned(),
+            Type::Iter(element) => format!("Iter<{}>", element.show(variables)),
+            Type::List(element) => format!("List<{}>", element.show(variables)),
+            Type::Map(key, value) => format!("Map<{}, {}>", key.show(variables), value.show(variables)),
+            Type::Function(params, result) => format!(
+                "fn({}): {}",
+                show_all(params, variables),
+                result.show(variables),
+            ),
+            Type::Named(var, generics) if generics.is_empty() => var.iden(variables).to_string(),
+            Type::Named(var, generics) => format!("{}<{}>", var.iden(variables), show_all(generics, variables)),
+            Type::Declaration(var) => format!("type {}", var.iden(variables)),
+            Type::Param(iden) => iden.to_string(),
+        }
+    }
+}
+
+fn show_all(types: &[Type], variables: &Variables) -> String {
+    types.iter()
+        .map(|typ| typ.show(variables))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,15 @@
+//! Lossless concrete syntax tree, which keeps whitespace and comments next to the [tel_ast::Ast].
+//! * Concatenating the tokens gives back the source exactly, even if it has syntax errors.
+//! * Nodes are built from the ast spans, so the ast must be parsed from the same source.
+//! * Comments and blank lines are attached to statements as leading or trailing trivia.
+//! * Useful for refactoring tools, and for formatting without losing comments.
+
+pub use self::token::lex;
+pub use self::token::Token;
+pub use self::token::TokenKind;
+pub use self::tree::Cst;
+pub use self::tree::Node;
+pub use self::tree::NodeKind;
+
+mod token;
+mod tree;
@@ -0,0 +1,148 @@
+use tel_common::Span;
+
+/// Keywords of the grammar, which are lexed as [TokenKind::Keyword] instead of identifiers.
+const KEYWORDS: [&str; 22] = [
+    "and", "elif", "else", "enum", "fn", "for", "if", "impl", "import", "in", "interface", "local", "match", "mut", "or", "outer",
+    "pub", "return", "self", "struct", "while", "xor",
+];
+
+/// Operators of two characters, which are one token.
+const DOUBLE_PUNCT: [&str; 9] = ["==", "!=", "<=", ">=", "+=", "-=", "*=", "/=", "%="];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces and tabs.
+    Whitespace,
+    /// A single line break, which ends a statement unless it is after `...` or inside brackets.
+    Newline,
+    /// From `#` to the end of the line, excluding the line break.
+    Comment,
+    /// The `...` that continues a statement on the next line.
+    Continuation,
+    Ident,
+    Keyword,
+    Number,
+    /// Quoted text, including the quotes.
+    Text,
+    /// Operators, brackets, `.`, `,`, `:` and `;`.
+    Punct,
+    /// Characters that are not valid Tel, kept so that no source is lost.
+    Unknown,
+}
+
+impl TokenKind {
+    /// Trivia does not affect the ast, apart from newlines ending statements.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Newline | TokenKind::Comment | TokenKind::Continuation)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn text(self, code: &str) -> &str {
+        &code[self.span.start as usize..self.span.end as usize]
+    }
+}
+
+/// Split all of the source into tokens, including whitespace and comments, so that
+/// concatenating the tokens gives back the source exactly. This never fails.
+pub fn lex(code: &str) -> Vec<Token> {
+    let bytes = code.as_bytes();
+    let mut tokens = Vec::with_capacity(code.len() / 3);
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &code[pos..];
+        let (kind, len) = match bytes[pos] {
+            b' ' | b'\t' => (TokenKind::Whitespace, count_while(rest, |ch| ch == ' ' || ch == '\t')),
+            b'\r' if rest.starts_with("\r\n") => (TokenKind::Newline, 2),
+            b'\n' | b'\r' => (TokenKind::Newline, 1),
+            b'#' => (TokenKind::Comment, rest.find(['\n', '\r']).unwrap_or(rest.len())),
+            b'.' if rest.starts_with("...") => (TokenKind::Continuation, 3),
+            quote @ (b'"' | b'\'') => match text_len(rest, quote) {
+                Some(len) => (TokenKind::Text, len),
+                None => (TokenKind::Unknown, 1),
+            },
+            b'0'..=b'9' => (TokenKind::Number, number_len(rest)),
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                let len = count_while(rest, |ch| ch.is_ascii_alphanumeric() || ch == '_');
+                let kind = if KEYWORDS.contains(&&rest[..len]) { TokenKind::Keyword } else { TokenKind::Ident };
+                (kind, len)
+            }
+            _ if DOUBLE_PUNCT.iter().any(|punct| rest.starts_with(punct)) => (TokenKind::Punct, 2),
+            b'(' | b')' | b'{' | b'}' | b'[' | b']' | b'<' | b'>' | b',' | b':' | b';' | b'.'
+                | b'=' | b'+' | b'-' | b'*' | b'/' | b'%' | b'!' | b'?' => (TokenKind::Punct, 1),
+            _ => (TokenKind::Unknown, rest.chars().next().map_or(1, char::len_utf8)),
+        };
+        tokens.push(Token { kind, span: Span::new(pos, pos + len) });
+        pos += len;
+    }
+    tokens
+}
+
+/// Length of the quoted text at the start, where a backslash escapes the character after it.
+fn text_len(text: &str, quote: u8) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut pos = 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            byte if byte == quote => return Some(pos + 1),
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+fn count_while(text: &str, pred: impl Fn(char) -> bool) -> usize {
+    text.find(|ch| !pred(ch)).unwrap_or(text.len())
+}
+
+/// Digits, optionally followed by a period and more digits, and then optionally a type suffix.
+fn number_len(text: &str) -> usize {
+    let int_len = count_while(text, |ch| ch.is_ascii_digit());
+    let frac = &text[int_len..];
+    let len = if frac.starts_with('.') && frac[1..].starts_with(|ch: char| ch.is_ascii_digit()) {
+        int_len + 1 + count_while(&frac[1..], |ch| ch.is_ascii_digit())
+    } else {
+        int_len
+    };
+    if text[len..].starts_with("i64") || text[len..].starts_with("f64") {
+        len + 3
+    } else {
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str) -> Vec<(TokenKind, &str)> {
+        lex(code).into_iter().map(|token| (token.kind, token.text(code))).collect()
+    }
+
+    #[test]
+    fn all_kinds() {
+        use TokenKind::*;
+        assert_eq!(kinds("x += 1.5.abs # hi\r\n'a#b' ...\niffy é"), vec![
+            (Ident, "x"), (Whitespace, " "), (Punct, "+="), (Whitespace, " "), (Number, "1.5"), (Punct, "."),
+            (Ident, "abs"), (Whitespace, " "), (Comment, "# hi"), (Newline, "\r\n"), (Text, "'a#b'"),
+            (Whitespace, " "), (Continuation, "..."), (Newline, "\n"), (Ident, "iffy"), (Whitespace, " "), (Unknown, "é"),
+        ]);
+    }
+
+    #[test]
+    fn unterminated_text() {
+        assert_eq!(kinds("\"ab"), vec![(TokenKind::Unknown, "\""), (TokenKind::Ident, "ab")]);
+    }
+
+    #[test]
+    fn escaped_quote() {
+        assert_eq!(kinds(r#""a\"{b}\\" x"#), vec![(TokenKind::Text, r#""a\"{b}\\""#), (TokenKind::Whitespace, " "), (TokenKind::Ident, "x")]);
+    }
+}
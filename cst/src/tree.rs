@@ -0,0 +1,413 @@
+use std::fmt;
+use std::ops::Range;
+
+use tel_ast::AssignmentDest;
+use tel_ast::Ast;
+use tel_ast::Block;
+use tel_ast::Closure;
+use tel_ast::Enum;
+use tel_ast::EnumVariant;
+use tel_ast::Expr;
+use tel_ast::ExprKind;
+use tel_ast::Impl;
+use tel_ast::Import;
+use tel_ast::Interface;
+use tel_ast::Invoke;
+use tel_ast::Struct;
+use tel_ast::Type;
+use tel_common::Span;
+
+use crate::token::lex;
+use crate::token::Token;
+use crate::token::TokenKind;
+
+/// Lossless syntax tree: every byte of the source is in exactly one token, and the nodes
+/// group tokens according to the ast that was parsed from the same source.
+#[derive(Debug)]
+pub struct Cst {
+    code: String,
+    tokens: Vec<Token>,
+    root: Node,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    File,
+    Import,
+    Assignments,
+    AssignmentDest,
+    Return,
+    SetField,
+    Struct,
+    Enum,
+    Interface,
+    Impl,
+    Type,
+    Num,
+    Text,
+    List,
+    Map,
+    Index,
+    Propagate,
+    BinOp,
+    UnaryOp,
+    Invoke,
+    Dot,
+    Closure,
+    If,
+    While,
+    ForEach,
+    Match,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// Span of the ast node that this node was created from.
+    pub span: Span,
+    /// Tokens of the node itself, as indices into [Cst::tokens].
+    pub tokens: Range<usize>,
+    /// For statements, the whitespace and comments on the lines before it. Empty for other nodes.
+    pub leading: Range<usize>,
+    /// For statements, the whitespace and comments after it on the same line, including the line break.
+    pub trailing: Range<usize>,
+    pub children: Vec<Node>,
+}
+
+impl Cst {
+    /// Build the tree for an ast that was parsed from `code`, so that the spans match.
+    pub fn new(code: &str, ast: &Ast) -> Self {
+        let tokens = lex(code);
+        let builder = Builder { tokens: &tokens };
+        let root = Node {
+            kind: NodeKind::File,
+            span: Span::new(0, code.len()),
+            tokens: 0..tokens.len(),
+            leading: 0..0,
+            trailing: tokens.len()..tokens.len(),
+            children: ast.imports.iter().map(|import| builder.import(import))
+                .chain(ast.blocks.iter().map(|block| builder.statement(block)))
+                .collect(),
+        };
+        Cst { code: code.to_owned(), tokens, root }
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Source code of a range of tokens.
+    pub fn text(&self, tokens: Range<usize>) -> &str {
+        if tokens.is_empty() {
+            return "";
+        }
+        let start = self.tokens[tokens.start].span.start as usize;
+        let end = self.tokens[tokens.end - 1].span.end as usize;
+        &self.code[start..end]
+    }
+
+    /// The comments in a range of tokens, like the leading trivia of a statement.
+    pub fn comments(&self, tokens: Range<usize>) -> impl Iterator<Item = &str> {
+        self.tokens[tokens].iter()
+            .filter(|token| token.kind == TokenKind::Comment)
+            .map(|token| token.text(&self.code))
+    }
+
+    /// The node for the ast node with the given span. If several nodes have the same span,
+    /// like a statement and its expression, this is the outermost one.
+    pub fn node_for(&self, span: Span) -> Option<&Node> {
+        fn find(node: &Node, span: Span) -> Option<&Node> {
+            if node.span == span {
+                return Some(node);
+            }
+            if node.span.start > span.start || node.span.end < span.end {
+                return None;
+            }
+            node.children.iter().find_map(|child| find(child, span))
+        }
+        find(&self.root, span)
+    }
+}
+
+impl fmt::Display for Cst {
+    /// The original source code.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.code)
+    }
+}
+
+struct Builder<'a> {
+    tokens: &'a [Token],
+}
+
+impl Builder<'_> {
+    fn node(&self, kind: NodeKind, span: Span, children: Vec<Node>) -> Node {
+        let start = self.tokens.partition_point(|token| token.span.start < span.start);
+        let end = self.tokens.partition_point(|token| token.span.end <= span.end).max(start);
+        Node { kind, span, tokens: start..end, leading: start..start, trailing: end..end, children }
+    }
+
+    fn import(&self, import: &Import) -> Node {
+        self.with_trivia(self.node(NodeKind::Import, import.span, vec![]))
+    }
+
+    /// Node for a statement, with the trivia around it.
+    fn statement(&self, block: &Block) -> Node {
+        let node = match block {
+            Block::Assigns(assign) => {
+                let mut children = assign.dest.iter().map(|dest| self.assign_dest(dest)).collect::<Vec<_>>();
+                children.push(self.expr(&assign.value));
+                self.node(NodeKind::Assignments, assign.span, children)
+            }
+            Block::Expression(expr) => self.expr(expr),
+            Block::Return(expr) => {
+                let mut node = self.node(NodeKind::Return, expr.span, vec![self.expr(expr)]);
+                // the ast span is that of the value, so include the keyword
+                if let Some(keyword) = self.previous_significant(node.tokens.start) {
+                    node.tokens.start = keyword;
+                    node.span = Span { start: self.tokens[keyword].span.start, ..node.span };
+                }
+                node
+            }
+            Block::SetField(set) => {
+                self.node(NodeKind::SetField, set.span, vec![self.expr(&set.target), self.expr(&set.value)])
+            }
+            Block::Struct(strct) => self.strct(strct),
+            Block::Enum(enm) => self.enm(enm),
+            Block::Interface(interface) => self.interface(interface),
+            Block::Impl(imp) => self.imp(imp),
+        };
+        self.with_trivia(node)
+    }
+
+    /// Add the whitespace and comments before the node, and those after it on the same line.
+    fn with_trivia(&self, mut node: Node) -> Node {
+        let start = node.tokens.start;
+        let mut lead = start;
+        while lead > 0 && self.tokens[lead - 1].kind.is_trivia() {
+            lead -= 1;
+        }
+        // trivia on the same line as the previous code is trailing trivia of that code
+        if lead > 0 {
+            lead = (lead..start)
+                .find(|&ix| self.tokens[ix].kind == TokenKind::Newline)
+                .map_or(start, |newline| newline + 1);
+        }
+        let end = node.tokens.end;
+        let mut trail = end;
+        while trail < self.tokens.len() && self.tokens[trail].kind.is_trivia() {
+            trail += 1;
+            if self.tokens[trail - 1].kind == TokenKind::Newline {
+                break;
+            }
+        }
+        node.leading = lead..start;
+        node.trailing = end..trail;
+        node
+    }
+
+    fn previous_significant(&self, before: usize) -> Option<usize> {
+        (0..before).rev().find(|&ix| !self.tokens[ix].kind.is_trivia())
+    }
+
+    fn statements(&self, blocks: &[Block]) -> Vec<Node> {
+        blocks.iter().map(|block| self.statement(block)).collect()
+    }
+
+    fn expr(&self, expr: &Expr) -> Node {
+        let (kind, children) = match &expr.kind {
+            ExprKind::Int(_) | ExprKind::Float(_) => (NodeKind::Num, vec![]),
+            // the code inside is part of the same token as the text
+            ExprKind::Text(_) | ExprKind::Interpolate(_) => (NodeKind::Text, vec![]),
+            ExprKind::List(items) => (NodeKind::List, items.iter().map(|item| self.expr(item)).collect()),
+            ExprKind::Map(entries) => (NodeKind::Map, entries.iter()
+                .flat_map(|(key, value)| [self.expr(key), self.expr(value)])
+                .collect()),
+            ExprKind::Index(base, index) => (NodeKind::Index, vec![self.expr(base), self.expr(index)]),
+            ExprKind::Propagate(inner) => (NodeKind::Propagate, vec![self.expr(inner)]),
+            ExprKind::BinOp(_, left, right) => (NodeKind::BinOp, vec![self.expr(left), self.expr(right)]),
+            ExprKind::UnaryOp(_, inner) => (NodeKind::UnaryOp, vec![self.expr(inner)]),
+            ExprKind::Invoke(invoke) => return self.invoke(invoke),
+            ExprKind::Dot(base, invoke) => (NodeKind::Dot, vec![self.expr(base), self.invoke(invoke)]),
+            ExprKind::Closure(closure) => return self.closure(closure),
+            ExprKind::If(branches, otherwise) => {
+                let mut children = Vec::new();
+                for (cond, body) in branches {
+                    children.push(self.expr(cond));
+                    children.extend(self.statements(body));
+                }
+                if let Some(otherwise) = otherwise {
+                    children.extend(self.statements(otherwise));
+                }
+                (NodeKind::If, children)
+            }
+            ExprKind::While(cond, body) => {
+                let mut children = vec![self.expr(cond)];
+                children.extend(self.statements(body));
+                (NodeKind::While, children)
+            }
+            ExprKind::ForEach(dest, iter, body) => {
+                let mut children = vec![self.assign_dest(dest), self.expr(iter)];
+                children.extend(self.statements(body));
+                (NodeKind::ForEach, children)
+            }
+            ExprKind::Match(value, arms, otherwise) => {
+                let mut children = vec![self.expr(value)];
+                for arm in arms {
+                    children.extend(self.statements(&arm.body));
+                }
+                if let Some(otherwise) = otherwise {
+                    children.extend(self.statements(otherwise));
+                }
+                (NodeKind::Match, children)
+            }
+        };
+        self.node(kind, expr.span, children)
+    }
+
+    fn invoke(&self, invoke: &Invoke) -> Node {
+        let children = invoke.args.iter().map(|arg| self.expr(arg)).collect();
+        self.node(NodeKind::Invoke, invoke.span, children)
+    }
+
+    fn closure(&self, closure: &Closure) -> Node {
+        let mut children = closure.generics.iter().chain(closure.params.iter())
+            .map(|dest| self.assign_dest(dest))
+            .collect::<Vec<_>>();
+        children.extend(closure.ret.iter().map(|ret| self.typ(ret)));
+        children.extend(self.statements(&closure.blocks));
+        self.node(NodeKind::Closure, closure.span, children)
+    }
+
+    fn assign_dest(&self, dest: &AssignmentDest) -> Node {
+        let children = dest.typ.iter().map(|typ| self.typ(typ)).collect();
+        self.node(NodeKind::AssignmentDest, dest.span, children)
+    }
+
+    fn typ(&self, typ: &Type) -> Node {
+        let children = typ.generics.iter().map(|generic| self.typ(generic)).collect();
+        self.node(NodeKind::Type, typ.span, children)
+    }
+
+    fn strct(&self, strct: &Struct) -> Node {
+        let mut children = strct.generics.iter().map(|generic| self.assign_dest(generic)).collect::<Vec<_>>();
+        children.extend(strct.fields.iter().map(|(_, typ)| self.typ(typ)));
+        children.extend(strct.methods.iter().map(|method| self.closure(&method.closure)));
+        self.node(NodeKind::Struct, strct.span, children)
+    }
+
+    fn enm(&self, enm: &Enum) -> Node {
+        let mut children = enm.generics.iter().map(|generic| self.assign_dest(generic)).collect::<Vec<_>>();
+        children.extend(enm.variants.iter().map(|variant| match variant {
+            EnumVariant::Struct(strct) => self.strct(strct),
+            EnumVariant::Enum(nested) => self.enm(nested),
+            EnumVariant::Existing(typ) => self.typ(typ),
+        }));
+        self.node(NodeKind::Enum, enm.span, children)
+    }
+
+    fn interface(&self, interface: &Interface) -> Node {
+        let children = interface.methods.iter()
+            .flat_map(|method| method.params.iter().map(|param| self.assign_dest(param))
+                .chain(method.ret.iter().map(|ret| self.typ(ret))))
+            .collect();
+        self.node(NodeKind::Interface, interface.span, children)
+    }
+
+    fn imp(&self, imp: &Impl) -> Node {
+        let mut children = vec![self.typ(&imp.interface), self.typ(&imp.target)];
+        children.extend(imp.methods.iter().map(|method| self.closure(&method.closure)));
+        self.node(NodeKind::Impl, imp.span, children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tel_parser::str_to_ast;
+
+    use super::*;
+
+    fn cst(code: &str) -> (Ast, Cst) {
+        let ast = str_to_ast(PathBuf::from("test.tel"), code.to_owned()).unwrap();
+        let cst = Cst::new(code, &ast);
+        (ast, cst)
+    }
+
+    #[test]
+    fn lossless_examples() {
+        for entry in fs::read_dir("../compiler/examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("tel".as_ref()) {
+                continue;
+            }
+            let code = fs::read_to_string(&path).unwrap();
+            let tokens = lex(&code);
+            assert_eq!(tokens.iter().map(|token| token.text(&code)).collect::<String>(), code);
+            // some examples use syntax that is not supported yet
+            let Ok(ast) = str_to_ast(path.clone(), code.clone()) else {
+                continue;
+            };
+            let cst = Cst::new(&code, &ast);
+            assert_eq!(cst.to_string(), code);
+            assert_eq!(cst.text(cst.root().tokens.clone()), code, "tokens do not cover {}", path.display());
+            for statement in &cst.root().children {
+                assert!(statement.leading.end <= statement.tokens.start && statement.tokens.end <= statement.trailing.start);
+            }
+        }
+    }
+
+    #[test]
+    fn statement_trivia() {
+        let code = "# about x\nx = 1\n\n  # about y\n  return x ...\n    + 1\n";
+        let (_, cst) = cst(code);
+        let [x, y] = &cst.root().children[..] else { panic!() };
+        assert_eq!(x.kind, NodeKind::Assignments);
+        assert_eq!(cst.comments(x.leading.clone()).collect::<Vec<_>>(), vec!["# about x"]);
+        assert_eq!(cst.text(x.trailing.clone()), "\n");
+        assert_eq!(y.kind, NodeKind::Return);
+        assert_eq!(cst.text(y.leading.clone()), "\n  # about y\n  ");
+        assert_eq!(cst.text(y.tokens.clone()), "return x ...\n    + 1");
+    }
+
+    #[test]
+    fn imports_and_public() {
+        let code = "import util { a, B }\n# exported\npub x: i64 = a\n";
+        let (_, cst) = cst(code);
+        let [import, x] = &cst.root().children[..] else { panic!() };
+        assert_eq!(import.kind, NodeKind::Import);
+        assert_eq!(cst.text(import.tokens.clone()), "import util { a, B }");
+        assert_eq!(x.kind, NodeKind::Assignments);
+        assert_eq!(cst.comments(x.leading.clone()).collect::<Vec<_>>(), vec!["# exported"]);
+        assert_eq!(cst.text(x.tokens.clone()), "pub x: i64 = a");
+    }
+
+    #[test]
+    fn nested_statements() {
+        let code = "f {\n    # inside\n    a = 1\n}\n";
+        let (_, cst) = cst(code);
+        let closure = &cst.root().children[0].children[0];
+        assert_eq!(closure.kind, NodeKind::Closure);
+        let assign = &closure.children[0];
+        assert_eq!(cst.text(assign.leading.clone()), "    # inside\n    ");
+        assert_eq!(cst.text(assign.tokens.clone()), "a = 1");
+    }
+
+    #[test]
+    fn ast_to_cst_mapping() {
+        let code = "total = items.map { it * 2 }.sum\n";
+        let (ast, cst) = cst(code);
+        let Block::Assigns(assign) = &ast.blocks[0] else { panic!() };
+        let ExprKind::Dot(map, _) = &assign.value.kind else { panic!() };
+        let node = cst.node_for(map.span).unwrap();
+        assert_eq!(node.kind, NodeKind::Dot);
+        assert_eq!(cst.text(node.tokens.clone()), "items.map { it * 2 }");
+        assert!(cst.node_for(Span::new(1, 3)).is_none());
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Serialize;
+use tel_common::Identifier;
+
+use crate::Expr;
+use crate::Variable;
+
+/// Sequence of statements, which evaluates to the value of the last one.
+#[derive(Debug, Serialize)]
+pub struct Block {
+    pub stmts: Box<[Stmt]>,
+}
+
+#[derive(Debug, Serialize)]
+pub enum Stmt {
+    Assign(Assignment),
+    Expression(Expr),
+    Return(Expr),
+    SetField(SetField),
+}
+
+#[derive(Debug, Serialize)]
+pub struct Assignment {
+    pub var: Variable,
+    pub value: Expr,
+}
+
+/// Assignment to a field like `a.b.c = 1`, which stores a changed copy of the value in `a`.
+#[derive(Debug, Serialize)]
+pub struct SetField {
+    pub var: Variable,
+    /// Path from the variable to the field, which is `b.c` in the example.
+    pub fields: Box<[Identifier]>,
+    pub value: Expr,
+}
@@ -0,0 +1,254 @@
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tel_ast::BinOpCode;
+use tel_ast::UnaryOpCode;
+use tel_common::Identifier;
+use tel_common::Span;
+
+use crate::Ix;
+use crate::Variable;
+use crate::VariableData;
+
+/// Functions that are available in every file, including the functions that operators resolve to.
+/// These are referred to by [Variable]s with negative indices, which are shared by all files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Builtin {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Modulo,
+    Eq,
+    Neq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+    Xor,
+    Neg,
+    Not,
+    Print,
+    PrintNoBreak,
+    Assert,
+    True,
+    False,
+    Range,
+    RangeTo,
+    Sqrt,
+    Upper,
+    Lower,
+    Trim,
+    Slice,
+    Split,
+    ToText,
+    ToInt,
+    ToFloat,
+    Then,
+    Alternatively,
+    Otherwise,
+    Len,
+    Contains,
+    Push,
+    Insert,
+    Remove,
+    Keys,
+    Values,
+    Iter,
+    Map,
+    Filter,
+    Take,
+    Sum,
+    ForEach,
+    Collect,
+    Join,
+}
+
+/// Name and signature of each builtin, in the order of [Builtin]. Types are written like annotations,
+/// and `Any` accepts every type. The first of several builtins with the same name is the one
+/// found by name, the others can only be used as methods with a different number of arguments.
+/// The collection and iterator builtins take any text, list or map (or iterable), their exact
+/// types are inferred where they are used.
+/// Integers can be passed as `f64`, and arithmetic on two integers gives an integer.
+/// The builtins for `Option` and `Result` use `Any`, because those types are in the prelude.
+const BUILTINS: [(Builtin, &str, &[&str], &str); 50] = [
+    (Builtin::Add, "op_add", &["f64", "f64"], "f64"),
+    (Builtin::Sub, "op_sub", &["f64", "f64"], "f64"),
+    (Builtin::Mul, "op_mul", &["f64", "f64"], "f64"),
+    (Builtin::Div, "op_div", &["f64", "f64"], "f64"),
+    (Builtin::Modulo, "op_mod", &["f64", "f64"], "f64"),
+    (Builtin::Eq, "op_eq", &["Any", "Any"], "Bool"),
+    (Builtin::Neq, "op_neq", &["Any", "Any"], "Bool"),
+    (Builtin::Lt, "op_lt", &["f64", "f64"], "Bool"),
+    (Builtin::Gt, "op_gt", &["f64", "f64"], "Bool"),
+    (Builtin::Le, "op_le", &["f64", "f64"], "Bool"),
+    (Builtin::Ge, "op_ge", &["f64", "f64"], "Bool"),
+    (Builtin::And, "op_and", &["Bool", "Bool"], "Bool"),
+    (Builtin::Or, "op_or", &["Bool", "Bool"], "Bool"),
+    (Builtin::Xor, "op_xor", &["Bool", "Bool"], "Bool"),
+    (Builtin::Neg, "op_neg", &["f64"], "f64"),
+    (Builtin::Not, "op_not", &["Bool"], "Bool"),
+    (Builtin::Print, "print", &["Any"], "Void"),
+    (Builtin::PrintNoBreak, "print_no_break", &["Any"], "Void"),
+    (Builtin::Assert, "assert", &["Bool"], "Void"),
+    (Builtin::True, "true", &[], "Bool"),
+    (Builtin::False, "false", &[], "Bool"),
+    (Builtin::Range, "range", &["i64", "i64"], "Iter<i64>"),
+    (Builtin::RangeTo, "range", &["i64"], "Iter<i64>"),
+    (Builtin::Sqrt, "sqrt", &["f64"], "f64"),
+    (Builtin::Upper, "upper", &["Text"], "Text"),
+    (Builtin::Lower, "lower", &["Text"], "Text"),
+    (Builtin::Trim, "trim", &["Text"], "Text"),
+    (Builtin::Slice, "slice", &["Text", "i64", "i64"], "Text"),
+    (Builtin::Split, "split", &["Text", "Text"], "List<Text>"),
+    (Builtin::ToText, "to_text", &["Any"], "Text"),
+    (Builtin::ToInt, "to_int", &["f64"], "i64"),
+    (Builtin::ToFloat, "to_float", &["f64"], "f64"),
+    (Builtin::Then, "then", &["Bool", "Any"], "Any"),
+    (Builtin::Alternatively, "alternatively", &["Any", "Bool", "Any"], "Any"),
+    (Builtin::Otherwise, "otherwise", &["Any", "Any"], "Any"),
+    (Builtin::Len, "len", &["Any"], "i64"),
+    (Builtin::Contains, "contains", &["Any", "Any"], "Bool"),
+    (Builtin::Push, "push", &["List", "Any"], "List"),
+    (Builtin::Insert, "insert", &["Map", "Any", "Any"], "Map"),
+    (Builtin::Remove, "remove", &["Map", "Any"], "Map"),
+    (Builtin::Keys, "keys", &["Map"], "Iter"),
+    (Builtin::Values, "values", &["Map"], "Iter"),
+    (Builtin::Iter, "iter", &["Any"], "Iter"),
+    (Builtin::Map, "map", &["Any", "Any"], "Iter"),
+    (Builtin::Filter, "filter", &["Any", "Any"], "Iter"),
+    (Builtin::Take, "take", &["Any", "i64"], "Iter"),
+    (Builtin::Sum, "sum", &["Any"], "f64"),
+    (Builtin::ForEach, "for_each", &["Any", "Any"], "Void"),
+    (Builtin::Collect, "collect", &["Any"], "List"),
+    (Builtin::Join, "join", &["Any", "Text"], "Text"),
+];
+
+static BUILTIN_DATA: LazyLock<Vec<VariableData>> = LazyLock::new(|| BUILTINS.iter()
+    .enumerate()
+    .map(|(pos, (builtin, name, _, _))| {
+        debug_assert_eq!(*builtin as usize, pos, "builtins should be in order");
+        VariableData {
+            ix: builtin_ix(pos),
+            iden: Identifier::new(*name).expect("builtin names should be valid identifiers"),
+            type_annotation: None,
+            mutable: false,
+            span: Span::default(),
+            visible: Span { start: 0, end: u32::MAX },
+        }
+    })
+    .collect());
+
+/// Builtins count down from -1, so that they do not overlap with the variables in a file.
+fn builtin_ix(pos: usize) -> Ix {
+    -1 - pos as Ix
+}
+
+impl Builtin {
+    pub fn all() -> impl Iterator<Item = Builtin> {
+        BUILTINS.iter().map(|(builtin, ..)| *builtin)
+    }
+
+    pub fn var(self) -> Variable {
+        BUILTIN_DATA[self as usize].refer()
+    }
+
+    pub fn name(self) -> &'static str {
+        BUILTINS[self as usize].1
+    }
+
+    /// Type names of the parameters, for methods the first one is the value the method is called on.
+    pub fn params(self) -> &'static [&'static str] {
+        BUILTINS[self as usize].2
+    }
+
+    pub fn result(self) -> &'static str {
+        BUILTINS[self as usize].3
+    }
+
+    pub fn binary(op: BinOpCode) -> Builtin {
+        match op {
+            BinOpCode::Add => Builtin::Add,
+            BinOpCode::Sub => Builtin::Sub,
+            BinOpCode::Mul => Builtin::Mul,
+            BinOpCode::Div => Builtin::Div,
+            BinOpCode::Modulo => Builtin::Modulo,
+            BinOpCode::Eq => Builtin::Eq,
+            BinOpCode::Neq => Builtin::Neq,
+            BinOpCode::Lt => Builtin::Lt,
+            BinOpCode::Gt => Builtin::Gt,
+            BinOpCode::Le => Builtin::Le,
+            BinOpCode::Ge => Builtin::Ge,
+            BinOpCode::And => Builtin::And,
+            BinOpCode::Or => Builtin::Or,
+            BinOpCode::Xor => Builtin::Xor,
+        }
+    }
+
+    pub fn unary(op: UnaryOpCode) -> Builtin {
+        match op {
+            UnaryOpCode::Not => Builtin::Not,
+            UnaryOpCode::Min => Builtin::Neg,
+        }
+    }
+
+    /// Whether this is the function for an operator, like `+` or `!`.
+    pub fn is_operator(self) -> bool {
+        (self as usize) <= (Builtin::Not as usize)
+    }
+
+    /// Whether this is an operator on numbers that gives a number, like `+` or unary `-`.
+    pub fn is_arithmetic(self) -> bool {
+        matches!(self, Builtin::Add | Builtin::Sub | Builtin::Mul | Builtin::Div | Builtin::Modulo | Builtin::Neg)
+    }
+
+    /// Whether this makes or unpacks an `Option` or `Result`, like `then` or `otherwise`.
+    pub fn is_optional(self) -> bool {
+        matches!(self, Builtin::Then | Builtin::Alternatively | Builtin::Otherwise)
+    }
+
+    /// Whether this works on a text, list or map, with types that depend on the collection, like `len` or `push`.
+    pub fn is_collection(self) -> bool {
+        (Builtin::Len as usize..Builtin::Iter as usize).contains(&(self as usize))
+    }
+
+    /// Whether this takes an iterable as the first argument, like `map` or `sum`.
+    pub fn is_iterator(self) -> bool {
+        (self as usize) >= (Builtin::Iter as usize)
+    }
+
+    /// Whether an argument can still be used after the call, because the result holds it, like with `push` or `map`.
+    pub fn keeps_arguments(self) -> bool {
+        matches!(self, Builtin::Push | Builtin::Insert | Builtin::Map | Builtin::Filter)
+    }
+
+    /// The builtin with the same name that takes this many arguments, like `range(end)` instead of `range(start, end)`.
+    pub fn with_arity(self, arg_count: usize) -> Builtin {
+        Builtin::all()
+            .find(|other| other.name() == self.name() && other.params().len() == arg_count)
+            .unwrap_or(self)
+    }
+
+    /// The builtin that is called as `value.name(args)`, with `arg_count` arguments besides the value.
+    pub fn method(name: &str, arg_count: usize) -> Option<Builtin> {
+        Builtin::all().find(|builtin| builtin.name() == name && builtin.params().len() == arg_count + 1)
+    }
+}
+
+pub(crate) fn builtin_data(ix: Ix) -> &'static VariableData {
+    debug_assert!(ix < 0);
+    &BUILTIN_DATA[(-1 - ix) as usize]
+}
+
+impl Variable {
+    pub fn builtin(self) -> Option<Builtin> {
+        let ix = self.ix();
+        if ix >= 0 {
+            return None
+        }
+        Some(BUILTINS[(-1 - ix) as usize].0)
+    }
+}
+
@@ -0,0 +1,70 @@
+use serde::Serialize;
+use tel_common::Identifier;
+use tel_common::SString;
+use tel_common::Span;
+
+use crate::Block;
+use crate::Closure;
+use crate::Variable;
+
+#[derive(Debug, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub enum ExprKind {
+    Int(i64),
+    Float(f64),
+    Text(SString),
+    /// Text made by joining the parts, which are turned into text if they are not already.
+    Interpolate(Box<[Expr]>),
+    List(Box<[Expr]>),
+    /// Entries of a map, where later keys replace earlier equal ones.
+    Map(Box<[(Expr, Expr)]>),
+    /// Element of a list or text at a position, or of a map by key.
+    Index(Box<Expr>, Box<Expr>),
+    /// The value inside `Some` or `Ok`, while `None` and `Err` are returned like with `return`.
+    Propagate(Box<Expr>),
+    /// Variable read or function call. Operators are calls to a [Builtin](crate::Builtin).
+    Invoke(Invoke),
+    /// Field or method of the value, which can only be found once its type is known.
+    Dot(Box<Expr>, Member),
+    Closure(Closure),
+    /// If, then, else (empty else is same as no else)
+    If(Box<[(Expr, Block)]>, Option<Block>),
+    While(Box<Expr>, Block),
+    ForEach(Variable, Box<Expr>, Block),
+    /// Branch on the variant of the value, with an optional `else` for the rest.
+    Match(Box<Expr>, Box<[MatchArm]>, Option<Block>),
+}
+
+/// Branch of a match, where the variant is the struct or enum that the value should be (inside).
+#[derive(Debug, Serialize)]
+pub struct MatchArm {
+    pub variant: Variable,
+    pub binding: Option<Variable>,
+    pub body: Block,
+}
+
+/// Like the ast version, this can still be either a read or a call. But the
+/// identifier is linked to the declaration now.
+#[derive(Debug, Serialize)]
+pub struct Invoke {
+    pub var: Variable,
+    pub args: Box<[Expr]>,
+}
+
+/// Member name is not linked, because that depends on the type of the value.
+#[derive(Debug, Serialize)]
+pub struct Member {
+    pub iden: Identifier,
+    pub args: Box<[Expr]>,
+}
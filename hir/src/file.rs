@@ -0,0 +1,75 @@
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::block::Block;
+use crate::function::Function;
+use crate::types::{Enum, Impl, Interface, Struct};
+use crate::Variable;
+use crate::Variables;
+use tel_common::Span;
+
+/// The resolved contents of one source file.
+///
+/// Types and functions are collected here regardless of where they were declared,
+/// their names are still scoped through the [Variable](crate::Variable) they are bound to.
+#[derive(Debug, Serialize)]
+pub struct TelFile {
+    pub variables: Variables,
+    pub structs: Box<[Struct]>,
+    pub enums: Box<[Enum]>,
+    pub interfaces: Box<[Interface]>,
+    pub impls: Box<[Impl]>,
+    /// Named functions declared at the top level, these can be used before their declaration.
+    pub functions: Box<[Function]>,
+    /// Where variables are used or assigned in the source, for tooling.
+    pub references: Box<[Reference]>,
+    /// Top-level code, in the order it appears in the file.
+    pub main: Block,
+    pub prelude: Prelude,
+    /// Names that this file imports from other files.
+    pub imports: Box<[Variable]>,
+    /// Indices of the variables of imported files. Their code is resolved together with this file,
+    /// so it can be checked and run by itself, but only the imported names can be used here.
+    pub imported_variables: Range<usize>,
+}
+
+impl TelFile {
+    /// Whether the variable is declared in this file, rather than being a builtin or from the prelude or another file.
+    pub fn declares(&self, var: Variable) -> bool {
+        var.builtin().is_none() && !self.prelude.contains(var) && !self.imports.contains(&var) && !self.is_imported(var)
+    }
+
+    /// Whether the variable can be used by name somewhere in this file.
+    pub fn can_use(&self, var: Variable) -> bool {
+        self.imports.contains(&var) || !self.is_imported(var)
+    }
+
+    fn is_imported(&self, var: Variable) -> bool {
+        usize::try_from(var.ix()).is_ok_and(|ix| self.imported_variables.contains(&ix))
+    }
+}
+
+/// Types that every file has, which operators like `?` and some builtins produce or use.
+/// Their declarations are with the other types, but they are not part of the source.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Prelude {
+    pub option: Variable,
+    pub some: Variable,
+    pub none: Variable,
+    pub result: Variable,
+    pub ok: Variable,
+    pub err: Variable,
+}
+
+impl Prelude {
+    pub fn contains(&self, var: Variable) -> bool {
+        [self.option, self.some, self.none, self.result, self.ok, self.err].contains(&var)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Reference {
+    pub span: Span,
+    pub var: Variable,
+}
@@ -0,0 +1,25 @@
+use serde::Serialize;
+use tel_ast::Type;
+use tel_common::Span;
+
+use crate::Block;
+use crate::TypeParam;
+use crate::Variable;
+
+#[derive(Debug, Serialize)]
+pub struct Function {
+    pub var: Variable,
+    pub closure: Closure,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Closure {
+    pub generics: Box<[TypeParam]>,
+    /// Type annotations are stored with the variables.
+    pub params: Box<[Variable]>,
+    pub ret: Option<Type>,
+    pub body: Block,
+    /// Closures written like `{ ... }` are part of the function around them, so `return` returns from that function.
+    pub lambda: bool,
+    pub span: Span,
+}
@@ -3,3 +3,45 @@
 //! * Type checked
 //! * Easy to do transformations on
 
+use std::mem::size_of;
+
+pub use self::block::Assignment;
+pub use self::block::Block;
+pub use self::block::SetField;
+pub use self::block::Stmt;
+pub use self::builtin::Builtin;
+pub use self::expr::Expr;
+pub use self::expr::ExprKind;
+pub use self::expr::Invoke;
+pub use self::expr::MatchArm;
+pub use self::expr::Member;
+pub use self::file::Prelude;
+pub use self::file::Reference;
+pub use self::file::TelFile;
+pub use self::function::Closure;
+pub use self::function::Function;
+pub use self::types::Enum;
+pub use self::types::EnumVariant;
+pub use self::types::Field;
+pub use self::types::Impl;
+pub use self::types::Interface;
+pub use self::types::Signature;
+pub use self::types::Struct;
+pub use self::types::TypeParam;
+pub use self::variable::Variable;
+pub use self::variable::VariableData;
+pub use self::variable::Variables;
+
+mod block;
+mod builtin;
+mod expr;
+mod file;
+mod function;
+mod types;
+mod variable;
+
+//TODO @mark: replace all usize in structs and enums by Ix if ~1kkk is enough
+/// Negative indices are used for built-ins
+pub type Ix = i32;
+
+const _: () = assert!(size_of::<Ix>() <= size_of::<usize>(), "index is too large for this platform");
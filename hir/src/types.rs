@@ -0,0 +1,68 @@
+use serde::Serialize;
+use tel_ast::Type;
+use tel_common::Identifier;
+use tel_common::Span;
+
+use crate::Function;
+use crate::Variable;
+
+#[derive(Debug, Serialize)]
+pub struct Struct {
+    pub var: Variable,
+    pub generics: Box<[TypeParam]>,
+    pub fields: Box<[Field]>,
+    /// The first parameter of each method is the instance it is called on.
+    pub methods: Box<[Function]>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Field {
+    pub iden: Identifier,
+    pub typ: Type,
+}
+
+/// Methods that a type must have to satisfy the interface as a generic bound.
+#[derive(Debug, Serialize)]
+pub struct Interface {
+    pub var: Variable,
+    pub methods: Box<[Signature]>,
+}
+
+/// Method of an interface, with the types of the parameters if they are annotated.
+#[derive(Debug, Serialize)]
+pub struct Signature {
+    pub iden: Identifier,
+    pub params: Box<[Option<Type>]>,
+    pub ret: Option<Type>,
+}
+
+/// Methods of a struct or enum for an interface, which can be called on any value of the type (including variants).
+#[derive(Debug, Serialize)]
+pub struct Impl {
+    pub interface: Variable,
+    pub target: Variable,
+    /// Like for structs, the first parameter of each method is the instance.
+    pub methods: Box<[Function]>,
+    pub span: Span,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Enum {
+    pub var: Variable,
+    pub generics: Box<[TypeParam]>,
+    pub variants: Box<[EnumVariant]>,
+}
+
+/// Variants declared inside the enum are stored with the other types, and referred to here.
+#[derive(Debug, Serialize)]
+pub enum EnumVariant {
+    Struct(Variable),
+    Enum(Variable),
+    Existing(Type),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeParam {
+    pub iden: Identifier,
+    pub bound: Option<Type>,
+}
@@ -1,14 +1,17 @@
 use std::mem::size_of;
 use std::ops::Index;
 
-use crate::typ::Type;
+use tel_ast::Type;
+use crate::builtin::builtin_data;
 use crate::Ix;
 use serde::Serialize;
 use tel_common::Identifier;
+use tel_common::Span;
 
 /// All variables per TelFile are owned by this central buffer.
 /// In the tree, lightweight indices are used, and this class is passed explicitly.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
 pub struct Variables {
     data: Vec<VariableData>,
 }
@@ -31,6 +34,8 @@ impl Variables {
         iden: Identifier,
         type_annotation: Option<Type>,
         mutable: bool,
+        span: Span,
+        visible: Span,
     ) -> Variable {
         let new_ix = self.data.len();
         debug_assert!(new_ix < (Ix::MAX as usize), "maximum number of variables per file exceeded ({new_ix})");
@@ -39,9 +44,24 @@ impl Variables {
             iden,
             type_annotation,
             mutable,
+            span,
+            visible,
         });
         self.data[new_ix].refer()
     }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// All variables in order of declaration.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &VariableData> {
+        self.data.iter()
+    }
 }
 
 impl Index<Variable> for Variables {
@@ -51,19 +71,22 @@ impl Index<Variable> for Variables {
         if var.ix >= 0 {
             &self.data[var.ix as usize]
         } else {
-            todo!()
-            // builtin_var_data(var.ix)
+            builtin_data(var.ix)
         }
     }
 }
 
 //TODO @mark: can I make this unsized by putting type last?
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct VariableData {
     pub ix: Ix,
     pub iden: Identifier,
     pub type_annotation: Option<Type>,
     pub mutable: bool,
+    /// Where the variable is declared.
+    pub span: Span,
+    /// The code in which the variable can be used, which is the whole file for globals.
+    pub visible: Span,
 }
 
 impl VariableData {
@@ -77,7 +100,7 @@ impl VariableData {
 /// This is implicitly linked to a specific Variables instance by being in the same TelFile.
 /// There is no safety check for this, calling code must pass the right Variables around.
 /// Note: PartialEq only makes sense within a TelFile
-#[derive(Debug, Serialize, PartialEq, Clone, Copy)]
+#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 #[serde(transparent)]
 pub struct Variable {
     ix: Ix,
@@ -89,6 +112,10 @@ impl Variable {
     // Indexing without bound check is safe if we don't do anything weird,
     // because `variables` never shrinks and Variable is only created on insertion.
 
+    pub(crate) fn ix(self) -> Ix {
+        self.ix
+    }
+
     pub fn iden(self, variables: &Variables) -> &Identifier {
         &variables[self].iden
     }
@@ -102,4 +129,8 @@ impl Variable {
         &variables[self].mutable
     }
 
+    pub fn span(self, variables: &Variables) -> Span {
+        variables[self].span
+    }
+
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
 mod parser;
 
 pub use parser::str_to_ast;
+pub use parser::str_to_ast_partial;
@@ -6,49 +6,57 @@ use std::path::PathBuf;
 use lalrpop_util::lalrpop_mod;
 use log::debug;
 
-use tel_ast::{Ast, ParseErr, TelFile};
+use tel_ast::{Ast, ParseErr};
+use tel_common::Diagnostic;
 
 use crate::parser::errors::build_error;
 
 mod errors;
+mod text;
 
 lalrpop_mod!(#[allow(clippy::all)] gen_parser, "/grammar.rs");
 
-pub fn str_to_ast(src_pth: PathBuf, mut code: String) -> Result<Ast, ParseErr> {
+pub fn str_to_ast(src_pth: PathBuf, code: String) -> Result<Ast, ParseErr> {
+    let (ast, diagnostics) = str_to_ast_partial(code);
+    if !diagnostics.is_empty() {
+        return Err(ParseErr::ParseErr {
+            file: src_pth,
+            diagnostics,
+        })
+    }
+    Ok(ast)
+}
+
+/// Parse as much as possible, skipping statements that have syntax errors, and return all the errors.
+/// The ast only contains the valid statements, so it should only be used for tooling if there are errors.
+pub fn str_to_ast_partial(mut code: String) -> (Ast, Vec<Diagnostic>) {
     if count_empty_lines_at_end(&code) == 0 {
         code.push('\n')
         //TODO @mark: remove this workaround
     }
-    fail_if_no_newline_at_end(&src_pth, &code)?;
+    let mut diagnostics = Vec::new();
+    fail_if_no_newline_at_end(&code, &mut diagnostics);
     let parser = gen_parser::ProgParser::new();
-    let res = parser.parse(&code);
+    let mut recovered = Vec::new();
+    let res = parser.parse(&mut recovered, &code);
+    diagnostics.extend(recovered.into_iter()
+        .map(|recovery| build_error(recovery.error, &code)));
     match res {
         Ok(ast) => {
             debug!("ast: {:?}", &ast);
-            Ok(ast)
+            (ast, diagnostics)
         }
         Err(err) => {
-            let (msg, line) = build_error(err, src_pth.to_str().unwrap(), &code);
-            Err(ParseErr::ParseErr {
-                file: src_pth,
-                line,
-                msg,
-            })
+            diagnostics.push(build_error(err, &code));
+            (Ast { imports: Box::new([]), blocks: Box::new([]) }, diagnostics)
         }
     }
-    //TODO @mark: no unwrap
 }
 
-fn fail_if_no_newline_at_end(src_pth: &Path, code: &str) -> Result<(), ParseErr> {
+fn fail_if_no_newline_at_end(code: &str, diagnostics: &mut Vec<Diagnostic>) {
     if count_empty_lines_at_end(code) == 0 {
-        return Err(ParseErr::ParseErr {
-            file: src_pth.to_owned(),
-            line: code.lines().count(),
-            //TODO @mark:  test ^
-            msg: "Source files must have an empty line at the end".to_string(),
-        })
+        diagnostics.push(Diagnostic::error("E0105", "source files must have an empty line at the end"))
     }
-    Ok(())
 }
 
 fn count_empty_lines_at_end(text: &str) -> usize {
@@ -88,13 +96,21 @@ mod internal {
 
 #[cfg(test)]
 mod bugs {
+    use tel_ast::Block;
+    use tel_ast::Expr;
+    use tel_ast::ExprKind;
+    use tel_ast::TextPart;
+    use tel_common::Span;
+
     use super::*;
 
     fn parse(code: &str) -> Ast {
         match str_to_ast(PathBuf::new(), code.to_owned()) {
             Ok(ast) => ast,
-            Err(ParseErr::ParseErr { msg, .. }) => {
-                println!("{}", msg);
+            Err(ParseErr::ParseErr { diagnostics, .. }) => {
+                for diagnostic in diagnostics {
+                    println!("{}", diagnostic.render_human(code, false));
+                }
                 panic!()
             }
             Err(_) => panic!(),
@@ -117,6 +133,17 @@ mod bugs {
         parse("struct D {\n}");
     }
 
+    #[test]
+    fn struct_construction_is_a_call() {
+        let ast = parse("struct T {\n    c: f64\n}\nt = T(1.5)\n");
+        let Block::Assigns(t) = &ast.blocks[1] else { panic!() };
+        let ExprKind::Invoke(invoke) = &t.value.kind else { panic!() };
+        assert_eq!(invoke.iden.as_str(), "T");
+        assert_eq!(invoke.args.len(), 1);
+        // a block after a name is a trailing closure, so fields cannot be named like this
+        assert!(str_to_ast(PathBuf::new(), "t = T { c: 1.5 }\n".to_owned()).is_err());
+    }
+
     #[test]
     fn nullary_function_with_parentheses() {
         parse("f()");
@@ -154,11 +181,103 @@ mod bugs {
     //     parse("x.a\\1\n.b;f").unwrap();
     // }
 
+    #[test]
+    fn text_escapes_and_interpolation() {
+        let ast = parse("x = 'a\\'\\n\\{'\ny = \"sum {a + 1}\"\n");
+        let Block::Assigns(x) = &ast.blocks[0] else { panic!() };
+        assert_eq!(x.value.kind, ExprKind::Text("a'\n{".into()));
+        let Block::Assigns(y) = &ast.blocks[1] else { panic!() };
+        let ExprKind::Interpolate(parts) = &y.value.kind else { panic!() };
+        let [TextPart::Text(text), TextPart::Code(code)] = &**parts else { panic!() };
+        assert_eq!(text.as_str(), "sum ");
+        assert_eq!(code.span, Span::new(24, 29));
+        let ExprKind::BinOp(_, left, _) = &code.kind else { panic!() };
+        assert_eq!(left.span, Span::new(24, 25));
+        let error_span = |code: &str| match str_to_ast(PathBuf::new(), code.to_owned()) {
+            Err(ParseErr::ParseErr { diagnostics, .. }) => diagnostics[0].primary_span(),
+            _ => panic!("expected a parse error"),
+        };
+        assert_eq!(error_span("x = \"{a b}\"\n"), Some(Span::new(6, 9)));
+        assert_eq!(error_span("x = \"a}\"\n"), Some(Span::new(6, 7)));
+        assert_eq!(error_span("x = 'ab\\q'\n"), Some(Span::new(7, 9)));
+        assert_eq!(error_span("x = \"a {b\"\n"), Some(Span::new(7, 8)));
+    }
+
+    #[test]
+    fn collection_literals() {
+        let ast = parse("x = [1, [],\n]\ny = {'a': x[0], (k): 2}\nz = {:}\nf = {}\ng = { a: i64 = 1 }\n");
+        let Block::Assigns(x) = &ast.blocks[0] else { panic!() };
+        let ExprKind::List(items) = &x.value.kind else { panic!() };
+        assert_eq!(items.len(), 2);
+        let Block::Assigns(y) = &ast.blocks[1] else { panic!() };
+        let ExprKind::Map(entries) = &y.value.kind else { panic!() };
+        assert_eq!(entries[0].0.kind, ExprKind::Text("a".into()));
+        assert!(matches!(entries[0].1.kind, ExprKind::Index(..)));
+        assert!(matches!(entries[1].0.kind, ExprKind::Invoke(_)));
+        let Block::Assigns(z) = &ast.blocks[2] else { panic!() };
+        assert_eq!(z.value.kind, ExprKind::Map(Box::new([])));
+        for block in &ast.blocks[3..] {
+            let Block::Assigns(closure) = block else { panic!() };
+            assert!(matches!(closure.value.kind, ExprKind::Closure(_)));
+        }
+        assert!(str_to_ast(PathBuf::new(), "x = {k: 1}\n".to_owned()).is_err());
+    }
+
+    #[test]
+    fn propagate() {
+        let ast = parse("x = a.b()?.c[0]?\n");
+        let Block::Assigns(x) = &ast.blocks[0] else { panic!() };
+        let ExprKind::Propagate(inner) = &x.value.kind else { panic!() };
+        let ExprKind::Index(base, _) = &inner.kind else { panic!() };
+        let ExprKind::Dot(target, _) = &base.kind else { panic!() };
+        assert!(matches!(target.kind, ExprKind::Propagate(_)));
+    }
+
     #[test]
     fn works_without_trailing_newline() {
         parse("5 +\n5");
     }
 
+    #[test]
+    fn error_points_at_token() {
+        let Err(ParseErr::ParseErr { diagnostics, .. }) = str_to_ast(PathBuf::new(), "a = (1 + 2))\n".to_owned()) else {
+            panic!()
+        };
+        let [diagnostic] = &*diagnostics else { panic!() };
+        assert_eq!(diagnostic.code, "E0102");
+        assert_eq!(diagnostic.msg, "did not expect ')'");
+        assert_eq!(diagnostic.primary_span(), Some(Span::new(11, 12)));
+    }
+
+    #[test]
+    fn integer_overflow_points_at_number() {
+        let Err(ParseErr::ParseErr { diagnostics, .. }) = str_to_ast(PathBuf::new(), "x = 1 + 99999999999999999999\n".to_owned()) else {
+            panic!()
+        };
+        let [diagnostic] = &*diagnostics else { panic!() };
+        assert_eq!(diagnostic.code, "E0104");
+        assert_eq!(diagnostic.msg, "integer is too large for i64");
+        assert_eq!(diagnostic.primary_span(), Some(Span::new(8, 28)));
+    }
+
+    #[test]
+    fn recover_after_statement_errors() {
+        let (ast, diagnostics) = str_to_ast_partial("a = 1 +* 2\nb = 2\nf(a b)\nc = 3\n".to_owned());
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].primary_span(), Some(Span::new(7, 8)));
+        assert_eq!(diagnostics[1].primary_span(), Some(Span::new(21, 22)));
+        assert_eq!(ast.blocks.len(), 2);
+    }
+
+    #[test]
+    fn recover_inside_block() {
+        let (ast, diagnostics) = str_to_ast_partial("if (x) {\n    a = = 1\n    b = 2\n}\nc = 3\n".to_owned());
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(ast.blocks.len(), 2);
+        let Block::Expression(Expr { kind: ExprKind::If(branches, _), .. }) = &ast.blocks[0] else { panic!() };
+        assert_eq!(branches[0].1.len(), 1);
+    }
+
     #[test]
     fn reject_arithmetic_without_spacing() {
         assert!(str_to_ast(PathBuf::new(), "1+\n1".to_owned()).is_err());
@@ -170,4 +289,16 @@ mod bugs {
         let code = "x = 1 ...\n\n+ 2";
         parse(code);
     }
+
+    #[test]
+    fn imports_and_public() {
+        let ast = parse("import shapes { Square, area }\nimport util {\n    twice,\n}\n\npub struct P {}\npub x: i64 = 1\ny = 2\n");
+        assert_eq!(ast.imports.len(), 2);
+        assert_eq!(ast.imports[0].module.as_str(), "shapes");
+        assert_eq!(ast.imports[0].names.iter().map(|name| name.iden.as_str()).collect::<Vec<_>>(), vec!["Square", "area"]);
+        assert_eq!(ast.imports[1].names.len(), 1);
+        let [Block::Struct(strct), Block::Assigns(x), Block::Assigns(y)] = &*ast.blocks else { panic!() };
+        assert!(strct.public && x.public && !y.public);
+        assert!(str_to_ast(PathBuf::new(), "x = 1\nimport util { a }\n".to_owned()).is_err());
+    }
 }
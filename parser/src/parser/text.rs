@@ -0,0 +1,116 @@
+use std::mem;
+
+use tel_ast::Block;
+use tel_ast::Expr;
+use tel_ast::ExprKind;
+use tel_ast::MapSpans;
+use tel_ast::TextPart;
+use tel_common::parse_util::unquote_span;
+use tel_common::Span;
+
+use crate::parser::errors::UserError;
+use crate::parser::gen_parser;
+
+/// Turn a quoted text token into an expression, handling escapes and code in `{}`.
+/// The result is plain text if there is no code in it.
+pub fn text_expr(token: &str, lo: usize, hi: usize) -> Result<Expr, UserError> {
+    let span = unquote_span(token, lo, hi);
+    let quoted = token.trim_start();
+    let content = &quoted[1..quoted.len() - 1];
+    let content_start = span.start as usize + 1;
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut pos = 0;
+    while let Some(ch) = content[pos..].chars().next() {
+        let ch_span = Span::new(content_start + pos, content_start + pos + ch.len_utf8());
+        pos += ch.len_utf8();
+        match ch {
+            '\\' => {
+                let escaped = content[pos..].chars().next();
+                pos += escaped.map_or(0, char::len_utf8);
+                text.push(unescape(escaped).ok_or(UserError {
+                    msg: "unknown escape in text, use '\\\\' for a literal backslash",
+                    span: Span::new(ch_span.start as usize, content_start + pos),
+                    label: "unknown escape",
+                })?);
+            }
+            '{' => {
+                let len = code_len(&content[pos..]).ok_or(UserError {
+                    msg: "'{' in text is not closed, use '\\{' for a literal brace",
+                    span: ch_span,
+                    label: "not closed",
+                })?;
+                if !text.is_empty() {
+                    parts.push(TextPart::Text(mem::take(&mut text).as_str().into()));
+                }
+                parts.push(TextPart::Code(parse_code(&content[pos..pos + len], content_start + pos)?));
+                pos += len + 1;
+            }
+            '}' => return Err(UserError {
+                msg: "'}' in text was not opened, use '\\}' for a literal brace",
+                span: ch_span,
+                label: "not opened",
+            }),
+            ch => text.push(ch),
+        }
+    }
+    if parts.is_empty() {
+        return Ok(Expr::new(ExprKind::Text(text.as_str().into()), span))
+    }
+    if !text.is_empty() {
+        parts.push(TextPart::Text(text.as_str().into()));
+    }
+    Ok(Expr::new(ExprKind::Interpolate(parts.into_boxed_slice()), span))
+}
+
+fn unescape(escaped: Option<char>) -> Option<char> {
+    Some(match escaped {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some(ch @ ('\\' | '"' | '\'' | '{' | '}')) => ch,
+        _ => return None,
+    })
+}
+
+/// Length of the code up to the `}` that closes it, which may contain other braces.
+fn code_len(code: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (ix, ch) in code.char_indices() {
+        if escaped {
+            escaped = false;
+            continue
+        }
+        match ch {
+            // braces in texts inside the code are escaped
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(ix),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the code inside a text as a single expression, which starts at `start` in the file.
+/// It is parsed by itself, so the spans are moved to point to the right place.
+fn parse_code(code: &str, start: usize) -> Result<Expr, UserError> {
+    let invalid = |msg| UserError { msg, span: Span::new(start, start + code.len()), label: "in this code" };
+    let line = format!("{code}\n");
+    let mut recovered = Vec::new();
+    let ast = gen_parser::ProgParser::new()
+        .parse(&mut recovered, &line)
+        .map_err(|_| invalid("invalid code in text"))?;
+    if !recovered.is_empty() {
+        return Err(invalid("invalid code in text"))
+    }
+    let mut blocks = Vec::from(ast.blocks);
+    let (Some(Block::Expression(mut expr)), true) = (blocks.pop(), blocks.is_empty()) else {
+        return Err(invalid("code in text should be a single expression"))
+    };
+    expr.map_spans(&mut |span| Span::new(span.start as usize + start, span.end as usize + start));
+    Ok(expr)
+}
@@ -11,6 +11,7 @@ use std::collections::HashSet;
 use crate::common::{Name, Path, FQ};
 use crate::context::{Global, RootContext};
 use crate::graph::{ExecId, StepId};
+use tel_common::Diagnostic;
 
 #[derive(Debug)]
 pub enum Error {
@@ -33,6 +34,20 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+impl Error {
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Error::Io(path, e) => Diagnostic::error("E1005", format!("IO error: {}", e))
+                .with_file(path.as_path()),
+            Error::Parse(path, e) => e.diagnostic().with_file(path.as_path()),
+            Error::Resolve(name, e) => e.diagnostic()
+                .with_note(format!("while resolving '{}'", name.as_str())),
+            Error::Execute(name, e) => e.diagnostic()
+                .with_note(format!("while executing '{}'", name.as_str())),
+        }
+    }
+}
+
 fn visualize_tree(ctx: &RootContext, step: &StepId, prefix: &str, is_last: bool, visited: &mut HashSet<StepId>) {
     let connector = if is_last { "└── " } else { "├── " };
     println!("{}{}{}", prefix, connector, step);
@@ -83,3 +98,46 @@ pub async fn run_file(path: &str, show_deps: bool) -> Result<(), Error> {
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ExecuteError, ParseError, ResolveError};
+
+    #[test]
+    fn error_codes_are_unique() {
+        let name = || Name::of("f");
+        let io = || std::io::Error::other("missing");
+        let errors = vec![
+            Error::Io(Path::of("a.telsb"), io()),
+            Error::Parse(Path::of("a.telsb"), ParseError::UnexpectedEof),
+            Error::Parse(Path::of("a.telsb"), ParseError::UnexpectedToken("x".to_owned())),
+            Error::Parse(Path::of("a.telsb"), ParseError::InvalidNumber("1x".to_owned())),
+            Error::Parse(Path::of("a.telsb"), ParseError::EmptyExpression),
+            Error::Parse(Path::of("a.telsb"), ParseError::IoError("missing".to_owned())),
+            Error::Resolve(name(), ResolveError::UndefinedVariable(name(), "x".to_owned())),
+            Error::Resolve(name(), ResolveError::UndefinedFunction(name(), "g".to_owned())),
+            Error::Resolve(name(), ResolveError::InvalidImportPath(name(), "b".to_owned())),
+            Error::Resolve(name(), ResolveError::VariableAlreadyDefined(name(), "x".to_owned())),
+            Error::Resolve(name(), ResolveError::ArgOutsideFunction(name())),
+            Error::Resolve(name(), ResolveError::InvalidArgNumber(name(), 0)),
+            Error::Resolve(name(), ResolveError::ImportNotAtTop(name())),
+            Error::Resolve(name(), ResolveError::FunctionDefNotAfterImports(name())),
+            Error::Resolve(name(), ResolveError::FunctionAlreadyDefined(name(), "g".to_owned())),
+            Error::Resolve(name(), ResolveError::FunctionOverload { loc: FQ::of("a.telsb", "g"), existing_arity: 1, new_arity: 2 }),
+            Error::Resolve(name(), ResolveError::ArityMismatch { context: name(), func_name: "g".to_owned(), expected: 1, got: 2 }),
+            Error::Resolve(name(), ResolveError::ArityGap { context: name(), func_name: "g".to_owned(), max_arg: 2 }),
+            Error::Resolve(name(), ResolveError::UnreachableCode { context: name(), source_location: "a.telsb:1".to_owned() }),
+            Error::Resolve(name(), ResolveError::CyclicDependency { cycle: vec![FQ::of("a.telsb", "g")] }),
+            Error::Resolve(name(), ResolveError::IoError(Path::of("a.telsb"), io())),
+            Error::Resolve(name(), ResolveError::JoinError("cancelled".to_owned())),
+            Error::Execute(name(), ExecuteError::DivisionByZero),
+            Error::Execute(name(), ExecuteError::ArgNotProvided(1)),
+            Error::Execute(name(), ExecuteError::Panic { source_location: "a.telsb:1".to_owned() }),
+        ];
+        let mut codes = HashSet::new();
+        for error in &errors {
+            let code = error.diagnostic().code;
+            assert!(codes.insert(code), "code {code} is used for more than one error, including {error}");
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use env_logger;
 use std::env;
+use std::io;
+use std::io::IsTerminal;
 use std::path::Path;
 use std::process;
 
@@ -67,7 +69,8 @@ fn main() {
     match result {
         Ok(()) => {}
         Err(e) => {
-            eprintln!("Error: {}", e);
+            // sandbox errors have no positions, so the source code is not needed
+            eprint!("{}", e.diagnostic().render_human("", io::stderr().is_terminal()));
             process::exit(1);
         }
     }
@@ -1,6 +1,7 @@
 use std::fmt;
 use crate::common::{Name, Path, FQ};
 use serde::{Deserialize, Serialize};
+use tel_common::Diagnostic;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BinOp {
@@ -164,6 +165,19 @@ impl fmt::Display for ParseError {
 
 impl std::error::Error for ParseError {}
 
+impl ParseError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let code = match self {
+            ParseError::UnexpectedEof => "E1000",
+            ParseError::UnexpectedToken(_) => "E1001",
+            ParseError::InvalidNumber(_) => "E1002",
+            ParseError::EmptyExpression => "E1003",
+            ParseError::IoError(_) => "E1004",
+        };
+        Diagnostic::error(code, self.to_string())
+    }
+}
+
 impl From<std::io::Error> for ParseError {
     fn from(err: std::io::Error) -> Self {
         ParseError::IoError(err.to_string())
@@ -230,6 +244,41 @@ impl fmt::Display for ResolveError {
 
 impl std::error::Error for ResolveError {}
 
+impl ResolveError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let code = match self {
+            ResolveError::UndefinedVariable(..) => "E1100",
+            ResolveError::UndefinedFunction(..) => "E1101",
+            ResolveError::InvalidImportPath(..) => "E1102",
+            ResolveError::VariableAlreadyDefined(..) => "E1103",
+            ResolveError::ArgOutsideFunction(..) => "E1104",
+            ResolveError::InvalidArgNumber(..) => "E1105",
+            ResolveError::ImportNotAtTop(..) => "E1106",
+            ResolveError::FunctionDefNotAfterImports(..) => "E1107",
+            ResolveError::FunctionAlreadyDefined(..) => "E1108",
+            ResolveError::FunctionOverload { .. } => "E1109",
+            ResolveError::ArityMismatch { .. } => "E1110",
+            ResolveError::ArityGap { .. } => "E1111",
+            ResolveError::UnreachableCode { .. } => "E1112",
+            ResolveError::CyclicDependency { .. } => "E1113",
+            ResolveError::IoError(..) => "E1114",
+            ResolveError::ParseError(path, err) => return err.diagnostic().with_file(path.as_path()),
+            ResolveError::JoinError(..) => "E1115",
+        };
+        match self {
+            ResolveError::CyclicDependency { cycle } => {
+                let steps = cycle.iter()
+                    .map(|location| format!("{}::{}", location.as_str(), location.name_str()))
+                    .collect::<Vec<_>>();
+                Diagnostic::error(code, "cyclic dependency detected")
+                    .with_note(format!("cycle: {}", steps.join(" -> ")))
+                    .with_note("remove one of the import dependencies above")
+            }
+            other => Diagnostic::error(code, other.to_string()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ExecuteError {
     DivisionByZero,
@@ -251,6 +300,18 @@ impl fmt::Display for ExecuteError {
 
 impl std::error::Error for ExecuteError {}
 
+impl ExecuteError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let code = match self {
+            ExecuteError::DivisionByZero => "E1200",
+            ExecuteError::ArgNotProvided(_) => "E1201",
+            ExecuteError::Panic { .. } => "E1202",
+            ExecuteError::ResolveError(err) => return err.diagnostic(),
+        };
+        Diagnostic::error(code, self.to_string())
+    }
+}
+
 impl From<ResolveError> for ExecuteError {
     fn from(err: ResolveError) -> Self {
         ExecuteError::ResolveError(Box::new(err))
@@ -0,0 +1,347 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use tel_ast::AssignmentDest;
+use tel_ast::AssignmentKw;
+use tel_ast::Assignments;
+use tel_ast::Ast;
+use tel_ast::BinOpCode;
+use tel_ast::Block;
+use tel_ast::Closure;
+use tel_ast::Enum;
+use tel_ast::EnumVariant;
+use tel_ast::Expr;
+use tel_ast::ExprKind;
+use tel_ast::Impl;
+use tel_ast::Import;
+use tel_ast::ImportName;
+use tel_ast::Interface;
+use tel_ast::Invoke;
+use tel_ast::MatchArm;
+use tel_ast::Method;
+use tel_ast::SetField;
+use tel_ast::Signature;
+use tel_ast::Struct;
+use tel_ast::TextPart;
+use tel_ast::Type;
+use tel_ast::UnaryOpCode;
+use tel_common::Identifier;
+use tel_common::Span;
+
+const BIN_OPS: [BinOpCode; 14] = [
+    BinOpCode::Add, BinOpCode::Sub, BinOpCode::Mul, BinOpCode::Div, BinOpCode::Modulo,
+    BinOpCode::Eq, BinOpCode::Neq, BinOpCode::Lt, BinOpCode::Gt, BinOpCode::Le, BinOpCode::Ge,
+    BinOpCode::And, BinOpCode::Or, BinOpCode::Xor,
+];
+/// Only these have a compound assignment syntax, like `+=`.
+const COMPOUND_OPS: [BinOpCode; 5] = [BinOpCode::Add, BinOpCode::Sub, BinOpCode::Mul, BinOpCode::Div, BinOpCode::Modulo];
+const TEXT_CHARS: &[u8] = b"abcXYZ019 _-+#.,!?\"'\\{}\n";
+
+/// Random ast that can be written as code, so it is not necessarily valid beyond syntax.
+/// Spans are all empty, since there is no source code.
+pub fn gen_random_file(rng: &mut StdRng) -> Ast {
+    let mut gen = Gen { rng, budget: 60 };
+    let import_count = if gen.rng.random_bool(0.8) { 0 } else { gen.rng.random_range(1..3) };
+    let imports = (0..import_count).map(|_| gen.import()).collect();
+    let count = gen.rng.random_range(1..8);
+    Ast { imports, blocks: gen.blocks(count, 3) }
+}
+
+struct Gen<'a> {
+    rng: &'a mut StdRng,
+    /// Roughly the number of nodes that can still be created, to keep programs small.
+    budget: u32,
+}
+
+impl Gen<'_> {
+    fn blocks(&mut self, count: usize, depth: u32) -> Box<[Block]> {
+        (0..count).map(|_| self.block(depth)).collect()
+    }
+
+    fn nested_blocks(&mut self, depth: u32) -> Box<[Block]> {
+        let count = if self.budget == 0 { 0 } else { self.rng.random_range(0..4) };
+        self.blocks(count, depth.saturating_sub(1))
+    }
+
+    fn import(&mut self) -> Import {
+        let count = self.rng.random_range(0..4);
+        let names = (0..count)
+            .map(|_| {
+                let iden = if self.rng.random_bool(0.5) { self.name() } else { self.type_name() };
+                ImportName { iden, span: Span::default() }
+            })
+            .collect();
+        Import { module: self.name(), names, span: Span::default() }
+    }
+
+    fn block(&mut self, depth: u32) -> Block {
+        // the grammar allows `pub` on nested declarations, scoping rejects it there
+        let public = self.rng.random_bool(0.1);
+        match self.rng.random_range(0..15) {
+            0..=3 => Block::Assigns(Assignments { public, ..self.assignments(depth) }),
+            4..=7 => Block::Expression(self.expr(depth, true)),
+            8 => Block::Return(self.expr(depth, true)),
+            9 => Block::SetField(self.set_field(depth)),
+            10 | 11 => Block::Struct(Struct { public, ..self.strct(depth) }),
+            12 => Block::Interface(Interface { public, ..self.interface() }),
+            13 => Block::Impl(self.imp(depth)),
+            _ => Block::Enum(Enum { public, ..self.enm(2) }),
+        }
+    }
+
+    fn set_field(&mut self, depth: u32) -> SetField {
+        let op = if self.rng.random_bool(0.2) {
+            Some(COMPOUND_OPS[self.rng.random_range(0..COMPOUND_OPS.len())])
+        } else {
+            None
+        };
+        SetField {
+            target: Box::new(self.expr(depth, false)),
+            field: self.name(),
+            op,
+            value: Box::new(self.expr(depth, true)),
+            span: Span::default(),
+        }
+    }
+
+    fn assignments(&mut self, depth: u32) -> Assignments {
+        let (dest, op) = if self.rng.random_bool(0.2) {
+            let op = COMPOUND_OPS[self.rng.random_range(0..COMPOUND_OPS.len())];
+            let dest = AssignmentDest { typ: None, ..self.assign_dest() };
+            (Box::new([dest]) as Box<[_]>, Some(op))
+        } else {
+            let count = self.rng.random_range(1..4);
+            ((0..count).map(|_| self.assign_dest()).collect(), None)
+        };
+        Assignments { dest, op, value: Box::new(self.expr(depth, true)), public: false, span: Span::default() }
+    }
+
+    fn assign_dest(&mut self) -> AssignmentDest {
+        let kw = match self.rng.random_range(0..8) {
+            0 => AssignmentKw::Outer,
+            1 => AssignmentKw::Local,
+            2 => AssignmentKw::Mut,
+            _ => AssignmentKw::None,
+        };
+        let typ = if self.rng.random_bool(0.3) { Some(self.typ(2)) } else { None };
+        AssignmentDest { kw, target: self.name(), typ, span: Span::default() }
+    }
+
+    /// Control flow can only be used where a full expression is allowed, not as an operand.
+    fn expr(&mut self, depth: u32, allow_control: bool) -> Expr {
+        self.budget = self.budget.saturating_sub(1);
+        if depth == 0 || self.budget == 0 {
+            return self.leaf();
+        }
+        let kind = match self.rng.random_range(0..if allow_control { 18 } else { 14 }) {
+            0..=2 => return self.leaf(),
+            3..=5 => ExprKind::BinOp(
+                BIN_OPS[self.rng.random_range(0..BIN_OPS.len())],
+                Box::new(self.expr(depth - 1, false)),
+                Box::new(self.expr(depth - 1, false)),
+            ),
+            6 => ExprKind::UnaryOp(
+                if self.rng.random_bool(0.5) { UnaryOpCode::Not } else { UnaryOpCode::Min },
+                Box::new(self.expr(depth - 1, false)),
+            ),
+            7 | 8 => ExprKind::Invoke(self.invoke(depth - 1)),
+            9 | 10 => ExprKind::Dot(Box::new(self.expr(depth - 1, false)), self.invoke(depth - 1)),
+            11 => ExprKind::Closure(self.closure(depth - 1, true)),
+            12 => {
+                let count = self.rng.random_range(0..4);
+                if self.rng.random_bool(0.5) {
+                    ExprKind::List((0..count).map(|_| self.expr(depth - 1, true)).collect())
+                } else {
+                    ExprKind::Map((0..count).map(|_| (self.map_key(depth - 1), self.expr(depth - 1, true))).collect())
+                }
+            }
+            13 if self.rng.random_bool(0.3) => ExprKind::Propagate(Box::new(self.expr(depth - 1, false))),
+            13 => ExprKind::Index(Box::new(self.expr(depth - 1, false)), Box::new(self.expr(depth - 1, true))),
+            14 => {
+                let branch_count = self.rng.random_range(1..4);
+                let branches = (0..branch_count)
+                    .map(|_| (self.expr(depth - 1, true), self.nested_blocks(depth)))
+                    .collect();
+                let otherwise = if self.rng.random_bool(0.5) { Some(self.nested_blocks(depth)) } else { None };
+                ExprKind::If(branches, otherwise)
+            }
+            15 => ExprKind::While(Box::new(self.expr(depth - 1, true)), self.nested_blocks(depth)),
+            16 => {
+                let arm_count = self.rng.random_range(0..4);
+                let arms = (0..arm_count)
+                    .map(|_| MatchArm {
+                        variant: self.type_name(),
+                        binding: if self.rng.random_bool(0.5) { Some(self.name()) } else { None },
+                        body: self.nested_blocks(depth),
+                        span: Span::default(),
+                    })
+                    .collect();
+                let otherwise = if self.rng.random_bool(0.5) { Some(self.nested_blocks(depth)) } else { None };
+                ExprKind::Match(Box::new(self.expr(depth - 1, true)), arms, otherwise)
+            }
+            _ => ExprKind::ForEach(
+                self.assign_dest(),
+                Box::new(self.expr(depth - 1, true)),
+                self.nested_blocks(depth),
+            ),
+        };
+        Expr::new(kind, Span::default())
+    }
+
+    /// Mostly literals, but other expressions are allowed as keys if the printer adds parentheses.
+    fn map_key(&mut self, depth: u32) -> Expr {
+        if self.rng.random_bool(0.3) {
+            return self.expr(depth, false)
+        }
+        self.plain_leaf()
+    }
+
+    fn leaf(&mut self) -> Expr {
+        if self.rng.random_bool(0.1) {
+            return Expr::new(self.interpolation(), Span::default())
+        }
+        self.plain_leaf()
+    }
+
+    /// Leaf that is not an interpolated text, so it can be used inside one.
+    fn plain_leaf(&mut self) -> Expr {
+        let kind = match self.rng.random_range(0..5) {
+            0 => ExprKind::Int(self.rng.random_range(0..1000)),
+            // quarters, so that the number is printed exactly
+            1 => ExprKind::Float(self.rng.random_range(0..4000) as f64 / 4.0),
+            2 => ExprKind::Text(self.text(0).as_str().into()),
+            _ => ExprKind::Invoke(Invoke { iden: self.name(), args: Box::new([]), span: Span::default() }),
+        };
+        Expr::new(kind, Span::default())
+    }
+
+    fn text(&mut self, min_len: usize) -> String {
+        let len = self.rng.random_range(min_len..8);
+        (0..len)
+            .map(|_| TEXT_CHARS[self.rng.random_range(0..TEXT_CHARS.len())] as char)
+            .collect()
+    }
+
+    /// Text parts are never empty or next to each other, because the parser would merge them.
+    fn interpolation(&mut self) -> ExprKind {
+        let mut parts = Vec::new();
+        for _ in 0..self.rng.random_range(1..3) {
+            if self.rng.random_bool(0.7) {
+                parts.push(TextPart::Text(self.text(1).as_str().into()));
+            }
+            parts.push(TextPart::Code(self.plain_leaf()));
+        }
+        if self.rng.random_bool(0.5) {
+            parts.push(TextPart::Text(self.text(1).as_str().into()));
+        }
+        ExprKind::Interpolate(parts.into_boxed_slice())
+    }
+
+    fn invoke(&mut self, depth: u32) -> Invoke {
+        let count = if self.rng.random_bool(0.5) { 0 } else { self.rng.random_range(1..4) };
+        let args = (0..count).map(|_| self.expr(depth, true)).collect();
+        Invoke { iden: self.name(), args, span: Span::default() }
+    }
+
+    fn closure(&mut self, depth: u32, allow_lambda: bool) -> Closure {
+        let (generics, params, ret) = if self.rng.random_bool(0.5) {
+            (Box::new([]) as Box<[_]>, Box::new([]) as Box<[_]>, None)
+        } else {
+            let count = self.rng.random_range(0..3);
+            let ret = if self.rng.random_bool(0.5) { Some(self.typ(2)) } else { None };
+            (self.generics(), (0..count).map(|_| self.assign_dest()).collect(), ret)
+        };
+        // methods are always written with `fn`, other closures use the short form when they can
+        let lambda = allow_lambda && generics.is_empty() && params.is_empty() && ret.is_none();
+        Closure { blocks: self.nested_blocks(depth), generics, params, ret, lambda, span: Span::default() }
+    }
+
+    fn strct(&mut self, depth: u32) -> Struct {
+        let count = self.rng.random_range(0..4);
+        let fields = (0..count).map(|_| (self.name(), self.typ(2))).collect();
+        let methods = self.methods(depth);
+        Struct { iden: self.type_name(), fields, methods, generics: self.generics(), public: false, span: Span::default() }
+    }
+
+    fn methods(&mut self, depth: u32) -> Vec<Method> {
+        let count = if self.rng.random_bool(0.7) { 0 } else { self.rng.random_range(1..3) };
+        (0..count)
+            .map(|_| Method { iden: self.name(), closure: self.closure(depth, false), span: Span::default() })
+            .collect()
+    }
+
+    fn interface(&mut self) -> Interface {
+        let count = self.rng.random_range(0..3);
+        let methods = (0..count)
+            .map(|_| {
+                let param_count = self.rng.random_range(0..3);
+                let params = (0..param_count).map(|_| self.assign_dest()).collect();
+                let ret = if self.rng.random_bool(0.5) { Some(self.typ(2)) } else { None };
+                Signature { iden: self.name(), params, ret, span: Span::default() }
+            })
+            .collect();
+        Interface { iden: self.type_name(), methods, public: false, span: Span::default() }
+    }
+
+    fn imp(&mut self, depth: u32) -> Impl {
+        let interface = Type { iden: self.type_name(), generics: Box::new([]), span: Span::default() };
+        let target = Type { iden: self.type_name(), generics: Box::new([]), span: Span::default() };
+        Impl { interface, target, methods: self.methods(depth), span: Span::default() }
+    }
+
+    fn enm(&mut self, depth: u32) -> Enum {
+        let count = self.rng.random_range(0..4);
+        let variants = (0..count)
+            .map(|_| match self.rng.random_range(0..4) {
+                0 => EnumVariant::Struct(self.strct(1)),
+                1 if depth > 0 => EnumVariant::Enum(self.enm(depth - 1)),
+                _ => EnumVariant::Existing(self.typ(2)),
+            })
+            .collect();
+        Enum { iden: self.type_name(), variants, generics: self.generics(), public: false, span: Span::default() }
+    }
+
+    fn generics(&mut self) -> Box<[AssignmentDest]> {
+        let count = if self.rng.random_bool(0.7) { 0 } else { self.rng.random_range(1..3) };
+        (0..count)
+            .map(|_| {
+                let typ = if self.rng.random_bool(0.5) { Some(self.typ(0)) } else { None };
+                AssignmentDest { kw: AssignmentKw::None, target: self.type_name(), typ, span: Span::default() }
+            })
+            .collect()
+    }
+
+    fn typ(&mut self, depth: u32) -> Type {
+        let count = if depth == 0 || self.rng.random_bool(0.7) { 0 } else { self.rng.random_range(1..3) };
+        let generics = (0..count).map(|_| self.typ(depth - 1)).collect();
+        Type { iden: self.type_name(), generics, span: Span::default() }
+    }
+
+    fn name(&mut self) -> Identifier {
+        self.identifier(b"abcdefghijklmnopqrstuvwxyz")
+    }
+
+    fn type_name(&mut self) -> Identifier {
+        self.identifier(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ")
+    }
+
+    /// Random identifier, which is sometimes a keyword with a suffix, like `iffy`.
+    fn identifier(&mut self, first: &[u8]) -> Identifier {
+        const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
+        const PREFIXES: [&str; 8] = ["if", "for", "fn", "and", "or", "struct", "return", "in"];
+        loop {
+            let mut name = String::new();
+            if self.rng.random_bool(0.1) {
+                name.push_str(PREFIXES[self.rng.random_range(0..PREFIXES.len())]);
+            } else {
+                name.push(first[self.rng.random_range(0..first.len())] as char);
+            }
+            for _ in 0..self.rng.random_range(0..5) {
+                name.push(REST[self.rng.random_range(0..REST.len())] as char);
+            }
+            if let Ok(iden) = Identifier::new(name) {
+                return iden;
+            }
+        }
+    }
+}
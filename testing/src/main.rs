@@ -1,23 +1,109 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
 
 use rand::rngs::StdRng;
-use rand::Rng;
 use rand::SeedableRng;
 
-use tel_ast::TelFile;
-// use tel_ast_to_code::ast_to_code;  //TODO @mark: ast_to_code crate doesn't exist yet
+use tel_ast::Ast;
+use tel_ast::ParseErr;
+use tel_ast_to_code::ast_to_code;
+use tel_parser::str_to_ast;
 
+use self::gen::gen_random_file;
+use self::shrink::shrink;
+
+mod gen;
+mod shrink;
+
+const DEFAULT_SEED: u64 = 123_456_789;
+
+/// Print random programs as code and parse them again, which should give the same ast.
+/// Usage: `tel-testing [iterations] [seed]`
 fn main() {
-    let _rng: StdRng = SeedableRng::seed_from_u64(123_456_789);
-    let _file = gen_random_file(&_rng);
-    // let code = ast_to_code(file);  //TODO @mark: implement ast_to_code
-    // let res = tel_build_str(PathBuf::from("test-input"), code.clone(), false);
-    // if res.is_err() {
-    //     println!("{}", &code);
-    //     panic!("failed to parse generated code")
-    // }
-    println!("Testing binary not yet implemented - ast_to_code crate missing");
+    let mut args = env::args().skip(1);
+    let iterations = args.next().map(|nr| nr.parse::<u64>().expect("iterations should be a number")).unwrap_or(1000);
+    let seed = args.next().map(|nr| nr.parse::<u64>().expect("seed should be a number")).unwrap_or(DEFAULT_SEED);
+    match fuzz_round_trip(seed, iterations) {
+        Ok(()) => println!("{iterations} random programs survived the round trip"),
+        Err(report) => {
+            eprintln!("{report}");
+            process::exit(1)
+        }
+    }
 }
 
-fn gen_random_file(_rng: &impl Rng) -> TelFile {
-    TelFile {}
+/// Check a random program for each seed from `seed` onwards, and describe the smallest failing program.
+fn fuzz_round_trip(seed: u64, iterations: u64) -> Result<(), String> {
+    for iteration in 0..iterations {
+        let mut rng = StdRng::seed_from_u64(seed + iteration);
+        let ast = gen_random_file(&mut rng);
+        let Err(problem) = round_trip(&ast) else {
+            continue;
+        };
+        // only keep simplifications that fail in the same way, not for some unrelated reason
+        let minimal = shrink(ast, |candidate| round_trip(candidate).is_err_and(|other| other.kind == problem.kind));
+        let problem = round_trip(&minimal).expect_err("shrinking should keep the failure");
+        return Err(format!(
+            "round trip failed for seed {}: {}\n{}\nminimal program:\n{}",
+            seed + iteration,
+            problem.kind,
+            problem.detail,
+            ast_to_code(&minimal),
+        ));
+    }
+    Ok(())
+}
+
+struct Problem {
+    /// Short description that is the same for similar failures, like the error code.
+    kind: String,
+    detail: String,
+}
+
+/// Print the ast, parse it again and compare, ignoring positions.
+fn round_trip(ast: &Ast) -> Result<(), Problem> {
+    let code = ast_to_code(ast);
+    let reparsed = match str_to_ast(PathBuf::from("generated.tel"), code.clone()) {
+        Ok(reparsed) => reparsed,
+        Err(ParseErr::ParseErr { diagnostics, .. }) => return Err(Problem {
+            kind: format!("could not parse ({})", diagnostics.iter().map(|diagnostic| diagnostic.code).collect::<Vec<_>>().join(", ")),
+            detail: diagnostics.iter().map(|diagnostic| diagnostic.render_human(&code, false)).collect(),
+        }),
+        Err(err) => return Err(Problem { kind: "could not parse".to_owned(), detail: format!("{err:?}") }),
+    };
+    if ast.without_spans() != reparsed.without_spans() {
+        return Err(Problem {
+            kind: "parsed to a different ast".to_owned(),
+            detail: format!("{:?}", reparsed.blocks),
+        });
+    }
+    let reprinted = ast_to_code(&reparsed);
+    if reprinted != code {
+        return Err(Problem { kind: "printed differently after parsing".to_owned(), detail: reprinted });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_programs_round_trip() {
+        fuzz_round_trip(DEFAULT_SEED, 300).unwrap();
+    }
+
+    #[test]
+    fn shrink_to_minimal() {
+        let mut rng = StdRng::seed_from_u64(DEFAULT_SEED);
+        let ast = (0..)
+            .map(|_| gen_random_file(&mut rng))
+            .find(|ast| ast_to_code(ast).contains("while ("))
+            .unwrap();
+        let minimal = shrink(ast, |candidate| ast_to_code(candidate).contains("while ("));
+        assert_eq!(minimal.blocks.len(), 1);
+        let code = ast_to_code(&minimal);
+        assert!(code.lines().count() <= 2, "not minimal:\n{code}");
+    }
 }
@@ -0,0 +1,310 @@
+use tel_ast::Ast;
+use tel_ast::Block;
+use tel_ast::Closure;
+use tel_ast::Expr;
+use tel_ast::ExprKind;
+use tel_ast::Invoke;
+use tel_ast::TextPart;
+use tel_common::Span;
+
+/// Repeatedly apply the first simplification after which `fails` still holds, until none is left.
+/// The result is a local minimum, which is usually small enough to see the problem.
+pub fn shrink(mut ast: Ast, fails: impl Fn(&Ast) -> bool) -> Ast {
+    let mut target = 0;
+    while let Some(candidate) = simplify(&ast, target) {
+        if fails(&candidate) {
+            ast = candidate;
+            // the same position may be simplified further
+            target = 0;
+        } else {
+            target += 1;
+        }
+    }
+    ast
+}
+
+/// The ast with the n-th possible simplification applied, or `None` if there are fewer.
+/// Simplifications keep the ast printable, e.g. control flow does not end up as an operand.
+fn simplify(ast: &Ast, target: usize) -> Option<Ast> {
+    let mut ast = ast.clone();
+    let mut shrinker = Shrinker { target, seen: 0, applied: false };
+    shrinker.remove_any(&mut ast.imports);
+    for import in ast.imports.iter_mut() {
+        shrinker.remove_any(&mut import.names);
+    }
+    shrinker.blocks(&mut ast.blocks);
+    shrinker.applied.then_some(ast)
+}
+
+struct Shrinker {
+    target: usize,
+    seen: usize,
+    applied: bool,
+}
+
+impl Shrinker {
+    /// Whether to apply the current simplification opportunity.
+    fn hit(&mut self) -> bool {
+        if self.applied {
+            return false;
+        }
+        let hit = self.seen == self.target;
+        self.seen += 1;
+        self.applied = hit;
+        hit
+    }
+
+    fn remove_any<T: Clone>(&mut self, items: &mut Box<[T]>) {
+        for ix in 0..items.len() {
+            if self.hit() {
+                let mut remaining = items.to_vec();
+                remaining.remove(ix);
+                *items = remaining.into_boxed_slice();
+                return;
+            }
+        }
+    }
+
+    fn blocks(&mut self, blocks: &mut Box<[Block]>) {
+        self.remove_any(blocks);
+        for block in blocks.iter_mut() {
+            self.block(block);
+        }
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        match block {
+            Block::Assigns(assign) => {
+                if assign.dest.len() > 1 {
+                    self.remove_any(&mut assign.dest);
+                }
+                for dest in assign.dest.iter_mut() {
+                    if dest.typ.is_some() && self.hit() {
+                        dest.typ = None;
+                    }
+                }
+                self.expr(&mut assign.value, true);
+            }
+            Block::Expression(expr) => self.expr(expr, true),
+            Block::Return(expr) => {
+                if self.hit() {
+                    *block = Block::Expression(expr.clone());
+                    return;
+                }
+                self.expr(expr, true)
+            }
+            Block::SetField(set) => {
+                if self.hit() {
+                    *block = Block::Expression((*set.value).clone());
+                    return;
+                }
+                self.expr(&mut set.target, false);
+                self.expr(&mut set.value, true);
+            }
+            Block::Struct(strct) => {
+                let mut fields = strct.fields.clone().into_boxed_slice();
+                self.remove_any(&mut fields);
+                strct.fields = fields.into_vec();
+                let mut methods = strct.methods.clone().into_boxed_slice();
+                self.remove_any(&mut methods);
+                strct.methods = methods.into_vec();
+                for method in strct.methods.iter_mut() {
+                    self.closure(&mut method.closure);
+                }
+                self.remove_any(&mut strct.generics);
+            }
+            Block::Enum(enm) => {
+                self.remove_any(&mut enm.variants);
+                self.remove_any(&mut enm.generics);
+            }
+            Block::Interface(interface) => {
+                let mut methods = interface.methods.clone().into_boxed_slice();
+                self.remove_any(&mut methods);
+                interface.methods = methods.into_vec();
+                for method in interface.methods.iter_mut() {
+                    self.remove_any(&mut method.params);
+                }
+            }
+            Block::Impl(imp) => {
+                let mut methods = imp.methods.clone().into_boxed_slice();
+                self.remove_any(&mut methods);
+                imp.methods = methods.into_vec();
+                for method in imp.methods.iter_mut() {
+                    self.closure(&mut method.closure);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr, allow_control: bool) {
+        // replace by a child expression
+        let children = children(expr);
+        for child in children {
+            if (allow_control || !is_control(&child)) && self.hit() {
+                *expr = child;
+                return;
+            }
+        }
+        if !matches!(expr.kind, ExprKind::Int(_)) && self.hit() {
+            *expr = Expr::new(ExprKind::Int(0), Span::default());
+            return;
+        }
+        match &mut expr.kind {
+            ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Text(_) => {}
+            ExprKind::Interpolate(parts) => {
+                for part in parts.iter_mut() {
+                    if let TextPart::Code(code) = part {
+                        self.expr(code, false);
+                    }
+                }
+            }
+            ExprKind::List(items) => {
+                self.remove_any(items);
+                for item in items.iter_mut() {
+                    self.expr(item, true);
+                }
+            }
+            ExprKind::Map(entries) => {
+                self.remove_any(entries);
+                for (key, value) in entries.iter_mut() {
+                    self.expr(key, false);
+                    self.expr(value, true);
+                }
+            }
+            ExprKind::Index(base, index) => {
+                self.expr(base, false);
+                self.expr(index, true);
+            }
+            ExprKind::Propagate(inner) => self.expr(inner, false),
+            ExprKind::BinOp(_, left, right) => {
+                self.expr(left, false);
+                self.expr(right, false);
+            }
+            ExprKind::UnaryOp(_, inner) => self.expr(inner, false),
+            ExprKind::Invoke(invoke) => self.invoke(invoke),
+            ExprKind::Dot(base, invoke) => {
+                self.expr(base, false);
+                self.invoke(invoke);
+            }
+            ExprKind::Closure(closure) => self.closure(closure),
+            ExprKind::If(branches, otherwise) => {
+                if branches.len() > 1 {
+                    self.remove_any(branches);
+                }
+                if otherwise.is_some() && self.hit() {
+                    *otherwise = None;
+                }
+                for (cond, body) in branches.iter_mut() {
+                    self.expr(cond, true);
+                    self.blocks(body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.blocks(otherwise);
+                }
+            }
+            ExprKind::While(cond, body) => {
+                self.expr(cond, true);
+                self.blocks(body);
+            }
+            ExprKind::ForEach(_, iter, body) => {
+                self.expr(iter, true);
+                self.blocks(body);
+            }
+            ExprKind::Match(value, arms, otherwise) => {
+                self.remove_any(arms);
+                if otherwise.is_some() && self.hit() {
+                    *otherwise = None;
+                }
+                self.expr(value, true);
+                for arm in arms.iter_mut() {
+                    if arm.binding.is_some() && self.hit() {
+                        arm.binding = None;
+                    }
+                    self.blocks(&mut arm.body);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.blocks(otherwise);
+                }
+            }
+        }
+    }
+
+    fn invoke(&mut self, invoke: &mut Invoke) {
+        self.remove_any(&mut invoke.args);
+        for arg in invoke.args.iter_mut() {
+            self.expr(arg, true);
+        }
+    }
+
+    fn closure(&mut self, closure: &mut Closure) {
+        self.remove_any(&mut closure.generics);
+        self.remove_any(&mut closure.params);
+        if closure.ret.is_some() && self.hit() {
+            closure.ret = None;
+        }
+        self.blocks(&mut closure.blocks);
+    }
+}
+
+/// Sub-expressions that could replace the expression.
+fn children(expr: &Expr) -> Vec<Expr> {
+    match &expr.kind {
+        ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Text(_) | ExprKind::Closure(_) => vec![],
+        ExprKind::Interpolate(parts) => parts.iter()
+            .filter_map(|part| match part {
+                TextPart::Code(code) => Some(code.clone()),
+                TextPart::Text(_) => None,
+            })
+            .collect(),
+        ExprKind::List(items) => items.to_vec(),
+        ExprKind::Map(entries) => entries.iter()
+            .flat_map(|(key, value)| [key.clone(), value.clone()])
+            .collect(),
+        ExprKind::Index(base, index) => vec![(**base).clone(), (**index).clone()],
+        ExprKind::Propagate(inner) => vec![(**inner).clone()],
+        ExprKind::BinOp(_, left, right) => vec![(**left).clone(), (**right).clone()],
+        ExprKind::UnaryOp(_, inner) => vec![(**inner).clone()],
+        ExprKind::Invoke(invoke) => invoke.args.to_vec(),
+        ExprKind::Dot(base, invoke) => {
+            let mut children = vec![(**base).clone()];
+            children.extend(invoke.args.iter().cloned());
+            children
+        }
+        ExprKind::If(branches, otherwise) => {
+            let mut children = branches.iter().map(|(cond, _)| cond.clone()).collect::<Vec<_>>();
+            for (_, body) in branches {
+                children.extend(body_exprs(body));
+            }
+            children.extend(otherwise.iter().flat_map(|body| body_exprs(body)));
+            children
+        }
+        ExprKind::While(cond, body) => vec_and_body(cond, body),
+        ExprKind::ForEach(_, iter, body) => vec_and_body(iter, body),
+        ExprKind::Match(value, arms, otherwise) => {
+            let mut children = vec![(**value).clone()];
+            for arm in arms {
+                children.extend(body_exprs(&arm.body));
+            }
+            children.extend(otherwise.iter().flat_map(|body| body_exprs(body)));
+            children
+        }
+    }
+}
+
+fn vec_and_body(expr: &Expr, body: &[Block]) -> Vec<Expr> {
+    let mut children = vec![expr.clone()];
+    children.extend(body_exprs(body));
+    children
+}
+
+/// Expression statements in a body, which can replace control flow around them.
+fn body_exprs(body: &[Block]) -> impl Iterator<Item = Expr> + '_ {
+    body.iter().filter_map(|block| match block {
+        Block::Expression(expr) => Some(expr.clone()),
+        _ => None,
+    })
+}
+
+fn is_control(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::If(..) | ExprKind::While(..) | ExprKind::ForEach(..) | ExprKind::Match(..))
+}